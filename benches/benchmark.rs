@@ -37,11 +37,13 @@ impl JsonMesh {
                 self.mesh.indices.to_owned(),
                 self.mesh.positions.to_owned(),
                 self.mesh.normals.to_owned(),
-            ),
+            )
+            .expect("Could not load mesh"),
             Wireframe::new(
                 self.edgesMesh.indices.to_owned(),
                 self.edgesMesh.positions.to_owned(),
-            ),
+            )
+            .expect("Could not load wireframe"),
         )
     }
 }
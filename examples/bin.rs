@@ -1,14 +1,14 @@
 extern crate clap;
 extern crate ctrlc;
-extern crate drawille;
 extern crate log_update;
 extern crate term_size;
 
 use std::f32::consts::PI;
 use std::ffi::OsStr;
-use std::fs::File;
+use std::fs::{read_to_string, File};
 use std::io::stdout;
 use std::io::BufReader;
+use std::io::Write;
 use std::path::Path;
 use std::process;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -17,18 +17,32 @@ use std::thread::sleep;
 use std::time::{Duration, Instant};
 
 use clap::{App, Arg, ArgMatches, SubCommand};
-use drawille::Canvas;
 use log_update::LogUpdate;
 use nalgebra::{Matrix4, Rotation3, Vector3};
 use serde::{Deserialize, Serialize};
 use serde_json;
 
+use mesh_to_svg::dxf_renderer::{screen_space_lines_to_dxf, DxfConfig};
+use mesh_to_svg::error::MeshToSvgError;
 use mesh_to_svg::find_categorized_line_segments;
-use mesh_to_svg::lines::{LineSegmentCategorized, LineVisibility};
+use mesh_to_svg::gcode_renderer::{screen_space_lines_to_gcode, GcodeConfig, GcodeTool};
+use mesh_to_svg::hpgl_renderer::{screen_space_lines_to_hpgl, HpglConfig};
+use mesh_to_svg::json_renderer::{screen_space_lines_to_fitted_json, JsonConfig};
+use mesh_to_svg::lines::LineSegmentCategorized;
 use mesh_to_svg::mesh::{Mesh, Wireframe};
+use mesh_to_svg::options::{RenderOptions, SheetOptions};
+use mesh_to_svg::paper::{DrawingScale, LengthUnit, Orientation, Page, PaperSize};
+use mesh_to_svg::path_order::plot_statistics;
+use mesh_to_svg::pdf_renderer::{screen_space_lines_to_pdf, PdfConfig};
+use mesh_to_svg::raster_renderer::{screen_space_lines_to_png, RasterConfig};
+use mesh_to_svg::renderer::MeshHandle;
 use mesh_to_svg::scene::Scene;
 use mesh_to_svg::svg_renderer::{
-    scale_screen_space_lines, screen_space_lines_to_fitted_svg, SvgConfig,
+    fitted_svg_paths, page_svg_paths, screen_space_lines_to_fitted_svg,
+    screen_space_lines_to_page_svg, SvgConfig, SvgLineConfig, SvgPageConfig,
+};
+use mesh_to_svg::term_renderer::{
+    screen_space_lines_to_terminal, TermCharset, TermColours, TermConfig,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -53,28 +67,42 @@ struct JsonMesh {
 }
 
 trait Meshable {
-    fn to_mesh(&self) -> (Mesh, Option<Wireframe>);
+    fn to_mesh(&self, repair: bool) -> Result<(Mesh, Option<Wireframe>), MeshToSvgError>;
 }
 
 impl Meshable for JsonMesh {
-    fn to_mesh(&self) -> (Mesh, Option<Wireframe>) {
-        (
+    fn to_mesh(&self, repair: bool) -> Result<(Mesh, Option<Wireframe>), MeshToSvgError> {
+        if repair {
+            return Ok((
+                repaired_mesh(
+                    self.mesh.indices.as_deref(),
+                    &self.mesh.positions,
+                    &self.mesh.normals,
+                ),
+                self.edgesMesh
+                    .as_ref()
+                    .map(|edges| repaired_wireframe(edges.indices.as_deref(), &edges.positions)),
+            ));
+        }
+
+        Ok((
             Mesh::new(
                 self.mesh.indices.to_owned(),
                 self.mesh.positions.to_owned(),
                 self.mesh.normals.to_owned(),
-            ),
+            )?,
             self.edgesMesh
                 .as_ref()
-                .map(|edges| Wireframe::new(edges.indices.to_owned(), edges.positions.to_owned())),
-        )
+                .map(|edges| Wireframe::new(edges.indices.to_owned(), edges.positions.to_owned()))
+                .transpose()?,
+        ))
     }
 }
 
 impl Meshable for stl_io::IndexedMesh {
     // @todo - due to the Mesh type needing vertex normals, we have to duplicate facet normals, however
     // this approach is duplicating vertices too
-    fn to_mesh(&self) -> (Mesh, Option<Wireframe>) {
+    fn to_mesh(&self, repair: bool) -> Result<(Mesh, Option<Wireframe>), MeshToSvgError> {
         let mut indices = vec![0; self.faces.len() * 3];
         let mut vertices = vec![0.0; indices.len() * 3];
         let mut normals = vec![0.0; indices.len() * 3];
@@ -94,10 +122,33 @@ impl Meshable for stl_io::IndexedMesh {
             }
         }
 
-        let mesh = Mesh::new(Some(indices), vertices, normals);
+        if repair {
+            return Ok((repaired_mesh(Some(&indices), &vertices, &normals), None));
+        }
+
+        let mesh = Mesh::new(Some(indices), vertices, normals)?;
+
+        Ok((mesh, None))
+    }
+}
+
+/// Fixes what can be safely fixed in a mesh, printing what was wrong with it
+fn repaired_mesh(indices: Option<&[usize]>, vertices: &[f32], normals: &[f32]) -> Mesh {
+    let report = Mesh::validate(indices, vertices, normals);
+    if !report.is_valid() {
+        eprintln!("Repairing mesh:\n{}", report);
+    }
 
-        (mesh, None)
+    Mesh::repair(indices, vertices, normals).0
+}
+
+fn repaired_wireframe(indices: Option<&[usize]>, vertices: &[f32]) -> Wireframe {
+    let report = Wireframe::validate(indices, vertices);
+    if !report.is_valid() {
+        eprintln!("Repairing wireframe:\n{}", report);
     }
+
+    Wireframe::repair(indices, vertices).0
 }
 
 fn main() {
@@ -113,6 +164,187 @@ fn main() {
                 .help("Set file to parse")
                 .required(true),
         )
+        .arg(
+            Arg::with_name("config")
+                .takes_value(true)
+                .short("c")
+                .long("config")
+                .help("Read render options from a json or toml file, which other arguments add to"),
+        )
+        .arg(
+            Arg::with_name("repair")
+                .long("repair")
+                .help("Fix what can be safely fixed in the mesh, printing what was wrong with it"),
+        )
+        .arg(
+            Arg::with_name("optimise_paths")
+                .long("optimise-paths")
+                .help("Order svg paths to minimise pen plotter travel"),
+        )
+        .arg(
+            Arg::with_name("precision")
+                .takes_value(true)
+                .long("precision")
+                .help("Decimal places for svg path coordinates [defaults to full precision]"),
+        )
+        .arg(
+            Arg::with_name("compact_paths")
+                .long("compact-paths")
+                .help("Write svg paths with relative commands and minimal separators"),
+        )
+        .arg(
+            Arg::with_name("fit_curves")
+                .long("fit-curves")
+                .help("Replace runs of svg lines with arcs and béziers, and of dxf lines with arcs"),
+        )
+        .arg(
+            Arg::with_name("curve_tolerance")
+                .takes_value(true)
+                .long("curve-tolerance")
+                .help("Furthest fitted curves may stray from the lines, in svg pixels [default: 0.5]"),
+        )
+        .arg(
+            Arg::with_name("simplify")
+                .takes_value(true)
+                .long("simplify")
+                .possible_values(&["douglas-peucker", "visvalingam"])
+                .help("Drop near collinear points from chained lines"),
+        )
+        .arg(
+            Arg::with_name("simplify_tolerance")
+                .takes_value(true)
+                .long("simplify-tolerance")
+                .help("Furthest simplified lines may move, in screen space units [default: 0.5]"),
+        )
+        .arg(
+            Arg::with_name("min_segment_length")
+                .takes_value(true)
+                .long("min-segment-length")
+                .help("Drop lines shorter than this in screen space units before testing visibility"),
+        )
+        .arg(
+            Arg::with_name("min_object_size")
+                .takes_value(true)
+                .long("min-object-size")
+                .help("Leave out objects smaller than this in screen space units"),
+        )
+        .arg(
+            Arg::with_name("frame")
+                .long("frame")
+                .help("Draw a border frame around svg output"),
+        )
+        .arg(
+            Arg::with_name("title")
+                .takes_value(true)
+                .long("title")
+                .help("Add a title block to svg output with this title"),
+        )
+        .arg(
+            Arg::with_name("part_number")
+                .takes_value(true)
+                .long("part-number")
+                .help("Part number for the title block"),
+        )
+        .arg(
+            Arg::with_name("author")
+                .takes_value(true)
+                .long("author")
+                .help("Author for the title block"),
+        )
+        .arg(
+            Arg::with_name("date")
+                .takes_value(true)
+                .long("date")
+                .help("Date for the title block"),
+        )
+        .arg(
+            Arg::with_name("view_label")
+                .takes_value(true)
+                .long("view-label")
+                .help("Label to write under the view"),
+        )
+        .arg(
+            Arg::with_name("dimensions")
+                .long("dimensions")
+                .help("Dimension the overall width and height of orthographic views"),
+        )
+        .arg(
+            Arg::with_name("dimension")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .long("dimension")
+                .help("Dimension between two mesh points, given as x1,y1,z1,x2,y2,z2"),
+        )
+        .arg(
+            Arg::with_name("fill")
+                .takes_value(true)
+                .long("fill")
+                .possible_values(&["flat", "silhouette"])
+                .help("Fill visible faces beneath the svg lines, flat shaded or as a solid silhouette"),
+        )
+        .arg(
+            Arg::with_name("fill_colour")
+                .takes_value(true)
+                .long("fill-colour")
+                .help("Css colour of lit faces or of the silhouette [default: #f0f0f0]"),
+        )
+        .arg(
+            Arg::with_name("feature_lines")
+                .long("feature-lines")
+                .help("Draw ridges, valleys and suggestive contours of smooth surfaces"),
+        )
+        .arg(
+            Arg::with_name("smooth_silhouettes")
+                .long("smooth-silhouettes")
+                .help("Trace silhouettes of curved surfaces within triangles instead of along their edges"),
+        )
+        .arg(
+            Arg::with_name("hatch")
+                .long("hatch")
+                .help("Shade visible faces with hatch lines, densest where facing away from the light"),
+        )
+        .arg(
+            Arg::with_name("hatch_spacing")
+                .takes_value(true)
+                .long("hatch-spacing")
+                .help("Distance between hatch lines on the darkest faces [default: 4]"),
+        )
+        .arg(
+            Arg::with_name("hidden_dashes")
+                .long("hidden-dashes")
+                .help("Dash obscured lines as ISO 128 hidden lines instead of drawing them solid"),
+        )
+        .arg(
+            Arg::with_name("dash_offset")
+                .takes_value(true)
+                .long("dash-offset")
+                .help("Distance into the dash pattern that obscured lines start at"),
+        )
+        .arg(
+            Arg::with_name("linecap")
+                .takes_value(true)
+                .long("linecap")
+                .possible_values(&["butt", "round", "square"])
+                .help("Line ends of the svg lines [default: round]"),
+        )
+        .arg(
+            Arg::with_name("linejoin")
+                .takes_value(true)
+                .long("linejoin")
+                .possible_values(&["miter", "round", "bevel"])
+                .help("Corners of the svg lines [default: round]"),
+        )
+        .arg(
+            Arg::with_name("group_layers")
+                .long("group-layers")
+                .help("Group svg paths into layers per object and edge class"),
+        )
+        .arg(
+            Arg::with_name("embed_style")
+                .long("embed-style")
+                .help("Embed a style block with a rule per class in grouped svgs"),
+        )
         .subcommand(
             SubCommand::with_name("term")
                 .about("output mesh to terminal")
@@ -136,6 +368,105 @@ fn main() {
                     Arg::with_name("animate")
                         .long("animate")
                         .help("Animate the terminal"),
+                )
+                .arg(
+                    Arg::with_name("obscured")
+                        .long("obscured")
+                        .help("Draw obscured lines too"),
+                )
+                .arg(
+                    Arg::with_name("colour")
+                        .long("colour")
+                        .help("Colour lines by visibility and edge kind"),
+                )
+                .arg(
+                    Arg::with_name("box")
+                        .long("box")
+                        .help("Draw with box drawing characters instead of braille"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("svg")
+                .about("output mesh as SVG laid out on paper, so it prints to size")
+                .arg(
+                    Arg::with_name("paper")
+                        .takes_value(true)
+                        .long("paper")
+                        .help("Paper size, one of a4, a3 or letter [defaults to a4]"),
+                )
+                .arg(
+                    Arg::with_name("portrait")
+                        .long("portrait")
+                        .help("Portrait orientation [defaults to landscape]"),
+                )
+                .arg(
+                    Arg::with_name("scale")
+                        .takes_value(true)
+                        .long("scale")
+                        .help("Exact drawing scale, e.g. 1:2 [defaults to fit to page]"),
+                )
+                .arg(
+                    Arg::with_name("units")
+                        .takes_value(true)
+                        .long("units")
+                        .help("Svg units, either mm or in [defaults to mm]"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("dxf").about("output mesh as DXF for CAD packages"))
+        .subcommand(
+            SubCommand::with_name("pdf")
+                .about("output mesh as a vector PDF")
+                .arg(
+                    Arg::with_name("paper")
+                        .takes_value(true)
+                        .long("paper")
+                        .help("Paper size, one of a4, a3 or letter [defaults to a4]"),
+                )
+                .arg(
+                    Arg::with_name("portrait")
+                        .long("portrait")
+                        .help("Portrait orientation [defaults to landscape]"),
+                )
+                .arg(
+                    Arg::with_name("scale")
+                        .takes_value(true)
+                        .long("scale")
+                        .help("Exact drawing scale, e.g. 1:2 [defaults to fit to page]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("png")
+                .about("output mesh as a PNG image")
+                .arg(
+                    Arg::with_name("dpi")
+                        .takes_value(true)
+                        .long("dpi")
+                        .help("Output resolution [defaults to 96]"),
+                )
+                .arg(
+                    Arg::with_name("background")
+                        .takes_value(true)
+                        .long("background")
+                        .help("Background css colour [defaults to white]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("json")
+                .about("output the categorized lines as JSON")
+                .arg(
+                    Arg::with_name("chain")
+                        .long("chain")
+                        .help("Output chained polylines instead of individual segments"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("hpgl").about("output mesh as HPGL on an A4 sheet"))
+        .subcommand(
+            SubCommand::with_name("gcode")
+                .about("output mesh as G-code on an A4 sheet")
+                .arg(
+                    Arg::with_name("laser")
+                        .long("laser")
+                        .help("Switch a laser on and off instead of lifting a pen"),
                 ),
         )
         .get_matches();
@@ -144,6 +475,7 @@ fn main() {
         .value_of("file")
         .expect("You must set a file argument!");
 
+    let repair = arg_matches.is_present("repair");
     let (mesh, wireframe) = match get_extension_from_filename(&file_path) {
         Some("json") => {
             let file = File::open(file_path).expect("Could not open file");
@@ -151,36 +483,345 @@ fn main() {
             let mesh_json: JsonMesh =
                 serde_json::from_reader(reader).expect("Could not parse JSON mesh file");
 
-            mesh_json.to_mesh()
+            mesh_json.to_mesh(repair)
         }
         Some("stl") => {
             let mut file = File::open(file_path).expect("Could not open file");
             let stl = stl_io::read_stl(&mut file).unwrap();
 
-            stl.to_mesh()
+            stl.to_mesh(repair)
         }
         Some(_) | None => panic!("Unsupported file extension"),
-    };
+    }
+    .unwrap_or_else(|error| panic!("Could not load mesh, try --repair: {}", error));
 
     let scene = Scene::new_test();
 
-    let svg_config = SvgConfig::new_default(scene.width as i32, scene.height as i32);
+    let mut options = match arg_matches.value_of("config") {
+        Some(config_path) => read_options(config_path),
+        None => RenderOptions::default(),
+    };
+    apply_arguments(&mut options, &arg_matches);
+
+    let renderer = options
+        .to_renderer(scene.width as i32, scene.height as i32)
+        .unwrap_or_else(|error| panic!("{}", error));
 
-    let segments = find_categorized_line_segments(&mesh, &wireframe, &scene);
+    let mesh = MeshHandle::from_mesh(mesh, wireframe);
+    let segments = renderer
+        .find_lines(&mesh, &scene)
+        .unwrap_or_else(|error| panic!("{}", error));
+    let svg_config = renderer
+        .svg_config_for_lines(&mesh, &scene, &segments)
+        .unwrap_or_else(|error| panic!("{}", error));
 
     if let Some(term_subcommand) = arg_matches.subcommand_matches("term") {
         if term_subcommand.is_present("animate") {
-            animate(&mesh, &wireframe, &term_subcommand);
+            animate(&mesh.mesh, &mesh.wireframe, &term_subcommand);
         } else {
             let terminal_drawing = draw_terminal(segments, &scene, &term_subcommand);
             println!("{}", terminal_drawing);
         }
+    } else if let Some(svg_subcommand) = arg_matches.subcommand_matches("svg") {
+        let svg = draw_page_svg(
+            &segments,
+            &scene,
+            &svg_config,
+            renderer.page.as_ref(),
+            svg_subcommand,
+        );
+        println!("{}", svg);
+    } else if arg_matches.subcommand_matches("dxf").is_some() {
+        let mut dxf_config = DxfConfig::new_default();
+
+        // the same tolerance in world units as in screen space units
+        dxf_config.arc_tolerance = svg_config
+            .curve_fit
+            .as_ref()
+            .map(|curve_fit| curve_fit.tolerance / scene.screen_space_scale());
+
+        let dxf = screen_space_lines_to_dxf(&segments, &scene, &dxf_config)
+            .unwrap_or_else(|error| panic!("{}", error));
+        println!("{}", dxf);
+    } else if let Some(pdf_subcommand) = arg_matches.subcommand_matches("pdf") {
+        let pdf = draw_pdf(&segments, &scene, pdf_subcommand);
+        stdout().write_all(&pdf).expect("Could not write pdf");
+    } else if let Some(png_subcommand) = arg_matches.subcommand_matches("png") {
+        let png = draw_png(&segments, &svg_config, png_subcommand);
+        stdout().write_all(&png).expect("Could not write png");
+    } else if let Some(json_subcommand) = arg_matches.subcommand_matches("json") {
+        let json_config = JsonConfig {
+            chain_lines: json_subcommand.is_present("chain"),
+        };
+        let json = screen_space_lines_to_fitted_json(&segments, &svg_config, &json_config)
+            .unwrap_or_else(|error| panic!("{}", error));
+        println!("{}", json);
+    } else if arg_matches.subcommand_matches("hpgl").is_some() {
+        let hpgl = draw_hpgl(&segments, &scene);
+        println!("{}", hpgl);
+    } else if let Some(gcode_subcommand) = arg_matches.subcommand_matches("gcode") {
+        let gcode = draw_gcode(&segments, &scene, gcode_subcommand);
+        println!("{}", gcode);
     } else {
-        let svg = screen_space_lines_to_fitted_svg(&segments, &svg_config);
+        if svg_config.optimise_path_order {
+            let paths = match &renderer.page {
+                Some(page_config) => page_svg_paths(&segments, &scene, &svg_config, page_config)
+                    .unwrap_or_else(|error| panic!("{}", error)),
+                None => fitted_svg_paths(&segments, &svg_config),
+            };
+            let statistics = plot_statistics(&paths);
+            eprintln!(
+                "draw length: {}, travel length: {}",
+                statistics.draw_length, statistics.travel_length
+            );
+        }
+
+        let svg = match &renderer.page {
+            Some(page_config) => {
+                screen_space_lines_to_page_svg(&segments, &scene, &svg_config, page_config)
+                    .unwrap_or_else(|error| panic!("{}", error))
+            }
+            None => screen_space_lines_to_fitted_svg(&segments, &svg_config),
+        };
         println!("{}", svg);
     }
 }
 
+// A4 landscape in mm. The margin is split between opposite edges, so lines stay 5mm clear of each
+fn plotter_page_config(scene: &Scene) -> SvgConfig {
+    SvgConfig {
+        width: 297,
+        height: 210,
+        margin: 10,
+        ..SvgConfig::new_default(scene.width as i32, scene.height as i32)
+    }
+}
+
+fn parse_dimension_points(points: &str) -> [f32; 6] {
+    let values: Vec<f32> = points
+        .split(',')
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .expect("dimension values must be numbers!")
+        })
+        .collect();
+
+    if values.len() != 6 {
+        panic!("dimensions must be given as x1,y1,z1,x2,y2,z2!");
+    }
+
+    [
+        values[0], values[1], values[2], values[3], values[4], values[5],
+    ]
+}
+
+fn read_options(config_path: &str) -> RenderOptions {
+    let config = read_to_string(config_path).expect("Could not read config file");
+
+    match get_extension_from_filename(config_path) {
+        Some("json") => serde_json::from_str(&config)
+            .unwrap_or_else(|error| panic!("Could not parse json config: {}", error)),
+        Some("toml") => toml::from_str(&config)
+            .unwrap_or_else(|error| panic!("Could not parse toml config: {}", error)),
+        Some(_) | None => panic!("Config files must be json or toml"),
+    }
+}
+
+/// Adds the options set by arguments to those read from a config file
+fn apply_arguments(options: &mut RenderOptions, matches: &ArgMatches) {
+    options.optimise_path_order |= matches.is_present("optimise_paths");
+    options.group_layers |= matches.is_present("group_layers");
+    options.embed_style |= matches.is_present("embed_style");
+    if let Some(precision) = matches.value_of("precision") {
+        options.precision = Some(precision.parse().expect("Precision must be a whole number"));
+    }
+    if matches.is_present("compact_paths") {
+        options.relative_commands = true;
+        options.compact_paths = true;
+    }
+    options.fit_curves |= matches.is_present("fit_curves");
+    if let Some(tolerance) = matches.value_of("curve_tolerance") {
+        options.curve_tolerance =
+            Some(tolerance.parse().expect("Curve tolerance must be a number"));
+    }
+    options.smooth_silhouettes |= matches.is_present("smooth_silhouettes");
+    if let Some(length) = matches.value_of("min_segment_length") {
+        options.min_segment_length = Some(length.parse().expect("Minimum length must be a number"));
+    }
+    if let Some(size) = matches.value_of("min_object_size") {
+        options.min_object_size = Some(size.parse().expect("Minimum size must be a number"));
+    }
+    options.obscured.iso_dashes |= matches.is_present("hidden_dashes");
+    if let Some(dash_offset) = matches.value_of("dash_offset") {
+        options.obscured.dash_offset =
+            Some(dash_offset.parse().expect("Dash offset must be a number"));
+    }
+    for line_options in &mut [&mut options.visible, &mut options.obscured] {
+        if let Some(linecap) = matches.value_of("linecap") {
+            line_options.linecap = Some(linecap.to_owned());
+        }
+        if let Some(linejoin) = matches.value_of("linejoin") {
+            line_options.linejoin = Some(linejoin.to_owned());
+        }
+    }
+
+    if matches.is_present("frame")
+        || matches.is_present("title")
+        || matches.is_present("view_label")
+    {
+        // only framed from the command line when asked to be
+        let sheet = options.sheet.get_or_insert_with(|| SheetOptions {
+            border: Some(false),
+            ..SheetOptions::default()
+        });
+
+        if matches.is_present("frame") {
+            sheet.border = Some(true);
+        }
+        if let Some(title) = matches.value_of("title") {
+            sheet.title = Some(title.to_owned());
+        }
+        if let Some(part_number) = matches.value_of("part_number") {
+            sheet.part_number = Some(part_number.to_owned());
+        }
+        if let Some(author) = matches.value_of("author") {
+            sheet.author = Some(author.to_owned());
+        }
+        if let Some(date) = matches.value_of("date") {
+            sheet.date = Some(date.to_owned());
+        }
+        if let Some(view_label) = matches.value_of("view_label") {
+            sheet.view_label = Some(view_label.to_owned());
+        }
+    }
+
+    if matches.is_present("dimensions") || matches.is_present("dimension") {
+        let dimensions = options.dimensions.get_or_insert_with(Default::default);
+
+        dimensions.overall |= matches.is_present("dimensions");
+        if let Some(points) = matches.values_of("dimension") {
+            dimensions.points.extend(points.map(parse_dimension_points));
+        }
+    }
+
+    if let Some(mode) = matches.value_of("fill") {
+        let fill = options.fill.get_or_insert_with(Default::default);
+
+        fill.mode = Some(mode.to_owned());
+        if let Some(colour) = matches.value_of("fill_colour") {
+            fill.colour = Some(colour.to_owned());
+        }
+    }
+
+    if matches.is_present("hatch") {
+        let hatch = options.hatch.get_or_insert_with(Default::default);
+
+        if let Some(spacing) = matches.value_of("hatch_spacing") {
+            hatch.spacing = Some(spacing.parse().expect("Hatch spacing must be a number"));
+        }
+    }
+
+    if matches.is_present("feature_lines") {
+        options.feature_lines.get_or_insert_with(Default::default);
+    }
+
+    if let Some(method) = matches.value_of("simplify") {
+        let simplify = options.simplify.get_or_insert_with(Default::default);
+
+        simplify.method = Some(method.to_owned());
+        if let Some(tolerance) = matches.value_of("simplify_tolerance") {
+            simplify.tolerance = Some(
+                tolerance
+                    .parse()
+                    .expect("Simplify tolerance must be a number"),
+            );
+        }
+    }
+}
+
+fn draw_pdf(segments: &[LineSegmentCategorized], scene: &Scene, matches: &ArgMatches) -> Vec<u8> {
+    let mut pdf_config = PdfConfig::new_default();
+
+    apply_page_arguments(&mut pdf_config.page, &mut pdf_config.scale, matches);
+
+    screen_space_lines_to_pdf(segments, scene, &pdf_config)
+        .unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn draw_page_svg(
+    segments: &[LineSegmentCategorized],
+    scene: &Scene,
+    svg_config: &SvgConfig,
+    configured_page: Option<&SvgPageConfig>,
+    matches: &ArgMatches,
+) -> String {
+    let mut page_config = configured_page
+        .cloned()
+        .unwrap_or_else(SvgPageConfig::new_default);
+
+    apply_page_arguments(&mut page_config.page, &mut page_config.scale, matches);
+
+    if let Some(units) = matches.value_of("units") {
+        page_config.units = LengthUnit::from_name(units).expect("units must be mm or in!");
+    }
+
+    screen_space_lines_to_page_svg(segments, scene, svg_config, &page_config)
+        .unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn apply_page_arguments(page: &mut Page, scale: &mut DrawingScale, matches: &ArgMatches) {
+    if let Some(paper) = matches.value_of("paper") {
+        page.size = PaperSize::from_name(paper).expect("Unsupported paper size!");
+    }
+
+    if matches.is_present("portrait") {
+        page.orientation = Orientation::Portrait;
+    }
+
+    if let Some(ratio) = matches.value_of("scale") {
+        *scale = DrawingScale::from_ratio(ratio).expect("scale must be a ratio like 1:2!");
+    }
+}
+
+fn draw_png(
+    segments: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+    matches: &ArgMatches,
+) -> Vec<u8> {
+    let mut raster_config = RasterConfig::new_default();
+
+    if let Some(dpi) = matches.value_of("dpi") {
+        raster_config.dpi = dpi.parse::<f32>().expect("dpi must be a number!");
+    }
+
+    if let Some(background) = matches.value_of("background") {
+        raster_config.background = background.to_owned();
+    }
+
+    screen_space_lines_to_png(segments, svg_config, &raster_config)
+        .unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn draw_hpgl(segments: &[LineSegmentCategorized], scene: &Scene) -> String {
+    screen_space_lines_to_hpgl(
+        segments,
+        &plotter_page_config(scene),
+        &HpglConfig::new_default(),
+    )
+}
+
+fn draw_gcode(segments: &[LineSegmentCategorized], scene: &Scene, matches: &ArgMatches) -> String {
+    let mut gcode_config = GcodeConfig::new_default();
+
+    if matches.is_present("laser") {
+        gcode_config.tool = GcodeTool::Laser;
+    }
+
+    screen_space_lines_to_gcode(segments, &plotter_page_config(scene), &gcode_config)
+}
+
 fn get_extension_from_filename(filename: &str) -> Option<&str> {
     Path::new(filename).extension().and_then(OsStr::to_str)
 }
@@ -192,13 +833,13 @@ fn draw_terminal(
 ) -> String {
     let (w, _) = term_size::dimensions().unwrap_or((100, 0));
 
-    // not entirely sure why the adjustment is needed, the canvas is 2x term as the braille char are
-    // at double density, but the `- 1` is inexplicable
+    // the canvas is 2x term as the braille char are at double density, less one to leave the last
+    // column free so that the terminal doesn't wrap
     let term_canvas_width: i32 = w as i32 * 2 - 1;
 
     let width: i32 = match matches.value_of("output_width") {
         Some(w) => w.parse::<i32>().expect("output_height must be a number!"),
-        None => term_canvas_width as i32,
+        None => term_canvas_width,
     };
 
     let height: i32 = match matches.value_of("output_height") {
@@ -206,40 +847,29 @@ fn draw_terminal(
         None => (scene.height / scene.width * width as f32) as i32,
     };
 
-    let svg_config = SvgConfig::new(
-        scene.width as i32,
-        scene.height as i32,
-        Some(width),
-        Some(height),
-        Some(5),
-        None,
-        None,
-        None,
-        None,
-        None,
-        Some(false),
-    );
-
-    let lines: Vec<LineSegmentCategorized> = scale_screen_space_lines(&segments, &svg_config)
-        .into_iter()
-        .filter(|line| match line.visibility {
-            LineVisibility::VISIBLE => true,
-            LineVisibility::OBSCURED => false,
-        })
-        .collect();
+    let svg_config = SvgConfig {
+        width,
+        height,
+        margin: 0,
+        obscured: match matches.is_present("obscured") {
+            true => Some(SvgLineConfig::new_default_obscured()),
+            false => None,
+        },
+        fit_lines: false,
+        ..SvgConfig::new_default(scene.width as i32, scene.height as i32)
+    };
 
-    let mut canvas = Canvas::new(svg_config.width as u32, svg_config.height as u32);
+    let mut term_config = TermConfig::new_default();
 
-    for line in lines {
-        canvas.line(
-            line.line_segment.from.x as u32,
-            line.line_segment.from.y as u32,
-            line.line_segment.to.x as u32,
-            line.line_segment.to.y as u32,
-        );
+    if matches.is_present("box") {
+        term_config.charset = TermCharset::BoxDrawing;
+    }
+
+    if matches.is_present("colour") {
+        term_config.colours = Some(TermColours::new_default());
     }
 
-    canvas.frame()
+    screen_space_lines_to_terminal(&segments, &svg_config, &term_config)
 }
 
 fn animate(mesh: &Mesh, wireframe: &Option<Wireframe>, matches: &ArgMatches) {
@@ -267,7 +897,8 @@ fn animate(mesh: &Mesh, wireframe: &Option<Wireframe>, matches: &ArgMatches) {
         scene.mesh_world_matrix *= &rotation;
 
         let start = Instant::now();
-        let segments = find_categorized_line_segments(&mesh, &wireframe, &scene);
+        let segments = find_categorized_line_segments(&mesh, &wireframe, &scene)
+            .unwrap_or_else(|error| panic!("{}", error));
         let terminal_drawing = draw_terminal(segments, &scene, &matches);
         let duration = start.elapsed();
 
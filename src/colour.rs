@@ -0,0 +1,206 @@
+/// An 8 bit per channel colour parsed from the css colour strings used for svg strokes, so that
+/// non-svg renderers can honour the same configuration
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Colour {
+    pub const BLACK: Colour = Colour::rgb(0, 0, 0);
+    pub const WHITE: Colour = Colour::rgb(255, 255, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Colour {
+        Colour { r, g, b, a: 255 }
+    }
+
+    /// Supports hex (`#rgb`, `#rrggbb`, `#rrggbbaa`), `rgb()`/`rgba()` and the basic named colours
+    pub fn parse(css: &str) -> Option<Colour> {
+        let css = css.trim().to_lowercase();
+
+        if let Some(hex) = css.strip_prefix('#') {
+            return parse_hex(hex);
+        }
+
+        if let Some(arguments) = css
+            .strip_prefix("rgba(")
+            .or_else(|| css.strip_prefix("rgb("))
+        {
+            return parse_functional(arguments.strip_suffix(')')?);
+        }
+
+        named(&css)
+    }
+
+    /// Each channel scaled to 0..1, as used by pdf
+    pub fn to_unit_rgb(&self) -> (f32, f32, f32) {
+        (
+            self.r as f32 / 255.0,
+            self.g as f32 / 255.0,
+            self.b as f32 / 255.0,
+        )
+    }
+
+    /// Hex notation, with the alpha channel only when it isn't opaque
+    pub fn to_hex(&self) -> String {
+        match self.a {
+            255 => format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b),
+            a => format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, a),
+        }
+    }
+
+    /// Linear interpolation of each channel towards another colour
+    pub fn lerp(&self, other: &Colour, t: f32) -> Colour {
+        let channel =
+            |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+
+        Colour {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+            a: channel(self.a, other.a),
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Colour> {
+    let channel = |i: usize, width: usize| -> Option<u8> {
+        let value = u8::from_str_radix(hex.get(i * width..(i + 1) * width)?, 16).ok()?;
+        // single digit channels are repeated, e.g. #f80 is #ff8800
+        Some(if width == 1 { value * 17 } else { value })
+    };
+
+    match hex.len() {
+        3 => Some(Colour::rgb(channel(0, 1)?, channel(1, 1)?, channel(2, 1)?)),
+        6 => Some(Colour::rgb(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+        8 => Some(Colour {
+            r: channel(0, 2)?,
+            g: channel(1, 2)?,
+            b: channel(2, 2)?,
+            a: channel(3, 2)?,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_functional(arguments: &str) -> Option<Colour> {
+    let values: Vec<&str> = arguments.split(',').map(|value| value.trim()).collect();
+
+    if values.len() < 3 || values.len() > 4 {
+        return None;
+    }
+
+    let channel = |value: &str| -> Option<u8> {
+        let parsed: f32 = value.parse().ok()?;
+        Some(parsed.clamp(0.0, 255.0).round() as u8)
+    };
+
+    let alpha = match values.get(3) {
+        Some(value) => {
+            let parsed: f32 = value.parse().ok()?;
+            (parsed.clamp(0.0, 1.0) * 255.0).round() as u8
+        }
+        None => 255,
+    };
+
+    Some(Colour {
+        r: channel(values[0])?,
+        g: channel(values[1])?,
+        b: channel(values[2])?,
+        a: alpha,
+    })
+}
+
+fn named(name: &str) -> Option<Colour> {
+    let colour = match name {
+        "black" => Colour::BLACK,
+        "white" => Colour::WHITE,
+        "grey" | "gray" => Colour::rgb(128, 128, 128),
+        "darkgrey" | "darkgray" => Colour::rgb(169, 169, 169),
+        "lightgrey" | "lightgray" => Colour::rgb(211, 211, 211),
+        "silver" => Colour::rgb(192, 192, 192),
+        "red" => Colour::rgb(255, 0, 0),
+        "green" => Colour::rgb(0, 128, 0),
+        "blue" => Colour::rgb(0, 0, 255),
+        "yellow" => Colour::rgb(255, 255, 0),
+        "orange" => Colour::rgb(255, 165, 0),
+        "purple" => Colour::rgb(128, 0, 128),
+        "transparent" => Colour {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        },
+        _ => return None,
+    };
+
+    Some(colour)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(Colour::parse("#f80"), Some(Colour::rgb(255, 136, 0)));
+        assert_eq!(Colour::parse("#FF8800"), Some(Colour::rgb(255, 136, 0)));
+        assert_eq!(
+            Colour::parse("#ff880080"),
+            Some(Colour {
+                r: 255,
+                g: 136,
+                b: 0,
+                a: 128
+            })
+        );
+        assert_eq!(Colour::parse("#ff88"), None);
+        assert_eq!(Colour::parse("#gg8800"), None);
+    }
+
+    #[test]
+    fn parses_functional() {
+        assert_eq!(
+            Colour::parse("rgb(0, 128, 255)"),
+            Some(Colour::rgb(0, 128, 255))
+        );
+        assert_eq!(
+            Colour::parse(" RGBA(300, -5, 10, 0.5) "),
+            Some(Colour {
+                r: 255,
+                g: 0,
+                b: 10,
+                a: 128
+            })
+        );
+        assert_eq!(Colour::parse("rgb(0, 128)"), None);
+        assert_eq!(Colour::parse("rgb(0, 128, 255"), None);
+        assert_eq!(Colour::parse("rgb(a, b, c)"), None);
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!(Colour::parse("Grey"), Colour::parse("gray"));
+        assert_eq!(Colour::parse("black"), Some(Colour::BLACK));
+        assert_eq!(Colour::parse("transparent").map(|colour| colour.a), Some(0));
+        assert_eq!(Colour::parse("blurple"), None);
+        assert_eq!(Colour::parse(""), None);
+    }
+
+    #[test]
+    fn writes_hex() {
+        assert_eq!(Colour::rgb(255, 136, 0).to_hex(), "#ff8800");
+        assert_eq!(Colour::parse("#ff880080").unwrap().to_hex(), "#ff880080");
+    }
+
+    #[test]
+    fn interpolates() {
+        let grey = Colour::BLACK.lerp(&Colour::WHITE, 0.5);
+
+        assert_eq!(grey, Colour::rgb(128, 128, 128));
+        assert_eq!(Colour::BLACK.lerp(&Colour::WHITE, 0.0), Colour::BLACK);
+        assert_eq!(Colour::BLACK.lerp(&Colour::WHITE, 1.0), Colour::WHITE);
+    }
+}
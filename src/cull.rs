@@ -0,0 +1,221 @@
+use na::{distance, Point2, Point3};
+
+use crate::lines::{LineSegment2, ProjectedLine, ProjectedSplitLine};
+use crate::mesh::Mesh;
+use crate::scene::Scene;
+
+/// Thresholds below which detail is dropped before the visibility test, in screen space units.
+/// Both are off at zero
+pub struct CullConfig {
+    /// lines shorter than this are dropped, and pieces of longer lines shorter than this are merged
+    /// into their neighbours
+    pub min_segment_length: f32,
+    /// objects whose projected bounding box is smaller than this in both directions are left out
+    /// entirely, both as lines and as occluders of other objects
+    pub min_object_size: f32,
+}
+
+impl CullConfig {
+    pub fn new_default() -> CullConfig {
+        CullConfig {
+            min_segment_length: 0.0,
+            min_object_size: 0.0,
+        }
+    }
+}
+
+/// Whether the mesh's projected bounding box is smaller than the minimum size. The corners of the
+/// mesh space box are projected rather than every point, which can only overestimate the size
+pub fn is_too_small(mesh: &Mesh, scene: &Scene, min_size: f32) -> bool {
+    if min_size <= 0.0 {
+        return false;
+    }
+
+    let (min, max) = match mesh.bounding_box() {
+        Some(bounds) => bounds,
+        None => return true,
+    };
+
+    let corners: Vec<Point2<f32>> = (0..8)
+        .map(|corner| {
+            scene.project_point(&Point3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            ))
+        })
+        .collect();
+
+    let extent = |axis: fn(&Point2<f32>) -> f32| {
+        let values = corners.iter().map(axis);
+        values.clone().fold(f32::NEG_INFINITY, f32::max) - values.fold(f32::INFINITY, f32::min)
+    };
+
+    extent(|point| point.x) < min_size && extent(|point| point.y) < min_size
+}
+
+/// Drops projected lines shorter than the minimum length
+pub fn cull_short_lines(lines: &mut Vec<ProjectedLine>, min_length: f32) {
+    if min_length <= 0.0 {
+        return;
+    }
+
+    lines.retain(|line| length(&line.screen_space) >= min_length);
+}
+
+/// Merges pieces of split lines shorter than the minimum length into the piece before them, so
+/// that each line keeps its full extent with fewer pieces to test for visibility. Pieces at the
+/// start of a line have nothing before them, so they take in the pieces after them until they're
+/// long enough
+pub fn merge_short_pieces(split_lines: &mut [ProjectedSplitLine], min_length: f32) {
+    if min_length <= 0.0 {
+        return;
+    }
+
+    for split_line in split_lines {
+        let mut merged: Vec<LineSegment2> =
+            Vec::with_capacity(split_line.split_screen_space_lines.len());
+
+        for piece in &split_line.split_screen_space_lines {
+            match merged.last_mut() {
+                Some(last) if length(piece) < min_length || length(last) < min_length => {
+                    last.to = piece.to;
+                }
+                _ => merged.push(*piece),
+            }
+        }
+
+        split_line.split_screen_space_lines = merged;
+    }
+}
+
+fn length(line: &LineSegment2) -> f32 {
+    distance(&line.from, &line.to)
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Matrix4;
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineSegment3};
+
+    // maps x and y from -10 to 10 across a 200 by 200 canvas, so one unit is ten pixels
+    fn front_scene() -> Scene {
+        #[rustfmt::skip]
+        let projection_matrix = Matrix4::new(
+            0.1, 0.0,  0.0, 0.0,
+            0.0, 0.1,  0.0, 0.0,
+            0.0, 0.0, -0.1, 0.0,
+            0.0, 0.0,  0.0, 1.0,
+        );
+
+        Scene::new(
+            200.0,
+            200.0,
+            Matrix4::identity(),
+            projection_matrix,
+            Matrix4::identity(),
+        )
+    }
+
+    fn triangle(width: f32, height: f32) -> Mesh {
+        Mesh::new(
+            Some(vec![0, 1, 2]),
+            vec![0.0, 0.0, 0.0, width, 0.0, 0.0, 0.0, height, 0.0],
+            vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+        )
+        .unwrap()
+    }
+
+    fn piece(from: f32, to: f32) -> LineSegment2 {
+        LineSegment2 {
+            from: Point2::new(from, 0.0),
+            to: Point2::new(to, 0.0),
+        }
+    }
+
+    fn projected(length: f32) -> ProjectedLine {
+        ProjectedLine {
+            screen_space: piece(0.0, length),
+            mesh_space: LineSegment3 {
+                from: Point3::origin(),
+                to: Point3::new(length, 0.0, 0.0),
+            },
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    fn merged(pieces: &[(f32, f32)], min_length: f32) -> Vec<(f32, f32)> {
+        let mut split_lines = vec![ProjectedSplitLine {
+            projected_line: projected(pieces[pieces.len() - 1].1),
+            split_screen_space_lines: pieces.iter().map(|&(from, to)| piece(from, to)).collect(),
+        }];
+
+        merge_short_pieces(&mut split_lines, min_length);
+
+        split_lines[0]
+            .split_screen_space_lines
+            .iter()
+            .map(|piece| (piece.from.x, piece.to.x))
+            .collect()
+    }
+
+    #[test]
+    fn objects_are_too_small_in_both_directions() {
+        let scene = front_scene();
+
+        assert!(is_too_small(&triangle(0.5, 0.5), &scene, 6.0));
+        // ten pixels wide
+        assert!(!is_too_small(&triangle(1.0, 0.5), &scene, 6.0));
+        assert!(!is_too_small(&triangle(0.5, 1.0), &scene, 6.0));
+        // off at zero
+        assert!(!is_too_small(&triangle(0.0, 0.0), &scene, 0.0));
+    }
+
+    #[test]
+    fn drops_short_lines() {
+        let mut lines = vec![projected(1.0), projected(2.0), projected(3.0)];
+
+        cull_short_lines(&mut lines, 2.0);
+
+        let lengths: Vec<f32> = lines
+            .iter()
+            .map(|line| length(&line.screen_space))
+            .collect();
+        assert_eq!(lengths, vec![2.0, 3.0]);
+
+        cull_short_lines(&mut lines, 0.0);
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn merges_short_pieces_into_the_piece_before() {
+        assert_eq!(
+            merged(&[(0.0, 5.0), (5.0, 6.0), (6.0, 11.0), (11.0, 12.0)], 2.0),
+            vec![(0.0, 6.0), (6.0, 12.0)]
+        );
+    }
+
+    #[test]
+    fn short_first_pieces_take_in_the_pieces_after() {
+        assert_eq!(
+            merged(&[(0.0, 1.0), (1.0, 6.0), (6.0, 11.0)], 2.0),
+            vec![(0.0, 6.0), (6.0, 11.0)]
+        );
+        // until they're long enough
+        assert_eq!(
+            merged(&[(0.0, 0.5), (0.5, 1.0), (1.0, 3.0), (3.0, 8.0)], 2.0),
+            vec![(0.0, 3.0), (3.0, 8.0)]
+        );
+    }
+
+    #[test]
+    fn leaves_pieces_at_zero() {
+        assert_eq!(
+            merged(&[(0.0, 0.5), (0.5, 1.0)], 0.0),
+            vec![(0.0, 0.5), (0.5, 1.0)]
+        );
+    }
+}
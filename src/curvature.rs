@@ -0,0 +1,564 @@
+use na::{Matrix3, Vector3};
+
+use crate::lines::{EdgeKind, EdgeLine};
+use crate::mesh::{Mesh, WeldedMesh};
+use crate::scene::Scene;
+
+pub struct FeatureLineConfig {
+    /// lines along maxima of the largest principal curvature, e.g. the crest of a rounded edge
+    pub ridges: bool,
+    /// lines along minima of the smallest principal curvature, e.g. the bottom of a groove
+    pub valleys: bool,
+    /// view dependent lines where the surface would become a silhouette from a nearby viewpoint
+    pub suggestive_contours: bool,
+    /// curvature ridges and valleys must exceed, relative to the size of the mesh so that 1 is the
+    /// curvature of a circle as wide as the mesh's bounding box diagonal
+    pub curvature_threshold: f32,
+    /// rate of change of curvature towards the viewer suggestive contours must exceed, relative to
+    /// the size of the mesh as for the curvature threshold
+    pub suggestive_threshold: f32,
+}
+
+impl FeatureLineConfig {
+    pub fn new_default() -> FeatureLineConfig {
+        FeatureLineConfig {
+            ridges: true,
+            valleys: true,
+            suggestive_contours: true,
+            curvature_threshold: 20.0,
+            suggestive_threshold: 200.0,
+        }
+    }
+}
+
+/// Principal curvatures and directions at a vertex, positive where the surface is convex
+#[derive(Copy, Clone)]
+struct Curvature {
+    max: f32,
+    min: f32,
+    max_direction: Vector3<f32>,
+    min_direction: Vector3<f32>,
+    /// second fundamental form in the vertex's tangent basis
+    tensor: (f32, f32, f32),
+    basis: (Vector3<f32>, Vector3<f32>),
+}
+
+/// Finds ridges, valleys and suggestive contours from estimated surface curvature, for the
+/// projection and visibility pipeline to treat like any other edge. These are intended for smooth
+/// meshes, so nothing is found along creases
+pub fn find_feature_lines(mesh: &Mesh, scene: &Scene, config: &FeatureLineConfig) -> Vec<EdgeLine> {
+    let welded = WeldedMesh::new(mesh);
+
+    if welded.size <= 0.0 {
+        return vec![];
+    }
+
+    let curvatures = welded.curvatures();
+    let mut lines = Vec::new();
+
+    if config.ridges {
+        let field: Vec<(f32, Vector3<f32>)> = curvatures
+            .iter()
+            .map(|curvature| (curvature.max, curvature.max_direction))
+            .collect();
+        let other: Vec<f32> = curvatures.iter().map(|curvature| curvature.min).collect();
+
+        lines.append(&mut welded.extremal_lines(&field, &other, config.curvature_threshold));
+    }
+
+    if config.valleys {
+        // valleys are ridges of the surface turned inside out
+        let field: Vec<(f32, Vector3<f32>)> = curvatures
+            .iter()
+            .map(|curvature| (-curvature.min, curvature.min_direction))
+            .collect();
+        let other: Vec<f32> = curvatures.iter().map(|curvature| -curvature.max).collect();
+
+        lines.append(&mut welded.extremal_lines(&field, &other, config.curvature_threshold));
+    }
+
+    if config.suggestive_contours {
+        lines.append(&mut welded.suggestive_contours(
+            scene,
+            &curvatures,
+            config.suggestive_threshold,
+        ));
+    }
+
+    lines
+}
+
+impl WeldedMesh {
+    /// Fits the second fundamental form at each vertex to the normal curvature along each of its
+    /// edges, estimated from the circle through both ends that meets the vertex normal
+    fn curvatures(&self) -> Vec<Curvature> {
+        let mut neighbours: Vec<Vec<usize>> = vec![vec![]; self.points.len()];
+
+        for triangle in &self.triangles {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                if !neighbours[a].contains(&b) {
+                    neighbours[a].push(b);
+                    neighbours[b].push(a);
+                }
+            }
+        }
+
+        (0..self.points.len())
+            .map(|vertex| {
+                let normal = self.normals[vertex];
+                let basis = tangent_basis(&normal);
+
+                let mut normal_matrix = Matrix3::zeros();
+                let mut right_side = Vector3::zeros();
+
+                for &neighbour in &neighbours[vertex] {
+                    let edge = self.points[neighbour] - self.points[vertex];
+                    let length_squared = edge.norm_squared();
+                    let tangent =
+                        match (edge - normal * edge.dot(&normal)).try_normalize(f32::EPSILON) {
+                            Some(tangent) => tangent,
+                            None => continue,
+                        };
+
+                    let curvature = -2.0 * normal.dot(&edge) / length_squared;
+                    let (a, b) = (tangent.dot(&basis.0), tangent.dot(&basis.1));
+                    let row = Vector3::new(a * a, 2.0 * a * b, b * b);
+
+                    normal_matrix += row * row.transpose();
+                    right_side += row * curvature;
+                }
+
+                let (l, m, n) = match normal_matrix.try_inverse() {
+                    Some(inverse) => {
+                        let solution = inverse * right_side;
+                        (solution.x, solution.y, solution.z)
+                    }
+                    None => (0.0, 0.0, 0.0),
+                };
+
+                // eigen decomposition of the symmetric 2x2 tensor
+                let mean = (l + n) / 2.0;
+                let spread = (((l - n) / 2.0).powi(2) + m * m).sqrt();
+                let angle = 0.5 * (2.0 * m).atan2(l - n);
+                let max_direction = basis.0 * angle.cos() + basis.1 * angle.sin();
+
+                Curvature {
+                    max: mean + spread,
+                    min: mean - spread,
+                    max_direction,
+                    min_direction: normal.cross(&max_direction),
+                    tensor: (l, m, n),
+                    basis,
+                }
+            })
+            .collect()
+    }
+
+    /// Lines where the curvature in `field` is at a maximum along its own direction, found as zero
+    /// crossings of its derivative in that direction. The curvature must exceed the threshold and
+    /// dominate the `other` principal curvature
+    fn extremal_lines(
+        &self,
+        field: &[(f32, Vector3<f32>)],
+        other: &[f32],
+        threshold: f32,
+    ) -> Vec<EdgeLine> {
+        let values: Vec<f32> = field.iter().map(|(value, _)| *value).collect();
+        let gradients = self.vertex_gradients(&values);
+        let threshold = threshold / self.size;
+
+        let mut lines = Vec::new();
+
+        for triangle in &self.triangles {
+            if triangle.iter().any(|&vertex| self.creased[vertex]) {
+                continue;
+            }
+
+            // principal directions have no sign, so line them up with the first vertex's
+            let reference = field[triangle[0]].1;
+            let mut directions = [Vector3::zeros(); 3];
+            let mut extremality = [0.0; 3];
+
+            for (i, &vertex) in triangle.iter().enumerate() {
+                let direction = match field[vertex].1.dot(&reference) < 0.0 {
+                    true => -field[vertex].1,
+                    false => field[vertex].1,
+                };
+                directions[i] = direction;
+                extremality[i] = gradients[vertex].dot(&direction);
+            }
+
+            let strong = triangle
+                .iter()
+                .all(|&vertex| values[vertex] > threshold && values[vertex] > other[vertex].abs());
+            if !strong {
+                continue;
+            }
+
+            // a maximum rather than a minimum, so the derivative decreases across the line
+            let direction = directions[0] + directions[1] + directions[2];
+            if self.face_gradient(triangle, &extremality).dot(&direction) >= 0.0 {
+                continue;
+            }
+
+            if let Some(line) = self.zero_crossing(triangle, &extremality, EdgeKind::FEATURE) {
+                lines.push(line);
+            }
+        }
+
+        lines
+    }
+
+    /// Zero crossings of the curvature in the direction of the viewer, where that curvature is
+    /// increasing towards the viewer
+    fn suggestive_contours(
+        &self,
+        scene: &Scene,
+        curvatures: &[Curvature],
+        threshold: f32,
+    ) -> Vec<EdgeLine> {
+        let towards_viewer: Vec<Vector3<f32>> = self
+            .points
+            .iter()
+            .map(|point| scene.towards_viewer(point))
+            .collect();
+
+        // the view direction projected onto the tangent plane, and the curvature along it
+        let radial: Vec<(Vector3<f32>, f32)> = (0..self.points.len())
+            .map(|vertex| {
+                let normal = self.normals[vertex];
+                let view = towards_viewer[vertex];
+                let curvature = &curvatures[vertex];
+
+                match (view - normal * view.dot(&normal)).try_normalize(f32::EPSILON) {
+                    Some(direction) => {
+                        let (a, b) = (
+                            direction.dot(&curvature.basis.0),
+                            direction.dot(&curvature.basis.1),
+                        );
+                        let (l, m, n) = curvature.tensor;
+
+                        (direction, l * a * a + 2.0 * m * a * b + n * b * b)
+                    }
+                    None => (Vector3::zeros(), 0.0),
+                }
+            })
+            .collect();
+
+        let threshold = threshold / (self.size * self.size);
+        let mut lines = Vec::new();
+
+        for triangle in &self.triangles {
+            if triangle.iter().any(|&vertex| self.creased[vertex]) {
+                continue;
+            }
+
+            // only faces turned towards the viewer can show a contour
+            let facing = triangle
+                .iter()
+                .all(|&vertex| self.normals[vertex].dot(&towards_viewer[vertex]) > 0.0);
+            if !facing {
+                continue;
+            }
+
+            let values = [
+                radial[triangle[0]].1,
+                radial[triangle[1]].1,
+                radial[triangle[2]].1,
+            ];
+            let direction = radial[triangle[0]].0 + radial[triangle[1]].0 + radial[triangle[2]].0;
+
+            let derivative = match direction.try_normalize(f32::EPSILON) {
+                Some(direction) => self.face_gradient(triangle, &values).dot(&direction),
+                None => continue,
+            };
+
+            if derivative <= threshold {
+                continue;
+            }
+
+            if let Some(line) = self.zero_crossing(triangle, &values, EdgeKind::FEATURE) {
+                lines.push(line);
+            }
+        }
+
+        lines
+    }
+
+    /// Gradient of a value linearly interpolated across a triangle
+    fn face_gradient(&self, triangle: &[usize; 3], values: &[f32; 3]) -> Vector3<f32> {
+        let [p0, p1, p2] = [
+            self.points[triangle[0]],
+            self.points[triangle[1]],
+            self.points[triangle[2]],
+        ];
+        let normal = (p1 - p0).cross(&(p2 - p0));
+        let double_area = normal.norm();
+
+        if double_area <= f32::EPSILON {
+            return Vector3::zeros();
+        }
+
+        let unit = normal / double_area;
+
+        (unit.cross(&(p2 - p1)) * values[0]
+            + unit.cross(&(p0 - p2)) * values[1]
+            + unit.cross(&(p1 - p0)) * values[2])
+            / double_area
+    }
+
+    /// Area weighted average of the gradients of the faces around each vertex
+    fn vertex_gradients(&self, values: &[f32]) -> Vec<Vector3<f32>> {
+        let mut gradients = vec![Vector3::zeros(); self.points.len()];
+
+        for triangle in &self.triangles {
+            let face_values = [
+                values[triangle[0]],
+                values[triangle[1]],
+                values[triangle[2]],
+            ];
+            let area = (self.points[triangle[1]] - self.points[triangle[0]])
+                .cross(&(self.points[triangle[2]] - self.points[triangle[0]]))
+                .norm();
+            let gradient = self.face_gradient(triangle, &face_values) * area;
+
+            for &vertex in triangle {
+                gradients[vertex] += gradient;
+            }
+        }
+
+        // only the direction along the surface is meaningful
+        gradients
+            .iter()
+            .zip(&self.normals)
+            .map(|(gradient, normal)| gradient - normal * gradient.dot(normal))
+            .collect()
+    }
+}
+
+fn tangent_basis(normal: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    // any vector not parallel to the normal will do to start from
+    let start = match normal.x.abs() < 0.9 {
+        true => Vector3::x(),
+        false => Vector3::y(),
+    };
+    let u = normal.cross(&start).normalize();
+
+    (u, normal.cross(&u))
+}
+
+#[cfg(test)]
+mod tests {
+    use na::{Matrix4, Point3};
+
+    use super::*;
+
+    /// Triangulates a grid over `u` and `v` from 0 to 1, with a normal at each point
+    fn grid<P, N>(columns: usize, rows: usize, point: P, normal: N) -> Mesh
+    where
+        P: Fn(f32, f32) -> Point3<f32>,
+        N: Fn(&Point3<f32>) -> Vector3<f32>,
+    {
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let point = point(column as f32 / columns as f32, row as f32 / rows as f32);
+                let normal = normal(&point);
+                vertices.extend_from_slice(&[point.x, point.y, point.z]);
+                normals.extend_from_slice(&[normal.x, normal.y, normal.z]);
+            }
+        }
+
+        let index = |column: usize, row: usize| row * (columns + 1) + column;
+        let mut indices = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                indices.extend_from_slice(&[
+                    index(column, row),
+                    index(column + 1, row),
+                    index(column + 1, row + 1),
+                    index(column, row),
+                    index(column + 1, row + 1),
+                    index(column, row + 1),
+                ]);
+            }
+        }
+
+        Mesh::new(Some(indices), vertices, normals).unwrap()
+    }
+
+    fn sphere(radius: f32, normal_sign: f32) -> Mesh {
+        grid(
+            32,
+            16,
+            |u, v| {
+                let (theta, phi) = (v * std::f32::consts::PI, u * 2.0 * std::f32::consts::PI);
+                Point3::new(
+                    radius * theta.sin() * phi.cos(),
+                    radius * theta.sin() * phi.sin(),
+                    radius * theta.cos(),
+                )
+            },
+            |point| point.coords.normalize() * normal_sign,
+        )
+    }
+
+    // a rounded crest running along y, facing up
+    fn crest(height: f32) -> Mesh {
+        grid(
+            40,
+            10,
+            |u, v| {
+                let x = u * 4.0 - 2.0;
+                Point3::new(x, v * 4.0 - 2.0, height * (-x * x).exp())
+            },
+            |_| Vector3::z(),
+        )
+    }
+
+    // looking straight down at the origin
+    fn top_scene() -> Scene {
+        Scene::new(
+            100.0,
+            100.0,
+            Matrix4::look_at_rh(
+                &Point3::new(0.0, 0.0, 10.0),
+                &Point3::origin(),
+                &Vector3::y(),
+            ),
+            Matrix4::new_orthographic(-5.0, 5.0, -5.0, 5.0, 0.1, 100.0),
+            Matrix4::identity(),
+        )
+    }
+
+    fn only(ridges: bool, valleys: bool, suggestive_contours: bool) -> FeatureLineConfig {
+        FeatureLineConfig {
+            ridges,
+            valleys,
+            suggestive_contours,
+            curvature_threshold: 1.0,
+            suggestive_threshold: 1.0,
+            ..FeatureLineConfig::new_default()
+        }
+    }
+
+    fn midpoints(lines: &[EdgeLine]) -> Vec<Point3<f32>> {
+        lines
+            .iter()
+            .map(|line| Point3::from((line.line.from.coords + line.line.to.coords) / 2.0))
+            .collect()
+    }
+
+    #[test]
+    fn curves_positively_where_convex() {
+        for &(normal_sign, expected) in &[(1.0, 0.5), (-1.0, -0.5)] {
+            let welded = WeldedMesh::new(&sphere(2.0, normal_sign));
+
+            // away from the poles, where the grid is most even
+            for (point, curvature) in welded.points.iter().zip(welded.curvatures()) {
+                if point.z.abs() < 1.5 {
+                    assert_relative_eq!(curvature.max, expected, epsilon = 0.05);
+                    assert_relative_eq!(curvature.min, expected, epsilon = 0.05);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn curves_only_around_a_cylinder() {
+        let cylinder = grid(
+            32,
+            4,
+            |u, v| {
+                let phi = u * 2.0 * std::f32::consts::PI;
+                Point3::new(phi.cos(), phi.sin(), v * 2.0)
+            },
+            |point| Vector3::new(point.x, point.y, 0.0),
+        );
+        let welded = WeldedMesh::new(&cylinder);
+
+        for (point, curvature) in welded.points.iter().zip(welded.curvatures()) {
+            if point.z > 0.1 && point.z < 1.9 {
+                assert_relative_eq!(curvature.max, 1.0, epsilon = 0.05);
+                assert_relative_eq!(curvature.min, 0.0, epsilon = 0.05);
+                assert_relative_eq!(curvature.min_direction.z.abs(), 1.0, epsilon = 1e-2);
+                assert_relative_eq!(curvature.max_direction.z, 0.0, epsilon = 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn finds_ridges_along_crests() {
+        let lines = find_feature_lines(&crest(1.0), &top_scene(), &only(true, false, false));
+
+        assert!(!lines.is_empty());
+        assert!(lines.iter().all(|line| line.kind == EdgeKind::FEATURE));
+        assert!(midpoints(&lines).iter().all(|point| point.x.abs() < 0.2));
+    }
+
+    #[test]
+    fn finds_valleys_where_the_surface_is_most_concave() {
+        // the flanks of the crest curve up most steeply at 1.22 either side
+        let flanks = find_feature_lines(&crest(1.0), &top_scene(), &only(false, true, false));
+        assert!(!flanks.is_empty());
+        assert!(midpoints(&flanks)
+            .iter()
+            .all(|point| (point.x.abs() - 1.5f32.sqrt()).abs() < 0.2));
+
+        // and turned upside down the crest becomes the valley
+        let trough = find_feature_lines(&crest(-1.0), &top_scene(), &only(false, true, false));
+        assert!(!trough.is_empty());
+        assert!(midpoints(&trough).iter().all(|point| point.x.abs() < 0.2));
+    }
+
+    #[test]
+    fn finds_suggestive_contours_where_curvature_turns_towards_the_viewer() {
+        // waves along x, seen from above and off towards +x
+        let waves = grid(
+            64,
+            8,
+            |u, v| {
+                let x = u * 4.0 * std::f32::consts::PI;
+                Point3::new(x, v * 4.0, 0.5 * x.sin())
+            },
+            |_| Vector3::z(),
+        );
+        let scene = Scene::new(
+            100.0,
+            100.0,
+            Matrix4::look_at_rh(
+                &Point3::new(30.0, 2.0, 20.0),
+                &Point3::new(6.0, 2.0, 0.0),
+                &Vector3::z(),
+            ),
+            Matrix4::new_orthographic(-10.0, 10.0, -10.0, 10.0, 0.1, 100.0),
+            Matrix4::identity(),
+        );
+
+        let lines = find_feature_lines(&waves, &scene, &only(false, false, true));
+
+        assert!(!lines.is_empty());
+        // only along the inflections where the curvature increases towards the viewer, which are
+        // on the slopes rising towards it, not those falling away
+        assert!(midpoints(&lines).iter().all(|point| {
+            let cycles = point.x / (2.0 * std::f32::consts::PI);
+            (cycles - cycles.round()).abs() < 0.05
+        }));
+
+        let strict = FeatureLineConfig {
+            suggestive_threshold: 1e6,
+            ..only(false, false, true)
+        };
+        assert!(find_feature_lines(&waves, &scene, &strict).is_empty());
+    }
+
+    #[test]
+    fn finds_nothing_unless_asked() {
+        assert!(
+            find_feature_lines(&crest(1.0), &top_scene(), &only(false, false, false)).is_empty()
+        );
+    }
+}
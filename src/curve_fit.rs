@@ -0,0 +1,556 @@
+use std::f32::consts::PI;
+
+use na::{distance, Matrix2, Point2, Vector2};
+
+// runs of fewer segments than this are left as lines, as a curve wouldn't save anything
+const MIN_CURVE_SEGMENTS: usize = 3;
+
+// polylines turning more sharply than this (in radians) at a point have a corner there, which
+// curves are never fitted across
+const CORNER_ANGLE: f32 = 0.5;
+
+// longer arcs are split, as an arc's end point pins down its centre less and less precisely as it
+// approaches a full circle
+const MAX_ARC_SWEEP: f32 = 1.5 * PI;
+
+#[derive(Clone)]
+pub struct CurveFitConfig {
+    /// furthest a fitted curve may stray from the polyline, in the units of the points being fitted
+    pub tolerance: f32,
+    pub arcs: bool,
+    pub beziers: bool,
+}
+
+impl CurveFitConfig {
+    pub fn new_default() -> CurveFitConfig {
+        CurveFitConfig {
+            tolerance: 0.5,
+            arcs: true,
+            beziers: true,
+        }
+    }
+}
+
+/// A piece of a path, continuing from wherever the previous one ended
+#[derive(Copy, Clone, PartialEq)]
+pub enum PathSegment {
+    Line(Point2<f32>),
+    /// circular arc, sweeping through a signed angle in radians that's positive from the x axis
+    /// towards the y axis
+    Arc {
+        to: Point2<f32>,
+        centre: Point2<f32>,
+        radius: f32,
+        sweep: f32,
+    },
+    Cubic {
+        control_1: Point2<f32>,
+        control_2: Point2<f32>,
+        to: Point2<f32>,
+    },
+}
+
+impl PathSegment {
+    pub fn end(&self) -> &Point2<f32> {
+        match self {
+            PathSegment::Line(to) => to,
+            PathSegment::Arc { to, .. } => to,
+            PathSegment::Cubic { to, .. } => to,
+        }
+    }
+}
+
+/// Replaces runs of the polyline with circular arcs and cubic béziers that stay within the
+/// tolerance of every point and every segment, leaving lines wherever nothing fits. Runs are grown
+/// greedily from the start, taking whichever curve reaches further and arcs on a tie. Runs that
+/// turn out to be straight within the tolerance become a single line instead
+pub fn fit_curves(points: &[Point2<f32>], config: &CurveFitConfig) -> Vec<PathSegment> {
+    if points.len() < 2 {
+        return vec![];
+    }
+
+    let last = points.len() - 1;
+    let corners: Vec<bool> = (0..=last)
+        .map(|i| i == 0 || i == last || turn(points, i).abs() > CORNER_ANGLE)
+        .collect();
+
+    let mut segments = Vec::with_capacity(last);
+    let mut start = 0;
+
+    while start < last {
+        // curves can run up to the next corner but not through it
+        let limit = (start + 1..=last).find(|&i| corners[i]).unwrap_or(last);
+
+        let arc = match config.arcs {
+            true => longest_fit(start, limit, |end| {
+                fit_arc(&points[start..=end], config.tolerance)
+            }),
+            false => None,
+        };
+        let cubic = match config.beziers {
+            true => longest_fit(start, limit, |end| {
+                fit_cubic(points, &corners, start, end, config.tolerance)
+            }),
+            false => None,
+        };
+
+        let curve = match (arc, cubic) {
+            (Some(arc), Some(cubic)) if cubic.0 > arc.0 => Some(cubic),
+            (Some(arc), _) => Some(arc),
+            (None, cubic) => cubic,
+        };
+
+        match curve {
+            Some((end, _)) if is_straight(&points[start..=end], config.tolerance) => {
+                segments.push(PathSegment::Line(points[end]));
+                start = end;
+            }
+            Some((end, segment)) => {
+                segments.push(segment);
+                start = end;
+            }
+            None => {
+                segments.push(PathSegment::Line(points[start + 1]));
+                start += 1;
+            }
+        }
+    }
+
+    segments
+}
+
+/// The furthest end point up to the limit for which the run from the start can be fitted. Fits are
+/// tried at doubling distances and then narrowed down by bisection, which assumes a run that
+/// doesn't fit won't fit once it's extended further
+fn longest_fit<F>(start: usize, limit: usize, fit: F) -> Option<(usize, PathSegment)>
+where
+    F: Fn(usize) -> Option<PathSegment>,
+{
+    let first = start + MIN_CURVE_SEGMENTS;
+    if first > limit {
+        return None;
+    }
+
+    let mut best = (first, fit(first)?);
+    let mut step = 1;
+    let mut failed = None;
+
+    while best.0 < limit {
+        let next = (best.0 + step).min(limit);
+
+        match fit(next) {
+            Some(segment) => {
+                best = (next, segment);
+                step *= 2;
+            }
+            None => {
+                failed = Some(next);
+                break;
+            }
+        }
+    }
+
+    if let Some(mut high) = failed {
+        while high - best.0 > 1 {
+            let middle = (best.0 + high) / 2;
+
+            match fit(middle) {
+                Some(segment) => best = (middle, segment),
+                None => high = middle,
+            }
+        }
+    }
+
+    Some(best)
+}
+
+/// The circle through the first, middle and last points, if it passes within the tolerance of the
+/// rest and they run round it in one direction
+fn fit_arc(run: &[Point2<f32>], tolerance: f32) -> Option<PathSegment> {
+    let (from, middle, to) = (run[0], run[run.len() / 2], run[run.len() - 1]);
+    let centre = circumcentre(&from, &middle, &to)?;
+    let radius = distance(&centre, &from);
+
+    let off_circle = |point: &Point2<f32>| (distance(&centre, point) - radius).abs() > tolerance;
+
+    let mut sweep = 0.0;
+    let mut previous_angle = angle_around(&centre, &from);
+
+    for pair in run.windows(2) {
+        if off_circle(&pair[1]) || off_circle(&na::center(&pair[0], &pair[1])) {
+            return None;
+        }
+
+        let angle = angle_around(&centre, &pair[1]);
+        let mut step = angle - previous_angle;
+
+        // the shorter way round, as consecutive points are close together on the circle
+        if step > PI {
+            step -= 2.0 * PI;
+        } else if step < -PI {
+            step += 2.0 * PI;
+        }
+
+        // doubling back on itself
+        if sweep != 0.0 && step * sweep < 0.0 {
+            return None;
+        }
+
+        sweep += step;
+        previous_angle = angle;
+    }
+
+    if sweep.abs() > MAX_ARC_SWEEP {
+        return None;
+    }
+
+    Some(PathSegment::Arc {
+        to,
+        centre,
+        radius,
+        sweep,
+    })
+}
+
+/// A least squares cubic bézier through the run, leaving and arriving along the polyline's
+/// tangents so that consecutive curves join smoothly. The points are placed along the curve by
+/// chord length, then moved to their nearest parameters once and the curve refitted
+fn fit_cubic(
+    points: &[Point2<f32>],
+    corners: &[bool],
+    start: usize,
+    end: usize,
+    tolerance: f32,
+) -> Option<PathSegment> {
+    let run = &points[start..=end];
+    let start_tangent = tangent(points, corners, start, 1)?;
+    let end_tangent = tangent(points, corners, end, -1)?;
+
+    let mut parameters = Vec::with_capacity(run.len());
+    let mut length = 0.0;
+    for (i, point) in run.iter().enumerate() {
+        if i > 0 {
+            length += distance(&run[i - 1], point);
+        }
+        parameters.push(length);
+    }
+
+    if length <= f32::EPSILON {
+        return None;
+    }
+
+    for parameter in &mut parameters {
+        *parameter /= length;
+    }
+
+    let mut controls = least_squares_controls(run, &parameters, &start_tangent, &end_tangent);
+
+    for (parameter, point) in parameters.iter_mut().zip(run) {
+        *parameter = newton_step(&controls, point, *parameter);
+    }
+
+    let refitted = least_squares_controls(run, &parameters, &start_tangent, &end_tangent);
+    if max_error(&refitted, run, &parameters) < max_error(&controls, run, &parameters) {
+        controls = refitted;
+    }
+
+    // segments are checked at their middles too so that the curve can't swing out between points
+    if max_error(&controls, run, &parameters) > tolerance {
+        return None;
+    }
+
+    for (pair, parameter_pair) in run.windows(2).zip(parameters.windows(2)) {
+        let between = bezier_point(&controls, (parameter_pair[0] + parameter_pair[1]) / 2.0);
+        if distance_to_segment(&between, &pair[0], &pair[1]) > tolerance {
+            return None;
+        }
+    }
+
+    Some(PathSegment::Cubic {
+        control_1: controls[1],
+        control_2: controls[2],
+        to: controls[3],
+    })
+}
+
+fn least_squares_controls(
+    run: &[Point2<f32>],
+    parameters: &[f32],
+    start_tangent: &Vector2<f32>,
+    end_tangent: &Vector2<f32>,
+) -> [Point2<f32>; 4] {
+    let (first, last) = (run[0], run[run.len() - 1]);
+
+    let mut matrix = Matrix2::zeros();
+    let mut right_side = Vector2::zeros();
+
+    for (point, &t) in run.iter().zip(parameters) {
+        let (b0, b1, b2, b3) = bernstein(t);
+        let a1 = start_tangent * b1;
+        let a2 = end_tangent * b2;
+
+        matrix[(0, 0)] += a1.dot(&a1);
+        matrix[(0, 1)] += a1.dot(&a2);
+        matrix[(1, 1)] += a2.dot(&a2);
+
+        let residual = point.coords - first.coords * (b0 + b1) - last.coords * (b2 + b3);
+        right_side += Vector2::new(residual.dot(&a1), residual.dot(&a2));
+    }
+    matrix[(1, 0)] = matrix[(0, 1)];
+
+    let chord = distance(&first, &last);
+    let (alpha_1, alpha_2) = match matrix.try_inverse() {
+        Some(inverse) => {
+            let solution = inverse * right_side;
+            (solution.x, solution.y)
+        }
+        None => (0.0, 0.0),
+    };
+
+    // degenerate or backwards handles would loop, so fall back to a third of the chord
+    let (alpha_1, alpha_2) = match alpha_1 > chord * 1e-3 && alpha_2 > chord * 1e-3 {
+        true => (alpha_1, alpha_2),
+        false => (chord / 3.0, chord / 3.0),
+    };
+
+    [
+        first,
+        first + start_tangent * alpha_1,
+        last + end_tangent * alpha_2,
+        last,
+    ]
+}
+
+/// One newton-raphson step towards the parameter of the nearest point on the curve
+fn newton_step(controls: &[Point2<f32>; 4], point: &Point2<f32>, t: f32) -> f32 {
+    let [p0, p1, p2, p3] = *controls;
+    let offset = bezier_point(controls, t) - point;
+
+    let u = 1.0 - t;
+    let first = (p1 - p0) * (3.0 * u * u) + (p2 - p1) * (6.0 * u * t) + (p3 - p2) * (3.0 * t * t);
+    let second = (p2 - p1 * 2.0 + p0.coords) * (6.0 * u) + (p3 - p2 * 2.0 + p1.coords) * (6.0 * t);
+
+    let denominator = first.dot(&first) + offset.dot(&second);
+    if denominator.abs() <= f32::EPSILON {
+        return t;
+    }
+
+    (t - offset.dot(&first) / denominator).clamp(0.0, 1.0)
+}
+
+fn max_error(controls: &[Point2<f32>; 4], run: &[Point2<f32>], parameters: &[f32]) -> f32 {
+    run.iter()
+        .zip(parameters)
+        .map(|(point, &t)| distance(&bezier_point(controls, t), point))
+        .fold(0.0, f32::max)
+}
+
+fn bezier_point(controls: &[Point2<f32>; 4], t: f32) -> Point2<f32> {
+    let (b0, b1, b2, b3) = bernstein(t);
+
+    Point2::from(
+        controls[0].coords * b0
+            + controls[1].coords * b1
+            + controls[2].coords * b2
+            + controls[3].coords * b3,
+    )
+}
+
+fn bernstein(t: f32) -> (f32, f32, f32, f32) {
+    let u = 1.0 - t;
+
+    (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t)
+}
+
+/// Unit tangent at a point heading into the run in the given direction along the polyline. Within
+/// smooth stretches this is the direction between the neighbouring points, so that curves meeting
+/// there share it
+fn tangent(
+    points: &[Point2<f32>],
+    corners: &[bool],
+    index: usize,
+    direction: isize,
+) -> Option<Vector2<f32>> {
+    let next = (index as isize + direction) as usize;
+
+    let tangent = match corners[index] {
+        true => points[next] - points[index],
+        false => points[next] - points[(index as isize - direction) as usize],
+    };
+
+    tangent.try_normalize(f32::EPSILON)
+}
+
+/// Signed angle the polyline turns through at an interior point
+fn turn(points: &[Point2<f32>], index: usize) -> f32 {
+    let incoming = points[index] - points[index - 1];
+    let outgoing = points[index + 1] - points[index];
+
+    incoming.perp(&outgoing).atan2(incoming.dot(&outgoing))
+}
+
+fn is_straight(run: &[Point2<f32>], tolerance: f32) -> bool {
+    let (first, last) = (run[0], run[run.len() - 1]);
+
+    run.iter()
+        .all(|point| distance_to_segment(point, &first, &last) <= tolerance)
+}
+
+fn circumcentre(a: &Point2<f32>, b: &Point2<f32>, c: &Point2<f32>) -> Option<Point2<f32>> {
+    let (ab, ac) = (b - a, c - a);
+    let denominator = 2.0 * ab.perp(&ac);
+
+    if denominator.abs() <= f32::EPSILON * ab.norm_squared().max(ac.norm_squared()) {
+        return None;
+    }
+
+    let offset = Vector2::new(
+        ac.y * ab.norm_squared() - ab.y * ac.norm_squared(),
+        ab.x * ac.norm_squared() - ac.x * ab.norm_squared(),
+    ) / denominator;
+
+    Some(a + offset)
+}
+
+fn angle_around(centre: &Point2<f32>, point: &Point2<f32>) -> f32 {
+    (point.y - centre.y).atan2(point.x - centre.x)
+}
+
+fn distance_to_segment(point: &Point2<f32>, from: &Point2<f32>, to: &Point2<f32>) -> f32 {
+    let along = to - from;
+    let length_squared = along.norm_squared();
+
+    if length_squared <= f32::EPSILON {
+        return distance(point, from);
+    }
+
+    let t = ((point - from).dot(&along) / length_squared).clamp(0.0, 1.0);
+
+    distance(point, &(from + along * t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circle_points(radius: f32, from: f32, to: f32, count: usize) -> Vec<Point2<f32>> {
+        (0..count)
+            .map(|i| {
+                let angle = from + (to - from) * i as f32 / (count - 1) as f32;
+                Point2::new(radius * angle.cos(), radius * angle.sin())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fits_an_arc() {
+        let points = circle_points(100.0, 0.0, PI / 2.0, 17);
+
+        let segments = fit_curves(&points, &CurveFitConfig::new_default());
+
+        assert_eq!(segments.len(), 1);
+        match segments[0] {
+            PathSegment::Arc {
+                to,
+                centre,
+                radius,
+                sweep,
+            } => {
+                assert_relative_eq!(to, points[16], epsilon = 1e-3);
+                assert_relative_eq!(centre, Point2::origin(), epsilon = 0.1);
+                assert_relative_eq!(radius, 100.0, epsilon = 0.1);
+                assert_relative_eq!(sweep, PI / 2.0, epsilon = 1e-3);
+            }
+            _ => panic!("expected an arc"),
+        }
+    }
+
+    #[test]
+    fn splits_long_arcs() {
+        let points = circle_points(100.0, 0.0, 1.9 * PI, 61);
+
+        let segments = fit_curves(&points, &CurveFitConfig::new_default());
+
+        assert!(segments.len() > 1);
+        assert!(segments.iter().all(|segment| match segment {
+            PathSegment::Arc { sweep, .. } => sweep.abs() <= MAX_ARC_SWEEP,
+            _ => true,
+        }));
+        assert_relative_eq!(*segments.last().unwrap().end(), points[60], epsilon = 1e-3);
+    }
+
+    #[test]
+    fn fits_a_bezier() {
+        let controls = [
+            Point2::new(0.0, 0.0),
+            Point2::new(60.0, 60.0),
+            Point2::new(140.0, -20.0),
+            Point2::new(200.0, 40.0),
+        ];
+        let points: Vec<Point2<f32>> = (0..=20)
+            .map(|i| bezier_point(&controls, i as f32 / 20.0))
+            .collect();
+        let config = CurveFitConfig {
+            arcs: false,
+            ..CurveFitConfig::new_default()
+        };
+
+        let segments = fit_curves(&points, &config);
+
+        assert!(segments.len() < 4);
+        assert!(segments
+            .iter()
+            .any(|segment| matches!(segment, PathSegment::Cubic { .. })));
+        assert_relative_eq!(*segments.last().unwrap().end(), points[20], epsilon = 1e-3);
+    }
+
+    #[test]
+    fn leaves_lines_when_curves_are_off() {
+        let points = circle_points(100.0, 0.0, PI / 2.0, 17);
+        let config = CurveFitConfig {
+            arcs: false,
+            beziers: false,
+            ..CurveFitConfig::new_default()
+        };
+
+        let segments = fit_curves(&points, &config);
+
+        assert_eq!(segments.len(), 16);
+        assert!(segments
+            .iter()
+            .all(|segment| matches!(segment, PathSegment::Line(_))));
+    }
+
+    #[test]
+    fn straight_runs_become_one_line() {
+        let points: Vec<Point2<f32>> = (0..10).map(|i| Point2::new(i as f32, 0.0)).collect();
+
+        let segments = fit_curves(&points, &CurveFitConfig::new_default());
+
+        assert!(segments == vec![PathSegment::Line(points[9])]);
+    }
+
+    #[test]
+    fn never_fits_across_corners() {
+        let mut points: Vec<Point2<f32>> = (0..10).map(|i| Point2::new(i as f32, 0.0)).collect();
+        points.extend((1..10).map(|i| Point2::new(9.0, i as f32)));
+
+        let segments = fit_curves(&points, &CurveFitConfig::new_default());
+
+        assert!(
+            segments
+                == vec![
+                    PathSegment::Line(Point2::new(9.0, 0.0)),
+                    PathSegment::Line(Point2::new(9.0, 9.0)),
+                ]
+        );
+    }
+
+    #[test]
+    fn needs_two_points() {
+        let config = CurveFitConfig::new_default();
+
+        assert!(fit_curves(&[], &config).is_empty());
+        assert!(fit_curves(&[Point2::new(1.0, 1.0)], &config).is_empty());
+    }
+}
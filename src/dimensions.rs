@@ -0,0 +1,534 @@
+use na::{Point2, Point3, Vector2};
+
+use crate::error::MeshToSvgError;
+use crate::lines::LineSegmentCategorized;
+use crate::scene::Scene;
+use crate::utils::escape_xml;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum DimensionDirection {
+    /// measured along x, placed above the points
+    Horizontal,
+    /// measured along y, placed to the left of the points
+    Vertical,
+    /// measured between the points, placed to their left when looking from `from` to `to`
+    Aligned,
+}
+
+/// A linear dimension between two screen space points
+#[derive(Clone)]
+pub struct Dimension {
+    pub from: Point2<f32>,
+    pub to: Point2<f32>,
+    pub direction: DimensionDirection,
+    pub label: String,
+}
+
+pub struct DimensionConfig {
+    /// dimension the overall width and height of the drawing
+    pub overall: bool,
+    /// pairs of mesh space points to dimension between
+    pub point_to_point: Vec<(Point3<f32>, Point3<f32>)>,
+    /// decimal places of the measurements
+    pub precision: usize,
+    /// multiplier from world units to the unit the measurements are given in
+    pub value_scale: f32,
+    /// appended to measurements, e.g. `mm`
+    pub unit_suffix: String,
+}
+
+/// Sizes in mm, converted to the units of whatever the dimensions are drawn in
+#[derive(Clone)]
+pub struct DimensionStyle {
+    /// distance from the furthest measured point to the dimension line
+    pub offset_mm: f32,
+    pub text_size_mm: f32,
+    pub arrow_length_mm: f32,
+    pub stroke_width_mm: f32,
+    pub stroke: String,
+    pub font_family: String,
+}
+
+/// Room left around the drawing for dimensions, as the length before and after the lines along x
+/// and y, i.e. to the left and top and to the right and bottom
+pub struct DimensionRoom {
+    pub before: Vector2<f32>,
+    pub after: Vector2<f32>,
+}
+
+// gap left between the feature and its extension line, and how far extension lines run past the
+// dimension line
+const EXTENSION_GAP_MM: f32 = 1.0;
+const EXTENSION_OVERSHOOT_MM: f32 = 2.0;
+
+impl DimensionConfig {
+    pub fn new_default() -> DimensionConfig {
+        DimensionConfig {
+            overall: true,
+            point_to_point: vec![],
+            precision: 1,
+            value_scale: 1.0,
+            unit_suffix: "".to_owned(),
+        }
+    }
+}
+
+impl DimensionStyle {
+    pub fn new_default() -> DimensionStyle {
+        DimensionStyle {
+            offset_mm: 8.0,
+            text_size_mm: 3.5,
+            arrow_length_mm: 3.0,
+            stroke_width_mm: 0.25,
+            stroke: "black".to_owned(),
+            font_family: "sans-serif".to_owned(),
+        }
+    }
+
+    /// How far dimensions can reach beyond the lines they measure
+    pub fn reach_mm(&self) -> f32 {
+        self.offset_mm + EXTENSION_OVERSHOOT_MM + self.text_size_mm
+    }
+}
+
+impl Dimension {
+    /// The same dimension with its points moved into another space, e.g. onto a page
+    pub fn transformed<F>(&self, transform: F) -> Dimension
+    where
+        F: Fn(&Point2<f32>) -> Point2<f32>,
+    {
+        Dimension {
+            from: transform(&self.from),
+            to: transform(&self.to),
+            ..self.clone()
+        }
+    }
+
+    /// The side of the points the dimension line is placed on, a quarter turn anticlockwise on
+    /// screen from the measured direction as y points down
+    fn placement_normal(&self) -> Vector2<f32> {
+        let direction = self.measured_direction();
+
+        Vector2::new(direction.y, -direction.x)
+    }
+
+    fn measured_direction(&self) -> Vector2<f32> {
+        match self.direction {
+            DimensionDirection::Horizontal => Vector2::new(1.0, 0.0),
+            // upwards, so that the dimension is placed to the left
+            DimensionDirection::Vertical => Vector2::new(0.0, -1.0),
+            DimensionDirection::Aligned => {
+                match (self.to - self.from).try_normalize(f32::EPSILON) {
+                    Some(direction) => direction,
+                    None => Vector2::new(1.0, 0.0),
+                }
+            }
+        }
+    }
+}
+
+/// Finds the configured dimensions, measuring lengths as projected onto the view in world units.
+/// Measurements only hold when lengths in screen space are proportional to lengths in the world,
+/// so perspective views are refused
+pub fn find_dimensions(
+    screen_space_lines: &[LineSegmentCategorized],
+    scene: &Scene,
+    config: &DimensionConfig,
+) -> Result<Vec<Dimension>, MeshToSvgError> {
+    if !config.overall && config.point_to_point.is_empty() {
+        return Ok(vec![]);
+    }
+    if !scene.is_orthographic() {
+        return Err(MeshToSvgError::NotOrthographic {
+            output: "dimensions",
+        });
+    }
+
+    // x and y scales differ when the projection doesn't match the canvas aspect ratio
+    let world_units_per_screen_unit = Vector2::new(
+        1.0 / scene.screen_space_scale(),
+        1.0 / scene.screen_space_vertical_scale(),
+    );
+    let label = |screen_length: Vector2<f32>| {
+        format!(
+            "{:.*}{}",
+            config.precision,
+            screen_length
+                .component_mul(&world_units_per_screen_unit)
+                .norm()
+                * config.value_scale,
+            config.unit_suffix
+        )
+    };
+
+    let mut dimensions = Vec::new();
+
+    if config.overall {
+        if let Some((min, max)) = bounds(screen_space_lines) {
+            dimensions.push(Dimension {
+                from: min,
+                to: Point2::new(max.x, min.y),
+                direction: DimensionDirection::Horizontal,
+                label: label(Vector2::new(max.x - min.x, 0.0)),
+            });
+            dimensions.push(Dimension {
+                from: min,
+                to: Point2::new(min.x, max.y),
+                direction: DimensionDirection::Vertical,
+                label: label(Vector2::new(0.0, max.y - min.y)),
+            });
+        }
+    }
+
+    for (from, to) in &config.point_to_point {
+        let from = scene.project_point(from);
+        let to = scene.project_point(to);
+
+        dimensions.push(Dimension {
+            from,
+            to,
+            direction: DimensionDirection::Aligned,
+            label: label(to - from),
+        });
+    }
+
+    Ok(dimensions)
+}
+
+/// The room the dimensions need on each side of the lines they measure, which is the style's
+/// reach on every side a dimension is placed on
+pub fn dimension_room(dimensions: &[Dimension], style: &DimensionStyle) -> DimensionRoom {
+    let mut room = DimensionRoom {
+        before: Vector2::new(0.0, 0.0),
+        after: Vector2::new(0.0, 0.0),
+    };
+
+    for dimension in dimensions {
+        let normal = dimension.placement_normal();
+
+        for axis in 0..2 {
+            if normal[axis] < -f32::EPSILON {
+                room.before[axis] = style.reach_mm();
+            } else if normal[axis] > f32::EPSILON {
+                room.after[axis] = style.reach_mm();
+            }
+        }
+    }
+
+    room
+}
+
+/// Svg elements drawing the dimensions with extension lines, arrows and text
+pub fn dimension_elements(
+    dimensions: &[Dimension],
+    style: &DimensionStyle,
+    units_per_mm: f32,
+) -> String {
+    let mm = |length: f32| length * units_per_mm;
+    let mut lines = String::new();
+    let mut arrows = String::new();
+    let mut labels = Vec::with_capacity(dimensions.len());
+
+    for dimension in dimensions {
+        let direction = dimension.measured_direction();
+        let normal = dimension.placement_normal();
+
+        // the dimension line sits beyond whichever point is furthest out
+        let level = dimension
+            .from
+            .coords
+            .dot(&normal)
+            .max(dimension.to.coords.dot(&normal))
+            + mm(style.offset_mm);
+
+        let foot = |point: &Point2<f32>| point + normal * (level - point.coords.dot(&normal));
+        let (start, end) = (foot(&dimension.from), foot(&dimension.to));
+
+        for (point, point_foot) in &[(dimension.from, start), (dimension.to, end)] {
+            push_line(
+                &mut lines,
+                &(point + normal * mm(EXTENSION_GAP_MM)),
+                &(point_foot + normal * mm(EXTENSION_OVERSHOOT_MM)),
+            );
+        }
+        push_line(&mut lines, &start, &end);
+
+        // arrows point outwards from the middle of the dimension line
+        let along = match (end - start).try_normalize(f32::EPSILON) {
+            Some(along) => along,
+            None => direction,
+        };
+        push_arrow(&mut arrows, &start, &-along, mm(style.arrow_length_mm));
+        push_arrow(&mut arrows, &end, &along, mm(style.arrow_length_mm));
+
+        // text reads left to right, or bottom to top for vertical dimensions
+        let mut angle = along.y.atan2(along.x).to_degrees();
+        if angle >= 90.0 {
+            angle -= 180.0;
+        } else if angle < -90.0 {
+            angle += 180.0;
+        }
+
+        // the text sits on the far side of the dimension line, so when its baseline faces away
+        // from the features it's moved out by its own height
+        let text_up = Vector2::new(angle.to_radians().sin(), -angle.to_radians().cos());
+        let text_offset = match text_up.dot(&normal) < 0.0 {
+            true => mm(1.0 + style.text_size_mm),
+            false => mm(1.0),
+        };
+        let text_position = Point2::from((start.coords + end.coords) * 0.5) + normal * text_offset;
+
+        let rotation = match angle == 0.0 {
+            true => "".to_owned(),
+            false => format!(
+                " transform=\"rotate({} {} {})\"",
+                angle, text_position.x, text_position.y
+            ),
+        };
+
+        labels.push(format!(
+            "<text x=\"{x}\" y=\"{y}\"{rotation} font-size=\"{size}\" font-family=\"{font_family}\" text-anchor=\"middle\" stroke=\"none\">{label}</text>",
+            x = text_position.x,
+            y = text_position.y,
+            rotation = rotation,
+            size = mm(style.text_size_mm),
+            font_family = escape_xml(&style.font_family),
+            label = escape_xml(&dimension.label),
+        ));
+    }
+
+    format!(
+        "<g id=\"dimensions\" class=\"dimensions\" stroke=\"{stroke}\" fill=\"{stroke}\">\n<path d=\"{lines}\" fill=\"none\" stroke-width=\"{stroke_width}\" />\n<path d=\"{arrows}\" stroke=\"none\" />\n{labels}\n</g>",
+        stroke = escape_xml(&style.stroke),
+        lines = lines.trim_start(),
+        arrows = arrows.trim_start(),
+        stroke_width = mm(style.stroke_width_mm),
+        labels = labels.join("\n"),
+    )
+}
+
+fn push_line(path: &mut String, from: &Point2<f32>, to: &Point2<f32>) {
+    path.push_str(format!(" M {} {} L {} {}", from.x, from.y, to.x, to.y).as_str());
+}
+
+/// A filled arrowhead with its tip at `tip`, pointing along `direction`
+fn push_arrow(path: &mut String, tip: &Point2<f32>, direction: &Vector2<f32>, length: f32) {
+    let back = tip - direction * length;
+    let side = Vector2::new(-direction.y, direction.x) * length / 6.0;
+    let (left, right) = (back + side, back - side);
+
+    path.push_str(
+        format!(
+            " M {} {} L {} {} {} {} Z",
+            tip.x, tip.y, left.x, left.y, right.x, right.y
+        )
+        .as_str(),
+    );
+}
+
+fn bounds(lines: &[LineSegmentCategorized]) -> Option<(Point2<f32>, Point2<f32>)> {
+    let mut points = lines
+        .iter()
+        .flat_map(|line| vec![line.line_segment.from, line.line_segment.to]);
+
+    let first = points.next()?;
+
+    Some(points.fold((first, first), |(min, max), point| {
+        (
+            Point2::new(min.x.min(point.x), min.y.min(point.y)),
+            Point2::new(max.x.max(point.x), max.y.max(point.y)),
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Matrix4;
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineSegment2, LineSegment3, LineVisibility};
+
+    fn segment(from: (f32, f32), to: (f32, f32)) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility: LineVisibility::VISIBLE,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    // 2 screen units per world unit across, and 1 down
+    fn orthographic_scene() -> Scene {
+        #[rustfmt::skip]
+        let projection_matrix = Matrix4::new(
+            0.02,  0.0,   0.0, 0.0,
+             0.0, 0.02,   0.0, 0.0,
+             0.0,  0.0, -0.01, 0.0,
+             0.0,  0.0,   0.0, 1.0,
+        );
+
+        Scene::new(
+            200.0,
+            100.0,
+            Matrix4::identity(),
+            projection_matrix,
+            Matrix4::identity(),
+        )
+    }
+
+    fn dimension(from: (f32, f32), to: (f32, f32), direction: DimensionDirection) -> Dimension {
+        Dimension {
+            from: Point2::new(from.0, from.1),
+            to: Point2::new(to.0, to.1),
+            direction,
+            label: "20.0".to_owned(),
+        }
+    }
+
+    fn path_data(svg: &str) -> &str {
+        svg.split("<path d=\"")
+            .nth(1)
+            .unwrap()
+            .split('"')
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn measures_in_world_units() {
+        let config = DimensionConfig {
+            point_to_point: vec![(Point3::new(0.0, 0.0, 0.0), Point3::new(3.0, 4.0, 0.0))],
+            unit_suffix: "mm".to_owned(),
+            ..DimensionConfig::new_default()
+        };
+
+        let dimensions = find_dimensions(
+            &[segment((0.0, 0.0), (40.0, 30.0))],
+            &orthographic_scene(),
+            &config,
+        )
+        .unwrap();
+        let labels: Vec<&str> = dimensions
+            .iter()
+            .map(|dimension| dimension.label.as_str())
+            .collect();
+
+        // x and y are scaled separately before measuring
+        assert_eq!(labels, ["20.0mm", "30.0mm", "5.0mm"]);
+        assert!(dimensions[0].direction == DimensionDirection::Horizontal);
+        assert!(dimensions[1].direction == DimensionDirection::Vertical);
+        assert!(dimensions[2].direction == DimensionDirection::Aligned);
+    }
+
+    #[test]
+    fn formats_labels_with_the_configured_precision_and_scale() {
+        let config = DimensionConfig {
+            precision: 0,
+            value_scale: 25.4,
+            ..DimensionConfig::new_default()
+        };
+
+        let dimensions = find_dimensions(
+            &[segment((0.0, 0.0), (2.0, 0.0))],
+            &orthographic_scene(),
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(dimensions[0].label, "25");
+    }
+
+    #[test]
+    fn refuses_perspective_only_when_dimensioning() {
+        let mut scene = orthographic_scene();
+        scene.projection_matrix = Matrix4::new_perspective(2.0, 0.8, 0.1, 100.0);
+        let lines = [segment((0.0, 0.0), (40.0, 30.0))];
+        let none = DimensionConfig {
+            overall: false,
+            ..DimensionConfig::new_default()
+        };
+
+        assert!(find_dimensions(&lines, &scene, &none).unwrap().is_empty());
+        assert!(
+            find_dimensions(&lines, &scene, &DimensionConfig::new_default()).err()
+                == Some(MeshToSvgError::NotOrthographic {
+                    output: "dimensions"
+                })
+        );
+    }
+
+    #[test]
+    fn places_horizontal_dimensions_above_the_furthest_point() {
+        let svg = dimension_elements(
+            &[dimension(
+                (10.0, 50.0),
+                (30.0, 60.0),
+                DimensionDirection::Horizontal,
+            )],
+            &DimensionStyle::new_default(),
+            1.0,
+        );
+
+        // extension lines stop short of the points and run past the dimension line
+        assert_eq!(
+            path_data(&svg),
+            "M 10 49 L 10 40 M 30 59 L 30 40 M 10 42 L 30 42"
+        );
+        assert!(svg.contains("<text x=\"20\" y=\"41\" font-size=\"3.5\" font-family=\"sans-serif\" text-anchor=\"middle\" stroke=\"none\">20.0</text>"));
+    }
+
+    #[test]
+    fn places_vertical_dimensions_to_the_left_reading_upwards() {
+        let svg = dimension_elements(
+            &[dimension(
+                (10.0, 50.0),
+                (10.0, 80.0),
+                DimensionDirection::Vertical,
+            )],
+            &DimensionStyle::new_default(),
+            1.0,
+        );
+
+        assert_eq!(path_data(&svg), "M 9 50 L 0 50 M 9 80 L 0 80 M 2 50 L 2 80");
+        assert!(svg.contains("<text x=\"1\" y=\"65\" transform=\"rotate(-90 1 65)\""));
+    }
+
+    #[test]
+    fn draws_arrows_pointing_outwards() {
+        let svg = dimension_elements(
+            &[dimension(
+                (0.0, 20.0),
+                (30.0, 20.0),
+                DimensionDirection::Horizontal,
+            )],
+            &DimensionStyle::new_default(),
+            1.0,
+        );
+        let arrows = svg.split("<path d=\"").nth(2).unwrap();
+
+        assert!(arrows.starts_with("M 0 12 L 3 11.5 3 12.5 Z M 30 12 L 27 12.5 27 11.5 Z\""));
+    }
+
+    #[test]
+    fn makes_room_on_the_sides_dimensions_are_placed() {
+        let style = DimensionStyle::new_default();
+        let room = dimension_room(
+            &[
+                dimension((0.0, 0.0), (10.0, 0.0), DimensionDirection::Horizontal),
+                dimension((0.0, 0.0), (0.0, 10.0), DimensionDirection::Vertical),
+            ],
+            &style,
+        );
+
+        assert_eq!(style.reach_mm(), 13.5);
+        assert_eq!(room.before, Vector2::new(13.5, 13.5));
+        assert_eq!(room.after, Vector2::new(0.0, 0.0));
+    }
+}
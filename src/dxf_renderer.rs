@@ -0,0 +1,381 @@
+use na::Point2;
+
+use crate::curve_fit::{fit_curves, CurveFitConfig, PathSegment};
+use crate::error::MeshToSvgError;
+use crate::lines::{LineSegmentCategorized, LineVisibility};
+use crate::polyline::chain_line_segments;
+use crate::scene::Scene;
+
+const VISIBLE_LAYER: &str = "VISIBLE";
+const HIDDEN_LAYER: &str = "HIDDEN";
+
+pub struct DxfConfig {
+    pub include_obscured: bool,
+    /// write runs of lines that fit a circle within this distance in world units as ARC entities
+    pub arc_tolerance: Option<f32>,
+}
+
+impl DxfConfig {
+    pub fn new_default() -> DxfConfig {
+        DxfConfig {
+            include_obscured: true,
+            arc_tolerance: None,
+        }
+    }
+}
+
+/// Writes an ASCII R12 DXF with visible and obscured lines on separate layers, the latter with the
+/// HIDDEN linetype. Screen space is converted back to world units, so the drawing is true to scale.
+/// R12 has no header variable for the unit itself, so one drawing unit is one world unit.
+///
+/// Perspective scenes have no single scale back to world units and are refused.
+///
+/// R12 predates LWPOLYLINE, so chained lines are written as POLYLINE entities instead. With an arc
+/// tolerance, runs that fit a circle are written as ARC entities between them
+pub fn screen_space_lines_to_dxf(
+    screen_space_lines: &[LineSegmentCategorized],
+    scene: &Scene,
+    config: &DxfConfig,
+) -> Result<String, MeshToSvgError> {
+    if !scene.is_orthographic() {
+        return Err(MeshToSvgError::NotOrthographic { output: "dxf" });
+    }
+
+    // x and y scales differ when the projection doesn't match the canvas aspect ratio
+    let scale_x = 1.0 / scene.screen_space_scale();
+    let scale_y = 1.0 / scene.screen_space_vertical_scale();
+
+    let mut entities = String::new();
+
+    for polyline in chain_line_segments(screen_space_lines) {
+        let layer = match polyline.visibility {
+            LineVisibility::VISIBLE => VISIBLE_LAYER,
+            LineVisibility::OBSCURED if config.include_obscured => HIDDEN_LAYER,
+            LineVisibility::OBSCURED => continue,
+        };
+
+        // dxf is y up, screen space is y down
+        let to_world = |point: &Point2<f32>| {
+            Point2::new(point.x * scale_x, (scene.height - point.y) * scale_y)
+        };
+
+        let points: Vec<Point2<f32>> = polyline.points.iter().map(to_world).collect();
+
+        let tolerance = match config.arc_tolerance {
+            Some(tolerance) => tolerance,
+            None => {
+                entities.push_str(polyline_entity(&points, layer).as_str());
+                continue;
+            }
+        };
+
+        let segments = fit_curves(
+            &points,
+            &CurveFitConfig {
+                tolerance,
+                arcs: true,
+                beziers: false,
+            },
+        );
+
+        // lines between arcs are gathered back into polylines
+        let mut run = vec![points[0]];
+
+        for segment in segments {
+            match segment {
+                PathSegment::Arc {
+                    to,
+                    centre,
+                    radius,
+                    sweep,
+                } => {
+                    if run.len() > 1 {
+                        entities.push_str(polyline_entity(&run, layer).as_str());
+                    }
+
+                    entities.push_str(
+                        arc_entity(&run[run.len() - 1], &to, &centre, radius, sweep, layer)
+                            .as_str(),
+                    );
+                    run = vec![to];
+                }
+                segment => run.push(*segment.end()),
+            }
+        }
+
+        if run.len() > 1 {
+            entities.push_str(polyline_entity(&run, layer).as_str());
+        }
+    }
+
+    Ok(format!(
+        "{header}{tables}  0\nSECTION\n  2\nENTITIES\n{entities}  0\nENDSEC\n  0\nEOF\n",
+        header = header(),
+        tables = tables(),
+        entities = entities,
+    ))
+}
+
+fn header() -> String {
+    "  0\nSECTION\n  2\nHEADER\n  9\n$ACADVER\n  1\nAC1009\n  0\nENDSEC\n".to_string()
+}
+
+fn tables() -> String {
+    format!(
+        "  0\nSECTION\n  2\nTABLES\n\
+  0\nTABLE\n  2\nLTYPE\n 70\n2\n\
+  0\nLTYPE\n  2\nCONTINUOUS\n 70\n0\n  3\nSolid line\n 72\n65\n 73\n0\n 40\n0.0\n\
+  0\nLTYPE\n  2\nHIDDEN\n 70\n0\n  3\nHidden __ __ __ __\n 72\n65\n 73\n2\n 40\n9.525\n 49\n6.35\n 49\n-3.175\n\
+  0\nENDTAB\n\
+  0\nTABLE\n  2\nLAYER\n 70\n2\n\
+  0\nLAYER\n  2\n{visible}\n 70\n0\n 62\n7\n  6\nCONTINUOUS\n\
+  0\nLAYER\n  2\n{hidden}\n 70\n0\n 62\n8\n  6\nHIDDEN\n\
+  0\nENDTAB\n\
+  0\nENDSEC\n",
+        visible = VISIBLE_LAYER,
+        hidden = HIDDEN_LAYER,
+    )
+}
+
+fn polyline_entity(points: &[Point2<f32>], layer: &str) -> String {
+    if points.len() == 2 {
+        let (from, to) = (points[0], points[1]);
+
+        return format!(
+            "  0\nLINE\n  8\n{layer}\n 10\n{x1}\n 20\n{y1}\n 30\n0.0\n 11\n{x2}\n 21\n{y2}\n 31\n0.0\n",
+            layer = layer,
+            x1 = from.x,
+            y1 = from.y,
+            x2 = to.x,
+            y2 = to.y,
+        );
+    }
+
+    let closed = points.len() > 2 && relative_eq!(points[0], points[points.len() - 1]);
+
+    // closed polylines repeat their first point, dxf marks them with a flag instead
+    let points = match closed {
+        true => &points[..points.len() - 1],
+        false => points,
+    };
+
+    let mut entity = format!(
+        "  0\nPOLYLINE\n  8\n{layer}\n 66\n1\n 10\n0.0\n 20\n0.0\n 30\n0.0\n 70\n{flags}\n",
+        layer = layer,
+        flags = if closed { 1 } else { 0 },
+    );
+
+    for vertex in points {
+        entity.push_str(
+            format!(
+                "  0\nVERTEX\n  8\n{layer}\n 10\n{x}\n 20\n{y}\n 30\n0.0\n",
+                layer = layer,
+                x = vertex.x,
+                y = vertex.y,
+            )
+            .as_str(),
+        );
+    }
+
+    entity.push_str(format!("  0\nSEQEND\n  8\n{layer}\n", layer = layer).as_str());
+
+    entity
+}
+
+/// Dxf arcs always run anticlockwise from their start angle to their end angle, so clockwise arcs
+/// are written from their end
+fn arc_entity(
+    from: &Point2<f32>,
+    to: &Point2<f32>,
+    centre: &Point2<f32>,
+    radius: f32,
+    sweep: f32,
+    layer: &str,
+) -> String {
+    let angle = |point: &Point2<f32>| (point.y - centre.y).atan2(point.x - centre.x).to_degrees();
+
+    let (start, end) = match sweep > 0.0 {
+        true => (angle(from), angle(to)),
+        false => (angle(to), angle(from)),
+    };
+
+    format!(
+        "  0\nARC\n  8\n{layer}\n 10\n{x}\n 20\n{y}\n 30\n0.0\n 40\n{radius}\n 50\n{start}\n 51\n{end}\n",
+        layer = layer,
+        x = centre.x,
+        y = centre.y,
+        radius = radius,
+        start = start,
+        end = end,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use na::{Matrix4, Point3};
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineSegment2, LineSegment3};
+
+    fn segment(
+        from: (f32, f32),
+        to: (f32, f32),
+        visibility: LineVisibility,
+    ) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    // a visible line and an obscured corner
+    fn drawing() -> Vec<LineSegmentCategorized> {
+        vec![
+            segment((0.0, 600.0), (100.0, 600.0), LineVisibility::VISIBLE),
+            segment((200.0, 300.0), (300.0, 300.0), LineVisibility::OBSCURED),
+            segment((300.0, 300.0), (300.0, 200.0), LineVisibility::OBSCURED),
+        ]
+    }
+
+    /// The group code and value pairs, with the codes' padding trimmed
+    fn groups(dxf: &str) -> Vec<(String, String)> {
+        let lines: Vec<&str> = dxf.lines().collect();
+
+        lines
+            .chunks_exact(2)
+            .map(|pair| (pair[0].trim().to_owned(), pair[1].to_owned()))
+            .collect()
+    }
+
+    fn values(groups: &[(String, String)], code: &str) -> Vec<String> {
+        groups
+            .iter()
+            .filter(|(group_code, _)| group_code == code)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    #[test]
+    fn writes_r12_sections() {
+        let dxf =
+            screen_space_lines_to_dxf(&drawing(), &Scene::new_test(), &DxfConfig::new_default())
+                .unwrap();
+        let groups = groups(&dxf);
+
+        assert_eq!(dxf.lines().count() % 2, 0);
+        assert_eq!(
+            values(&groups, "2")[..4],
+            ["HEADER", "TABLES", "LTYPE", "CONTINUOUS"]
+        );
+        assert!(groups.contains(&("1".to_owned(), "AC1009".to_owned())));
+        assert_eq!(groups.last().unwrap(), &("0".to_owned(), "EOF".to_owned()));
+
+        let structure: Vec<String> = values(&groups, "0")
+            .into_iter()
+            .filter(|value| value == "SECTION" || value == "ENDSEC")
+            .collect();
+        assert_eq!(structure, ["SECTION", "ENDSEC"].repeat(3));
+    }
+
+    #[test]
+    fn puts_each_visibility_on_its_layer() {
+        let dxf =
+            screen_space_lines_to_dxf(&drawing(), &Scene::new_test(), &DxfConfig::new_default())
+                .unwrap();
+        let groups = groups(&dxf);
+        let entities_start = groups
+            .iter()
+            .position(|group| group == &("2".to_owned(), "ENTITIES".to_owned()))
+            .unwrap();
+        let entities = &groups[entities_start + 1..];
+
+        assert_eq!(
+            values(&groups[..entities_start], "2")[4..],
+            ["HIDDEN", "LAYER", "VISIBLE", "HIDDEN"]
+        );
+        assert_eq!(
+            values(entities, "0"),
+            ["LINE", "POLYLINE", "VERTEX", "VERTEX", "VERTEX", "SEQEND", "ENDSEC", "EOF"]
+        );
+        assert_eq!(
+            values(entities, "8"),
+            ["VISIBLE", "HIDDEN", "HIDDEN", "HIDDEN", "HIDDEN", "HIDDEN"]
+        );
+    }
+
+    #[test]
+    fn leaves_out_obscured_lines() {
+        let config = DxfConfig {
+            include_obscured: false,
+            ..DxfConfig::new_default()
+        };
+
+        let dxf = screen_space_lines_to_dxf(&drawing(), &Scene::new_test(), &config).unwrap();
+
+        assert!(!dxf.contains("POLYLINE"));
+        assert!(!dxf.contains("  8\nHIDDEN"));
+    }
+
+    #[test]
+    fn draws_in_world_units() {
+        let scene = Scene::new_test();
+        let scale = scene.screen_space_scale();
+        let segments = vec![segment(
+            (scale, scene.height),
+            (scale * 3.0, scene.height - scale * 2.0),
+            LineVisibility::VISIBLE,
+        )];
+
+        let dxf = screen_space_lines_to_dxf(&segments, &scene, &DxfConfig::new_default()).unwrap();
+        let groups = groups(&dxf);
+        let coordinate = |code: &str| values(&groups, code)[0].parse::<f32>().unwrap();
+
+        assert_relative_eq!(coordinate("10"), 1.0, epsilon = 1e-4);
+        assert_relative_eq!(coordinate("20"), 0.0, epsilon = 1e-4);
+        assert_relative_eq!(coordinate("11"), 3.0, epsilon = 1e-4);
+        assert_relative_eq!(coordinate("21"), 2.0, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn writes_arcs_anticlockwise() {
+        let (right, top) = (Point2::new(1.0, 0.0), Point2::new(0.0, 1.0));
+        let angles = |arc: String| {
+            let groups = groups(&arc);
+            (values(&groups, "50"), values(&groups, "51"))
+        };
+
+        let anticlockwise = arc_entity(&right, &top, &Point2::origin(), 1.0, PI / 2.0, "VISIBLE");
+        let clockwise = arc_entity(&top, &right, &Point2::origin(), 1.0, -PI / 2.0, "VISIBLE");
+
+        assert_eq!(
+            angles(anticlockwise),
+            (vec!["0".to_owned()], vec!["90".to_owned()])
+        );
+        assert_eq!(
+            angles(clockwise),
+            (vec!["0".to_owned()], vec!["90".to_owned()])
+        );
+    }
+
+    #[test]
+    fn refuses_perspective() {
+        let mut scene = Scene::new_test();
+        scene.projection_matrix = Matrix4::new_perspective(4.0 / 3.0, 0.8, 0.1, 1000.0);
+
+        assert!(
+            screen_space_lines_to_dxf(&drawing(), &scene, &DxfConfig::new_default())
+                == Err(MeshToSvgError::NotOrthographic { output: "dxf" })
+        );
+    }
+}
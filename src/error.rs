@@ -0,0 +1,239 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use wasm_bindgen::JsValue;
+
+use crate::options::InvalidOption;
+
+/// Problems with the meshes, matrices or options given, which would otherwise panic part way
+/// through a drawing
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshToSvgError {
+    /// an index pointing past the end of the vertices it indexes
+    IndexOutOfRange {
+        buffer: &'static str,
+        index: usize,
+        vertex_count: usize,
+    },
+    /// a buffer that doesn't split evenly into the items it holds, e.g. vertices not in threes
+    BufferLength {
+        buffer: &'static str,
+        length: usize,
+        multiple_of: usize,
+    },
+    /// fewer normals than vertices, as each vertex needs its own normal
+    MissingNormals {
+        normal_count: usize,
+        vertex_count: usize,
+    },
+    /// a matrix without the 16 values of a column major 4x4 matrix
+    MatrixLength {
+        matrix: &'static str,
+        length: usize,
+    },
+    /// a vertex, normal or matrix value that's NaN or infinite
+    NonFiniteValue {
+        buffer: &'static str,
+    },
+    /// the view, projection and world matrices combine into a transform that can't be undone, e.g.
+    /// one with a scale of zero
+    NonInvertibleTransform,
+    /// an output measured in world units, which a perspective projection has no single scale for
+    NotOrthographic {
+        output: &'static str,
+    },
+    InvalidOption(InvalidOption),
+    /// a drawing that couldn't be written out in its format, e.g. a png without any pixels
+    Encoding {
+        output: &'static str,
+        message: String,
+    },
+}
+
+impl Display for MeshToSvgError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshToSvgError::IndexOutOfRange {
+                buffer,
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "{} has index {} but there are only {} vertices",
+                buffer, index, vertex_count
+            ),
+            MeshToSvgError::BufferLength {
+                buffer,
+                length,
+                multiple_of,
+            } => write!(
+                f,
+                "{} has {} values, which isn't a multiple of {}",
+                buffer, length, multiple_of
+            ),
+            MeshToSvgError::MissingNormals {
+                normal_count,
+                vertex_count,
+            } => write!(
+                f,
+                "mesh has {} normals for {} vertices",
+                normal_count, vertex_count
+            ),
+            MeshToSvgError::MatrixLength { matrix, length } => {
+                write!(f, "{} has {} values instead of 16", matrix, length)
+            }
+            MeshToSvgError::NonFiniteValue { buffer } => {
+                write!(f, "NaN or infinite value in {}", buffer)
+            }
+            MeshToSvgError::NonInvertibleTransform => write!(
+                f,
+                "the view, projection and mesh world matrices can't be inverted"
+            ),
+            MeshToSvgError::NotOrthographic { output } => write!(
+                f,
+                "{} needs an orthographic projection to measure in world units",
+                output
+            ),
+            MeshToSvgError::InvalidOption(invalid_option) => invalid_option.fmt(f),
+            MeshToSvgError::Encoding { output, message } => {
+                write!(f, "couldn't write the {}: {}", output, message)
+            }
+        }
+    }
+}
+
+impl Error for MeshToSvgError {}
+
+impl From<InvalidOption> for MeshToSvgError {
+    fn from(invalid_option: InvalidOption) -> MeshToSvgError {
+        MeshToSvgError::InvalidOption(invalid_option)
+    }
+}
+
+/// Thrown as a js `Error` with the message, rather than aborting the wasm module
+impl From<MeshToSvgError> for JsValue {
+    fn from(error: MeshToSvgError) -> JsValue {
+        js_sys::Error::new(&error.to_string()).into()
+    }
+}
+
+/// Checks a flat buffer holds whole items of the given size, all of them finite
+pub fn check_buffer(
+    buffer: &'static str,
+    values: &[f32],
+    multiple_of: usize,
+) -> Result<(), MeshToSvgError> {
+    if !values.chunks_exact(multiple_of).remainder().is_empty() {
+        return Err(MeshToSvgError::BufferLength {
+            buffer,
+            length: values.len(),
+            multiple_of,
+        });
+    }
+
+    match values.iter().all(|value| value.is_finite()) {
+        true => Ok(()),
+        false => Err(MeshToSvgError::NonFiniteValue { buffer }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_each_error() {
+        let messages = [
+            (
+                MeshToSvgError::IndexOutOfRange {
+                    buffer: "mesh indices",
+                    index: 7,
+                    vertex_count: 3,
+                },
+                "mesh indices has index 7 but there are only 3 vertices",
+            ),
+            (
+                MeshToSvgError::BufferLength {
+                    buffer: "mesh vertices",
+                    length: 10,
+                    multiple_of: 3,
+                },
+                "mesh vertices has 10 values, which isn't a multiple of 3",
+            ),
+            (
+                MeshToSvgError::MissingNormals {
+                    normal_count: 2,
+                    vertex_count: 3,
+                },
+                "mesh has 2 normals for 3 vertices",
+            ),
+            (
+                MeshToSvgError::MatrixLength {
+                    matrix: "view matrix",
+                    length: 9,
+                },
+                "view matrix has 9 values instead of 16",
+            ),
+            (
+                MeshToSvgError::NonFiniteValue {
+                    buffer: "mesh normals",
+                },
+                "NaN or infinite value in mesh normals",
+            ),
+            (
+                MeshToSvgError::NonInvertibleTransform,
+                "the view, projection and mesh world matrices can't be inverted",
+            ),
+            (
+                MeshToSvgError::NotOrthographic { output: "dxf" },
+                "dxf needs an orthographic projection to measure in world units",
+            ),
+            (
+                MeshToSvgError::Encoding {
+                    output: "png",
+                    message: "image is empty".to_owned(),
+                },
+                "couldn't write the png: image is empty",
+            ),
+        ];
+
+        for (error, message) in &messages {
+            assert_eq!(error.to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn wraps_invalid_options() {
+        let invalid_option = InvalidOption {
+            option: "margin".to_owned(),
+            message: "can't be negative, got -1".to_owned(),
+        };
+        let error = MeshToSvgError::from(invalid_option.clone());
+
+        assert_eq!(error, MeshToSvgError::InvalidOption(invalid_option));
+        assert_eq!(
+            error.to_string(),
+            "invalid margin: can't be negative, got -1"
+        );
+    }
+
+    #[test]
+    fn checks_buffers() {
+        assert_eq!(check_buffer("mesh vertices", &[0.0; 6], 3), Ok(()));
+        assert_eq!(
+            check_buffer("mesh vertices", &[0.0; 5], 3),
+            Err(MeshToSvgError::BufferLength {
+                buffer: "mesh vertices",
+                length: 5,
+                multiple_of: 3,
+            })
+        );
+        assert_eq!(
+            check_buffer("mesh normals", &[0.0, std::f32::INFINITY, 0.0], 3),
+            Err(MeshToSvgError::NonFiniteValue {
+                buffer: "mesh normals"
+            })
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::{BTreeMap, HashMap};
+
+use na::{Matrix4, Point2, Point3, Vector3};
+
+use crate::colour::Colour;
+use crate::mesh::{Facet, Mesh, WeldedMesh};
+use crate::scene::Scene;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum FillMode {
+    /// each visible face shaded by how directly it faces the light
+    Flat,
+    /// a single solid fill of the area the meshes cover, outlined rather than drawn face by face
+    Silhouette,
+}
+
+pub struct FillConfig {
+    pub mode: FillMode,
+    /// colour of faces lit head on, or of the whole silhouette
+    pub colour: Colour,
+    /// colour of faces turned away from the light
+    pub shadow: Colour,
+    /// direction towards the light relative to the view, with x to the right, y up and z towards
+    /// the viewer
+    pub light_direction: Vector3<f32>,
+    /// number of distinct shades faces are rounded to, continuous when `None`
+    pub shades: Option<u32>,
+}
+
+/// A screen space polygon to be filled beneath the lines. Polygons all wind the same way on screen,
+/// apart from the outlines of holes, so that overlapping polygons fill with the nonzero rule
+#[derive(Clone)]
+pub struct FilledFace {
+    pub points: Vec<Point2<f32>>,
+    pub colour: String,
+}
+
+/// A facet turned towards the viewer
+pub struct LitFacet<'a> {
+    pub facet: &'a Facet,
+    /// clip space depth of the facet's centre, greater values are further away
+    pub depth: f32,
+    /// lambertian reflectance, from 0 facing away from the light to 1 facing straight at it
+    pub intensity: f32,
+}
+
+impl FillMode {
+    pub fn from_name(name: &str) -> Option<FillMode> {
+        match name.to_lowercase().as_str() {
+            "flat" => Some(FillMode::Flat),
+            "silhouette" => Some(FillMode::Silhouette),
+            _ => None,
+        }
+    }
+}
+
+impl FillConfig {
+    pub fn new_default() -> FillConfig {
+        FillConfig {
+            mode: FillMode::Flat,
+            colour: Colour::rgb(0xf0, 0xf0, 0xf0),
+            shadow: Colour::rgb(0x80, 0x80, 0x80),
+            // from above left, slightly in front
+            light_direction: Vector3::new(-0.5, 0.7, 1.0),
+            shades: None,
+        }
+    }
+}
+
+impl FilledFace {
+    /// The same face with its points moved into another space, e.g. onto a page
+    pub fn transformed<F>(&self, transform: F) -> FilledFace
+    where
+        F: Fn(&Point2<f32>) -> Point2<f32>,
+    {
+        FilledFace {
+            points: self.points.iter().map(transform).collect(),
+            colour: self.colour.clone(),
+        }
+    }
+}
+
+/// Projects the faces of the meshes turned towards the viewer, ordered back to front across all of
+/// them so that nearer faces are painted over further ones. Sorting whole faces by depth is only
+/// approximate where faces overlap in depth, but the line work drawn on top hides most of that.
+///
+/// Silhouettes are instead the outlines of the area each mesh covers, see `silhouette_outlines`
+pub fn find_filled_faces(meshes: &[&Mesh], scene: &Scene, config: &FillConfig) -> Vec<FilledFace> {
+    if config.mode == FillMode::Silhouette {
+        return meshes
+            .iter()
+            .flat_map(|mesh| silhouette_outlines(mesh, scene))
+            .map(|points| FilledFace {
+                points,
+                colour: config.colour.to_hex(),
+            })
+            .collect();
+    }
+
+    let mut facets: Vec<LitFacet> = meshes
+        .iter()
+        .flat_map(|mesh| lit_facets(mesh, scene, &config.light_direction))
+        .collect();
+    facets.sort_by(|a, b| {
+        b.depth
+            .partial_cmp(&a.depth)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    facets
+        .iter()
+        .map(|lit_facet| {
+            let colour = config
+                .shadow
+                .lerp(&config.colour, quantise(lit_facet.intensity, config.shades));
+
+            let mut points: Vec<Point2<f32>> = lit_facet
+                .facet
+                .points
+                .iter()
+                .map(|point| scene.project_point(point))
+                .collect();
+            if signed_area(&points[0], &points[1], &points[2]) < 0.0 {
+                points.reverse();
+            }
+
+            FilledFace {
+                points,
+                colour: colour.to_hex(),
+            }
+        })
+        .collect()
+}
+
+/// The outlines of the area covered by the faces of the mesh turned towards the viewer. Edges
+/// between two of those faces are inside the area and left out, so faces are only merged where
+/// they share vertices. Outlines wind the same way on screen around the area and the other way
+/// around holes in it
+fn silhouette_outlines(mesh: &Mesh, scene: &Scene) -> Vec<Vec<Point2<f32>>> {
+    let welded = WeldedMesh::new(mesh);
+    let projected: Vec<Point2<f32>> = welded
+        .points
+        .iter()
+        .map(|point| scene.project_point(point))
+        .collect();
+
+    // how many more times each edge runs one way than the other
+    let mut edge_counts: HashMap<(usize, usize), i32> = HashMap::new();
+
+    for (triangle, normal) in welded.triangles.iter().zip(&welded.face_normals) {
+        let centroid = Point3::from(
+            (welded.points[triangle[0]].coords
+                + welded.points[triangle[1]].coords
+                + welded.points[triangle[2]].coords)
+                / 3.0,
+        );
+        if normal.dot(&scene.towards_viewer(&centroid)) <= 0.0 {
+            continue;
+        }
+
+        let [a, b, c] = *triangle;
+        let area = signed_area(&projected[a], &projected[b], &projected[c]);
+        let corners = match area {
+            area if area > 0.0 => [a, b, c],
+            area if area < 0.0 => [a, c, b],
+            _ => continue,
+        };
+
+        for i in 0..3 {
+            let (from, to) = (corners[i], corners[(i + 1) % 3]);
+            match from < to {
+                true => *edge_counts.entry((from, to)).or_insert(0) += 1,
+                false => *edge_counts.entry((to, from)).or_insert(0) -= 1,
+            }
+        }
+    }
+
+    // sorted so that the outlines come out in the same order every time
+    let mut next_vertices: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (&(low, high), &count) in &edge_counts {
+        let (from, to) = match count > 0 {
+            true => (low, high),
+            false => (high, low),
+        };
+
+        for _ in 0..count.abs() {
+            next_vertices.entry(from).or_default().push(to);
+        }
+    }
+    for next in next_vertices.values_mut() {
+        next.sort_unstable_by(|a, b| b.cmp(a));
+    }
+
+    // every vertex has as many outline edges leaving as arriving, so walking from any vertex
+    // always leads back to it
+    let starts: Vec<usize> = next_vertices.keys().cloned().collect();
+    let mut outlines = Vec::new();
+
+    for start in starts {
+        while let Some(mut vertex) = next_vertices.get_mut(&start).and_then(Vec::pop) {
+            let mut outline = vec![projected[start]];
+
+            while vertex != start {
+                outline.push(projected[vertex]);
+                vertex = match next_vertices.get_mut(&vertex).and_then(Vec::pop) {
+                    Some(next) => next,
+                    None => break,
+                };
+            }
+
+            outlines.push(outline);
+        }
+    }
+
+    outlines
+}
+
+/// Twice the area of the screen space triangle, positive when it winds clockwise on screen
+fn signed_area(a: &Point2<f32>, b: &Point2<f32>, c: &Point2<f32>) -> f32 {
+    (b - a).perp(&(c - a))
+}
+
+/// The facets turned towards the viewer, lit from a direction given relative to the view as for
+/// `FillConfig::light_direction`
+pub fn lit_facets<'a>(
+    mesh: &'a Mesh,
+    scene: &Scene,
+    light_direction: &Vector3<f32>,
+) -> Vec<LitFacet<'a>> {
+    let transformation = scene.transformation_matrix();
+    let inverse = match transformation.try_inverse() {
+        Some(inverse) => inverse,
+        None => return vec![],
+    };
+
+    let light = light_in_mesh_space(mesh, &transformation, &inverse, light_direction);
+
+    mesh.facets
+        .iter()
+        .filter_map(|facet| {
+            let centroid = Point3::from(
+                (facet.points[0].coords + facet.points[1].coords + facet.points[2].coords) / 3.0,
+            );
+            let projected = transformation.transform_point(&centroid);
+            let normal = facet.normal.try_normalize(f32::EPSILON)?;
+
+            if normal.dot(&scene.towards_viewer(&centroid)) <= 0.0 {
+                return None;
+            }
+
+            Some(LitFacet {
+                facet,
+                depth: projected.z,
+                intensity: normal.dot(&light).max(0.0),
+            })
+        })
+        .collect()
+}
+
+/// Converts the view relative light direction into mesh space, using the view's axes at the centre
+/// of the mesh
+fn light_in_mesh_space(
+    mesh: &Mesh,
+    transformation: &Matrix4<f32>,
+    inverse: &Matrix4<f32>,
+    light_direction: &Vector3<f32>,
+) -> Vector3<f32> {
+    let centre = match mesh.points.len() {
+        0 => Point3::origin(),
+        count => Point3::from(
+            mesh.points
+                .iter()
+                .fold(Vector3::zeros(), |sum, point| sum + point.coords)
+                / count as f32,
+        ),
+    };
+    let projected = transformation.transform_point(&centre);
+
+    let axis = |offset: Vector3<f32>| {
+        let moved = inverse.transform_point(&(projected + offset));
+        (moved - centre)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::zeros)
+    };
+
+    let light = axis(Vector3::new(1.0, 0.0, 0.0)) * light_direction.x
+        + axis(Vector3::new(0.0, 1.0, 0.0)) * light_direction.y
+        + axis(Vector3::new(0.0, 0.0, -1.0)) * light_direction.z;
+
+    light.try_normalize(f32::EPSILON).unwrap_or(light)
+}
+
+fn quantise(intensity: f32, shades: Option<u32>) -> f32 {
+    match shades {
+        Some(shades) if shades > 1 => {
+            let steps = (shades - 1) as f32;
+            (intensity * steps).round() / steps
+        }
+        Some(_) => 1.0,
+        None => intensity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // looking down -z, with x and y from -10 to 10 across the canvas
+    fn front_scene() -> Scene {
+        #[rustfmt::skip]
+        let projection_matrix = Matrix4::new(
+            0.1, 0.0,  0.0, 0.0,
+            0.0, 0.1,  0.0, 0.0,
+            0.0, 0.0, -0.1, 0.0,
+            0.0, 0.0,  0.0, 1.0,
+        );
+
+        Scene::new(
+            200.0,
+            200.0,
+            Matrix4::identity(),
+            projection_matrix,
+            Matrix4::identity(),
+        )
+    }
+
+    // triangles in the z = 0 plane facing the viewer, however they're wound
+    fn flat_mesh(points: &[(f32, f32)], triangles: &[[usize; 3]]) -> Mesh {
+        Mesh::new(
+            Some(triangles.iter().flatten().cloned().collect()),
+            points.iter().flat_map(|&(x, y)| vec![x, y, 0.0]).collect(),
+            [0.0, 0.0, 1.0].repeat(points.len()),
+        )
+        .unwrap()
+    }
+
+    // a square with a square hole in the middle
+    fn frame() -> Mesh {
+        flat_mesh(
+            &[
+                (-5.0, -5.0),
+                (5.0, -5.0),
+                (5.0, 5.0),
+                (-5.0, 5.0),
+                (-2.0, -2.0),
+                (2.0, -2.0),
+                (2.0, 2.0),
+                (-2.0, 2.0),
+            ],
+            &[
+                [0, 1, 5],
+                [0, 5, 4],
+                [1, 2, 6],
+                [1, 6, 5],
+                [2, 3, 7],
+                [2, 7, 6],
+                [3, 0, 4],
+                [3, 4, 7],
+            ],
+        )
+    }
+
+    fn polygon_area(points: &[Point2<f32>]) -> f32 {
+        (0..points.len())
+            .map(|i| signed_area(&points[0], &points[i], &points[(i + 1) % points.len()]))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    #[test]
+    fn outlines_faces_as_one_area() {
+        // wound opposite ways, which used to cut holes where faces overlapped
+        let square = flat_mesh(
+            &[(-5.0, -5.0), (5.0, -5.0), (5.0, 5.0), (-5.0, 5.0)],
+            &[[0, 1, 2], [0, 3, 2]],
+        );
+
+        let outlines = silhouette_outlines(&square, &front_scene());
+
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(outlines[0].len(), 4);
+        // 10 units across is 100 pixels
+        assert_relative_eq!(polygon_area(&outlines[0]), 10000.0, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn winds_holes_the_other_way() {
+        let outlines = silhouette_outlines(&frame(), &front_scene());
+        let mut areas: Vec<f32> = outlines
+            .iter()
+            .map(|outline| polygon_area(outline))
+            .collect();
+        areas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        assert_eq!(areas.len(), 2);
+        assert_relative_eq!(areas[0], -1600.0, epsilon = 1e-2);
+        assert_relative_eq!(areas[1], 10000.0, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn leaves_out_faces_turned_away() {
+        let mut back = frame();
+        for facet in &mut back.facets {
+            facet.normal = -facet.normal;
+        }
+
+        assert!(silhouette_outlines(&back, &front_scene()).is_empty());
+    }
+
+    #[test]
+    fn fills_silhouettes_with_one_colour() {
+        let config = FillConfig {
+            mode: FillMode::Silhouette,
+            colour: Colour::rgb(0x12, 0x34, 0x56),
+            ..FillConfig::new_default()
+        };
+
+        let faces = find_filled_faces(&[&frame(), &frame()], &front_scene(), &config);
+
+        assert_eq!(faces.len(), 4);
+        assert!(faces.iter().all(|face| face.colour == "#123456"));
+    }
+
+    #[test]
+    fn winds_flat_faces_the_same_way() {
+        let faces = find_filled_faces(&[&frame()], &front_scene(), &FillConfig::new_default());
+
+        assert_eq!(faces.len(), 8);
+        assert!(faces.iter().all(|face| polygon_area(&face.points) > 0.0));
+    }
+}
@@ -0,0 +1,230 @@
+use na::Point2;
+
+use crate::lines::{LineSegmentCategorized, LineVisibility};
+use crate::path_order::optimise_path_order;
+use crate::polyline::chain_line_segments;
+use crate::svg_renderer::{scale_screen_space_lines, SvgConfig};
+
+pub enum GcodeTool {
+    /// A pen lifted and lowered with arbitrary commands, e.g. a servo or z axis move
+    Pen { up: String, down: String },
+    /// A laser switched on with `M3 S<power>` and off with `M5`
+    Laser,
+}
+
+pub struct GcodeLineConfig {
+    /// mm/min
+    pub feed_rate: f32,
+    /// spindle/laser power, only used with `GcodeTool::Laser`
+    pub power: f32,
+}
+
+pub struct GcodeConfig {
+    pub tool: GcodeTool,
+    /// mm/min
+    pub travel_rate: f32,
+    pub visible: GcodeLineConfig,
+    pub obscured: Option<GcodeLineConfig>,
+    pub optimise_path_order: bool,
+}
+
+impl GcodeConfig {
+    pub fn new_default() -> GcodeConfig {
+        GcodeConfig {
+            tool: GcodeTool::Pen {
+                up: "G0 Z5".to_owned(),
+                down: "G1 Z0 F1000".to_owned(),
+            },
+            travel_rate: 3000.0,
+            visible: GcodeLineConfig {
+                feed_rate: 1500.0,
+                power: 1000.0,
+            },
+            obscured: None,
+            optimise_path_order: true,
+        }
+    }
+}
+
+/// Scales the lines to fit the svg config's width and height, treating its units as millimetres,
+/// and writes them out as G-code
+pub fn screen_space_lines_to_gcode(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+    gcode_config: &GcodeConfig,
+) -> String {
+    let scaled = scale_screen_space_lines(screen_space_lines, svg_config);
+    line_segments_to_gcode(&scaled, svg_config.height as f32, gcode_config)
+}
+
+/// Segments are expected in millimetres with the origin at the top left, as for svg. Machine
+/// coordinates have the origin at the bottom left, so `page_height` is needed to flip the y axis
+pub fn line_segments_to_gcode(
+    segments: &[LineSegmentCategorized],
+    page_height: f32,
+    config: &GcodeConfig,
+) -> String {
+    let mut polylines = chain_line_segments(segments);
+
+    if config.optimise_path_order {
+        polylines = optimise_path_order(polylines);
+    }
+
+    let mut gcode = vec![
+        "G21 ; units in mm".to_owned(),
+        "G90 ; absolute positioning".to_owned(),
+        tool_up(&config.tool),
+    ];
+
+    for polyline in &polylines {
+        let line_config = match polyline.visibility {
+            LineVisibility::VISIBLE => &config.visible,
+            LineVisibility::OBSCURED => match &config.obscured {
+                Some(line_config) => line_config,
+                None => continue,
+            },
+        };
+
+        let (x, y) = to_machine_coordinates(&polyline.points[0], page_height);
+        gcode.push(format!("G0 X{:.3} Y{:.3} F{}", x, y, config.travel_rate));
+        gcode.push(tool_down(&config.tool, line_config));
+
+        for point in &polyline.points[1..] {
+            let (x, y) = to_machine_coordinates(point, page_height);
+            gcode.push(format!("G1 X{:.3} Y{:.3} F{}", x, y, line_config.feed_rate));
+        }
+
+        gcode.push(tool_up(&config.tool));
+    }
+
+    gcode.push("G0 X0 Y0".to_owned());
+    gcode.push("M2 ; end of program".to_owned());
+
+    gcode.join("\n")
+}
+
+fn tool_up(tool: &GcodeTool) -> String {
+    match tool {
+        GcodeTool::Pen { up, .. } => up.to_owned(),
+        GcodeTool::Laser => "M5".to_owned(),
+    }
+}
+
+fn tool_down(tool: &GcodeTool, line_config: &GcodeLineConfig) -> String {
+    match tool {
+        GcodeTool::Pen { down, .. } => down.to_owned(),
+        GcodeTool::Laser => format!("M3 S{}", line_config.power),
+    }
+}
+
+fn to_machine_coordinates(point: &Point2<f32>, page_height: f32) -> (f32, f32) {
+    (point.x, page_height - point.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineSegment2, LineSegment3};
+
+    fn segment(
+        from: (f32, f32),
+        to: (f32, f32),
+        visibility: LineVisibility,
+    ) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    fn drawing() -> Vec<LineSegmentCategorized> {
+        vec![
+            segment((0.0, 0.0), (1.0, 0.0), LineVisibility::VISIBLE),
+            segment((1.0, 0.0), (1.0, 2.0), LineVisibility::VISIBLE),
+            segment((2.0, 2.0), (3.0, 2.0), LineVisibility::OBSCURED),
+        ]
+    }
+
+    #[test]
+    fn lifts_the_pen_between_paths() {
+        let config = GcodeConfig {
+            optimise_path_order: false,
+            ..GcodeConfig::new_default()
+        };
+
+        assert_eq!(
+            line_segments_to_gcode(&drawing(), 10.0, &config),
+            [
+                "G21 ; units in mm",
+                "G90 ; absolute positioning",
+                "G0 Z5",
+                "G0 X0.000 Y10.000 F3000",
+                "G1 Z0 F1000",
+                "G1 X1.000 Y10.000 F1500",
+                "G1 X1.000 Y8.000 F1500",
+                "G0 Z5",
+                "G0 X0 Y0",
+                "M2 ; end of program",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn switches_a_laser_with_the_power_of_each_visibility() {
+        let config = GcodeConfig {
+            tool: GcodeTool::Laser,
+            obscured: Some(GcodeLineConfig {
+                feed_rate: 3000.0,
+                power: 200.0,
+            }),
+            optimise_path_order: false,
+            ..GcodeConfig::new_default()
+        };
+
+        let gcode = line_segments_to_gcode(&drawing(), 10.0, &config);
+        let lines: Vec<&str> = gcode.lines().collect();
+
+        assert_eq!(lines[2], "M5");
+        assert_eq!(lines[4], "M3 S1000");
+        assert_eq!(
+            lines[8..12],
+            [
+                "G0 X2.000 Y8.000 F3000",
+                "M3 S200",
+                "G1 X3.000 Y8.000 F3000",
+                "M5"
+            ]
+        );
+    }
+
+    #[test]
+    fn scales_to_the_page_in_millimetres() {
+        let svg_config = SvgConfig {
+            width: 100,
+            height: 50,
+            margin: 0,
+            fit_lines: false,
+            ..SvgConfig::new_default(200, 100)
+        };
+        let segments = vec![segment((0.0, 0.0), (200.0, 100.0), LineVisibility::VISIBLE)];
+
+        let gcode =
+            screen_space_lines_to_gcode(&segments, &svg_config, &GcodeConfig::new_default());
+        let lines: Vec<&str> = gcode.lines().collect();
+
+        assert_eq!(lines[3], "G0 X0.000 Y50.000 F3000");
+        assert_eq!(lines[5], "G1 X100.000 Y0.000 F1500");
+    }
+}
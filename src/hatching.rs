@@ -0,0 +1,416 @@
+use na::{Point2, Point3, Vector2, Vector3};
+
+use crate::error::MeshToSvgError;
+use crate::fill::lit_facets;
+use crate::lines::{
+    dedupe_lines, split_lines_by_intersection, EdgeKind, LineSegment2, LineSegment3,
+    LineSegmentCategorized, LineVisibility, ProjectedLine,
+};
+use crate::mesh::Mesh;
+use crate::scene::Scene;
+use crate::{partition_visibility, SceneObject};
+
+pub struct HatchConfig {
+    /// direction towards the light relative to the view, as for `FillConfig::light_direction`
+    pub light_direction: Vector3<f32>,
+    /// distance between hatch lines on the darkest faces, in screen space units
+    pub spacing: f32,
+    /// angle of the hatch lines in degrees, anticlockwise from horizontal
+    pub angle: f32,
+    /// number of tones faces are rounded to. Each lighter tone doubles the spacing, and the
+    /// lightest is left unhatched
+    pub tones: u32,
+    /// darkness from 0 to 1 above which a second set of lines is crossed over the first
+    pub cross_hatch_from: Option<f32>,
+}
+
+impl HatchConfig {
+    pub fn new_default() -> HatchConfig {
+        HatchConfig {
+            // from above left, slightly in front
+            light_direction: Vector3::new(-0.5, 0.7, 1.0),
+            spacing: 4.0,
+            angle: 45.0,
+            tones: 4,
+            cross_hatch_from: Some(0.75),
+        }
+    }
+}
+
+/// Hatches the faces turned towards the viewer, more densely the further they face away from the
+/// light. Hatch lines are split where they cross the given lines, which should include the
+/// silhouettes, and only the visible parts are kept. Lines of the same spacing line up across
+/// neighbouring faces so that they chain into long strokes. Each object's hatches are tagged with
+/// its id, and can be hidden by any of the objects
+pub fn find_hatch_lines(
+    objects: &[SceneObject],
+    scene: &Scene,
+    edge_lines: &[LineSegmentCategorized],
+    config: &HatchConfig,
+) -> Result<Vec<LineSegmentCategorized>, MeshToSvgError> {
+    let angle = config.angle.to_radians();
+    // screen space is y down, so anticlockwise on screen is a negative y
+    let across = Vector2::new(angle.sin(), angle.cos());
+    let cross_across = Vector2::new(across.y, -across.x);
+
+    let mut hatches = Vec::new();
+
+    let lit_facets = objects.iter().flat_map(|object| {
+        lit_facets(object.mesh, scene, &config.light_direction)
+            .into_iter()
+            .map(move |lit_facet| (object.id, lit_facet))
+    });
+
+    for (object_id, lit_facet) in lit_facets {
+        let darkness = 1.0 - lit_facet.intensity;
+        let tone = (darkness * (config.tones.max(1) - 1) as f32).round() as u32;
+
+        if tone == 0 {
+            continue;
+        }
+
+        let spacing = config.spacing * 2f32.powi((config.tones - 1 - tone) as i32);
+        let screen_points: Vec<Point2<f32>> = lit_facet
+            .facet
+            .points
+            .iter()
+            .map(|point| scene.project_point(point))
+            .collect();
+
+        hatch_facet(
+            &screen_points,
+            &lit_facet.facet.points,
+            &across,
+            spacing,
+            object_id,
+            &mut hatches,
+        );
+
+        if config.cross_hatch_from.is_some_and(|from| darkness >= from) {
+            hatch_facet(
+                &screen_points,
+                &lit_facet.facet.points,
+                &cross_across,
+                spacing,
+                object_id,
+                &mut hatches,
+            );
+        }
+    }
+
+    if hatches.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // splitting against the edges also splits the edges against the hatches, but only the hatch
+    // lines are kept
+    let mut projected = hatches;
+    projected.extend(
+        edge_lines
+            .iter()
+            .filter(|line| line.visibility == LineVisibility::VISIBLE)
+            .map(|line| ProjectedLine {
+                screen_space: line.line_segment,
+                mesh_space: line.mesh_space,
+                kind: line.kind,
+                object_id: line.object_id,
+            }),
+    );
+
+    let split_hatches: Vec<_> = split_lines_by_intersection(&dedupe_lines(projected)?)
+        .into_iter()
+        .filter(|split| split.projected_line.kind == EdgeKind::HATCH)
+        .collect();
+
+    let meshes: Vec<&Mesh> = objects.iter().map(|object| object.mesh).collect();
+    let mut segments = partition_visibility(&meshes, scene, &split_hatches)?;
+    segments.retain(|segment| segment.visibility == LineVisibility::VISIBLE);
+
+    Ok(segments)
+}
+
+/// Adds the lines of a grid perpendicular to `across` that fall within the triangle, with their
+/// mesh space positions interpolated along the triangle's edges
+fn hatch_facet(
+    screen_points: &[Point2<f32>],
+    mesh_points: &[Point3<f32>],
+    across: &Vector2<f32>,
+    spacing: f32,
+    object_id: usize,
+    hatches: &mut Vec<ProjectedLine>,
+) {
+    let offsets: Vec<f32> = screen_points
+        .iter()
+        .map(|point| point.coords.dot(across))
+        .collect();
+
+    let min = offsets.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = offsets.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+    // offsets are multiples of the spacing from the origin so that neighbouring faces line up
+    let first = (min / spacing).ceil() as i64;
+    let last = (max / spacing).floor() as i64;
+
+    for step in first..=last {
+        let offset = step as f32 * spacing;
+        let mut crossings: Vec<(Point2<f32>, Point3<f32>)> = Vec::with_capacity(2);
+
+        for (a, b) in [(0, 1), (1, 2), (2, 0)].iter().cloned() {
+            if offsets[a] == offsets[b] {
+                continue;
+            }
+
+            let t = (offset - offsets[a]) / (offsets[b] - offsets[a]);
+            if !(0.0..=1.0).contains(&t) {
+                continue;
+            }
+
+            let screen = screen_points[a] + (screen_points[b] - screen_points[a]) * t;
+
+            // a line through a corner crosses both of the edges meeting there
+            if crossings
+                .iter()
+                .any(|(point, _)| relative_eq!(*point, screen))
+            {
+                continue;
+            }
+
+            crossings.push((
+                screen,
+                mesh_points[a] + (mesh_points[b] - mesh_points[a]) * t,
+            ));
+        }
+
+        if let [(screen_from, mesh_from), (screen_to, mesh_to)] = crossings[..] {
+            hatches.push(ProjectedLine {
+                screen_space: LineSegment2 {
+                    from: screen_from,
+                    to: screen_to,
+                },
+                mesh_space: LineSegment3 {
+                    from: mesh_from,
+                    to: mesh_to,
+                },
+                kind: EdgeKind::HATCH,
+                object_id,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Matrix4;
+
+    use super::*;
+
+    // looking down -z, with x and y from -10 to 10 across the canvas
+    fn front_scene() -> Scene {
+        #[rustfmt::skip]
+        let projection_matrix = Matrix4::new(
+            0.1, 0.0,  0.0, 0.0,
+            0.0, 0.1,  0.0, 0.0,
+            0.0, 0.0, -0.1, 0.0,
+            0.0, 0.0,  0.0, 1.0,
+        );
+
+        Scene::new(
+            200.0,
+            200.0,
+            Matrix4::identity(),
+            projection_matrix,
+            Matrix4::identity(),
+        )
+    }
+
+    // a 10 unit square facing the viewer, covering 50 to 150 on screen
+    fn square() -> Mesh {
+        #[rustfmt::skip]
+        let vertices = vec![
+            -5.0, -5.0, 0.0,
+             5.0, -5.0, 0.0,
+             5.0,  5.0, 0.0,
+            -5.0,  5.0, 0.0,
+        ];
+
+        Mesh::new(
+            Some(vec![0, 1, 2, 0, 2, 3]),
+            vertices,
+            [0.0, 0.0, 1.0].repeat(4),
+        )
+        .unwrap()
+    }
+
+    fn hatch_square(
+        config: &HatchConfig,
+        edge_lines: &[LineSegmentCategorized],
+    ) -> Vec<LineSegmentCategorized> {
+        let mesh = square();
+        let objects = [SceneObject {
+            id: 3,
+            mesh: &mesh,
+            wireframe: &None,
+            edge_candidates: None,
+            feature_lines: None,
+            smooth_silhouettes: false,
+        }];
+
+        find_hatch_lines(&objects, &front_scene(), edge_lines, config).unwrap()
+    }
+
+    // lit so that the square is a third of the way to dark, the lightest hatched tone
+    fn light_tone_config() -> HatchConfig {
+        HatchConfig {
+            light_direction: Vector3::new(5f32.sqrt() / 3.0, 0.0, 2.0 / 3.0),
+            angle: 0.0,
+            ..HatchConfig::new_default()
+        }
+    }
+
+    fn distinct(values: impl Iterator<Item = f32>) -> Vec<f32> {
+        let mut values: Vec<f32> = values.map(|value| value.round()).collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        values.dedup();
+        values
+    }
+
+    #[test]
+    fn clips_lines_to_the_triangle() {
+        let screen_points = [
+            Point2::new(0.0, 0.0),
+            Point2::new(20.0, 0.0),
+            Point2::new(0.0, 20.0),
+        ];
+        let mesh_points = [
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(2.0, 0.0, 1.0),
+            Point3::new(0.0, 2.0, 1.0),
+        ];
+        let mut hatches = Vec::new();
+
+        hatch_facet(
+            &screen_points,
+            &mesh_points,
+            &Vector2::new(0.0, 1.0),
+            5.0,
+            0,
+            &mut hatches,
+        );
+
+        // the line through the far corner only touches it, so it's left out
+        let lines: Vec<(Point2<f32>, Point2<f32>)> = hatches
+            .iter()
+            .map(|hatch| (hatch.screen_space.from, hatch.screen_space.to))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (Point2::new(20.0, 0.0), Point2::new(0.0, 0.0)),
+                (Point2::new(15.0, 5.0), Point2::new(0.0, 5.0)),
+                (Point2::new(10.0, 10.0), Point2::new(0.0, 10.0)),
+                (Point2::new(5.0, 15.0), Point2::new(0.0, 15.0)),
+            ]
+        );
+        // mesh space follows along the same edges
+        assert_relative_eq!(hatches[1].mesh_space.from, Point3::new(1.5, 0.5, 1.0));
+        assert_relative_eq!(hatches[1].mesh_space.to, Point3::new(0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn lines_up_with_neighbouring_faces() {
+        let screen_points = [
+            Point2::new(0.0, 3.0),
+            Point2::new(20.0, 3.0),
+            Point2::new(0.0, 23.0),
+        ];
+        let mut hatches = Vec::new();
+
+        hatch_facet(
+            &screen_points,
+            &[Point3::origin(); 3],
+            &Vector2::new(0.0, 1.0),
+            5.0,
+            0,
+            &mut hatches,
+        );
+
+        let offsets = distinct(hatches.iter().map(|hatch| hatch.screen_space.from.y));
+        assert_eq!(offsets, [5.0, 10.0, 15.0, 20.0]);
+    }
+
+    #[test]
+    fn leaves_lit_faces_unhatched() {
+        let config = HatchConfig {
+            light_direction: Vector3::new(0.0, 0.0, 1.0),
+            ..HatchConfig::new_default()
+        };
+
+        assert!(hatch_square(&config, &[]).is_empty());
+    }
+
+    #[test]
+    fn doubles_the_spacing_for_each_lighter_tone() {
+        let hatches = hatch_square(&light_tone_config(), &[]);
+
+        // two tones lighter than the darkest, so 4 times its spacing, and all one way
+        let offsets = distinct(hatches.iter().map(|hatch| hatch.line_segment.from.y));
+        assert_eq!(offsets.len(), 6);
+        assert!(offsets.windows(2).all(|pair| pair[1] - pair[0] == 16.0));
+        assert!(hatches
+            .iter()
+            .all(|hatch| hatch.line_segment.from.y == hatch.line_segment.to.y));
+        assert!(hatches.iter().all(|hatch| hatch.kind == EdgeKind::HATCH
+            && hatch.visibility == LineVisibility::VISIBLE
+            && hatch.object_id == 3));
+    }
+
+    #[test]
+    fn cross_hatches_the_darkest_faces() {
+        let config = HatchConfig {
+            light_direction: Vector3::new(1.0, 0.0, 0.0),
+            angle: 0.0,
+            ..HatchConfig::new_default()
+        };
+
+        let hatches = hatch_square(&config, &[]);
+        let is_horizontal = |hatch: &&LineSegmentCategorized| {
+            relative_eq!(hatch.line_segment.from.y, hatch.line_segment.to.y)
+        };
+        let horizontal: Vec<&LineSegmentCategorized> =
+            hatches.iter().filter(is_horizontal).collect();
+        let vertical = hatches.len() - horizontal.len();
+
+        let offsets = distinct(horizontal.iter().map(|hatch| hatch.line_segment.from.y));
+        assert_eq!(offsets.len(), 25);
+        assert!(offsets.windows(2).all(|pair| pair[1] - pair[0] == 4.0));
+        assert!(vertical > 0);
+    }
+
+    #[test]
+    fn splits_lines_where_they_cross_edges() {
+        let edge = LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(100.0, 0.0),
+                to: Point2::new(100.0, 200.0),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(0.0, 10.0, 0.0),
+                to: Point3::new(0.0, -10.0, 0.0),
+            },
+            visibility: LineVisibility::VISIBLE,
+            kind: EdgeKind::CREASE,
+            object_id: 3,
+        };
+
+        let unsplit = hatch_square(&light_tone_config(), &[]);
+        let split = hatch_square(&light_tone_config(), &[edge]);
+
+        // the edge itself isn't kept
+        assert!(split.iter().all(|hatch| hatch.kind == EdgeKind::HATCH));
+        assert!(split.len() > unsplit.len());
+        assert!(split.iter().all(
+            |hatch| hatch.line_segment.from.x.min(hatch.line_segment.to.x) >= 100.0 - 1e-3
+                || hatch.line_segment.from.x.max(hatch.line_segment.to.x) <= 100.0 + 1e-3
+        ));
+    }
+}
@@ -0,0 +1,214 @@
+use na::Point2;
+
+use crate::lines::{EdgeKind, LineSegmentCategorized, LineVisibility};
+use crate::path_order::optimise_path_order;
+use crate::polyline::{chain_line_segments, Polyline};
+use crate::svg_renderer::{scale_screen_space_lines, SvgConfig};
+
+// HPGL addresses the page in plotter units of 0.025mm
+const PLOTTER_UNITS_PER_MM: f32 = 40.0;
+
+pub struct HpglPens {
+    pub silhouette: u32,
+    pub crease: u32,
+    pub wireframe: u32,
+    pub feature: u32,
+    pub hatch: u32,
+}
+
+impl HpglPens {
+    pub fn pen_for(&self, kind: EdgeKind) -> u32 {
+        match kind {
+            EdgeKind::SILHOUETTE => self.silhouette,
+            EdgeKind::CREASE => self.crease,
+            EdgeKind::WIREFRAME => self.wireframe,
+            EdgeKind::FEATURE => self.feature,
+            EdgeKind::HATCH => self.hatch,
+        }
+    }
+}
+
+pub struct HpglConfig {
+    pub visible: HpglPens,
+    pub obscured: Option<HpglPens>,
+    pub optimise_path_order: bool,
+}
+
+impl HpglConfig {
+    pub fn new_default() -> HpglConfig {
+        HpglConfig {
+            visible: HpglPens {
+                silhouette: 1,
+                crease: 1,
+                wireframe: 1,
+                feature: 1,
+                hatch: 1,
+            },
+            obscured: Some(HpglPens {
+                silhouette: 2,
+                crease: 2,
+                wireframe: 2,
+                feature: 2,
+                hatch: 2,
+            }),
+            optimise_path_order: true,
+        }
+    }
+}
+
+/// Scales the lines to fit the svg config's width and height, treating its units as millimetres,
+/// and writes them out as HPGL
+pub fn screen_space_lines_to_hpgl(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+    hpgl_config: &HpglConfig,
+) -> String {
+    let scaled = scale_screen_space_lines(screen_space_lines, svg_config);
+    line_segments_to_hpgl(&scaled, svg_config.height as f32, hpgl_config)
+}
+
+/// Segments are expected in millimetres with the origin at the top left, as for svg. HPGL's
+/// origin is at the bottom left, so `page_height` is needed to flip the y axis
+pub fn line_segments_to_hpgl(
+    segments: &[LineSegmentCategorized],
+    page_height: f32,
+    config: &HpglConfig,
+) -> String {
+    let mut polylines = chain_line_segments(segments);
+
+    if config.optimise_path_order {
+        polylines = optimise_path_order(polylines);
+    }
+
+    let mut hpgl = "IN;".to_owned();
+    let mut current_pen = None;
+
+    for polyline in &polylines {
+        let pen = match polyline_pen(polyline, config) {
+            Some(pen) => pen,
+            None => continue,
+        };
+
+        if current_pen != Some(pen) {
+            hpgl.push_str(format!("SP{};", pen).as_str());
+            current_pen = Some(pen);
+        }
+
+        let (x, y) = to_plotter_units(&polyline.points[0], page_height);
+        hpgl.push_str(format!("PU{},{};PD", x, y).as_str());
+
+        let coordinates: Vec<String> = polyline.points[1..]
+            .iter()
+            .map(|point| {
+                let (x, y) = to_plotter_units(point, page_height);
+                format!("{},{}", x, y)
+            })
+            .collect();
+
+        hpgl.push_str(coordinates.join(",").as_str());
+        hpgl.push(';');
+    }
+
+    // lift the pen and put it back in the carousel
+    hpgl.push_str("PU;SP0;");
+
+    hpgl
+}
+
+fn polyline_pen(polyline: &Polyline, config: &HpglConfig) -> Option<u32> {
+    match polyline.visibility {
+        LineVisibility::VISIBLE => Some(config.visible.pen_for(polyline.kind)),
+        LineVisibility::OBSCURED => config
+            .obscured
+            .as_ref()
+            .map(|pens| pens.pen_for(polyline.kind)),
+    }
+}
+
+fn to_plotter_units(point: &Point2<f32>, page_height: f32) -> (i32, i32) {
+    (
+        (point.x * PLOTTER_UNITS_PER_MM).round() as i32,
+        ((page_height - point.y) * PLOTTER_UNITS_PER_MM).round() as i32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::*;
+    use crate::lines::{LineSegment2, LineSegment3};
+
+    fn segment(
+        from: (f32, f32),
+        to: (f32, f32),
+        visibility: LineVisibility,
+    ) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    fn drawing() -> Vec<LineSegmentCategorized> {
+        vec![
+            segment((0.0, 0.0), (1.0, 0.0), LineVisibility::VISIBLE),
+            segment((1.0, 0.0), (1.0, 2.0), LineVisibility::VISIBLE),
+            segment((2.0, 2.0), (3.0, 2.0), LineVisibility::OBSCURED),
+        ]
+    }
+
+    #[test]
+    fn writes_a_pen_per_visibility() {
+        let config = HpglConfig {
+            optimise_path_order: false,
+            ..HpglConfig::new_default()
+        };
+
+        assert_eq!(
+            line_segments_to_hpgl(&drawing(), 10.0, &config),
+            "IN;SP1;PU0,400;PD40,400,40,320;SP2;PU80,320;PD120,320;PU;SP0;"
+        );
+    }
+
+    #[test]
+    fn leaves_out_obscured_lines_without_pens() {
+        let config = HpglConfig {
+            obscured: None,
+            optimise_path_order: false,
+            ..HpglConfig::new_default()
+        };
+
+        assert_eq!(
+            line_segments_to_hpgl(&drawing(), 10.0, &config),
+            "IN;SP1;PU0,400;PD40,400,40,320;PU;SP0;"
+        );
+    }
+
+    #[test]
+    fn scales_to_the_page_in_millimetres() {
+        // half size, with the canvas's bottom right corner at the page's
+        let svg_config = SvgConfig {
+            width: 100,
+            height: 50,
+            margin: 0,
+            fit_lines: false,
+            ..SvgConfig::new_default(200, 100)
+        };
+        let segments = vec![segment((0.0, 0.0), (200.0, 100.0), LineVisibility::VISIBLE)];
+
+        assert_eq!(
+            screen_space_lines_to_hpgl(&segments, &svg_config, &HpglConfig::new_default()),
+            "IN;SP1;PU0,2000;PD4000,0;PU;SP0;"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use na::{Point2, Point3};
+use serde::Serialize;
+
+use crate::error::MeshToSvgError;
+use crate::lines::{LineSegmentCategorized, LineVisibility};
+use crate::polyline::chain_line_segments;
+use crate::svg_renderer::{scale_screen_space_lines, SvgConfig};
+
+pub struct JsonConfig {
+    /// emit chained polylines rather than individual segments
+    pub chain_lines: bool,
+}
+
+impl JsonConfig {
+    pub fn new_default() -> JsonConfig {
+        JsonConfig { chain_lines: false }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonDrawing {
+    width: i32,
+    height: i32,
+    lines: Vec<JsonLine>,
+}
+
+/// A segment or polyline. Screen space points are in the same coordinates as the fitted svg, and
+/// mesh space points are the matching positions on the mesh
+#[derive(Serialize)]
+struct JsonLine {
+    visibility: &'static str,
+    kind: &'static str,
+    object_id: usize,
+    screen_space: Vec<[f32; 2]>,
+    mesh_space: Vec<[f32; 3]>,
+}
+
+/// Serializes the lines fitted to the svg config, so that each line can be matched up with the
+/// drawing produced by `screen_space_lines_to_fitted_svg`. Obscured lines are only included when
+/// the svg config has an obscured line config.
+///
+/// Json has no NaN or infinity, so lines with them are refused rather than written as null
+pub fn screen_space_lines_to_fitted_json(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+    json_config: &JsonConfig,
+) -> Result<String, MeshToSvgError> {
+    let mut scaled = scale_screen_space_lines(screen_space_lines, svg_config);
+    if svg_config.obscured.is_none() {
+        scaled.retain(|line| line.visibility == LineVisibility::VISIBLE);
+    }
+
+    let lines: Vec<JsonLine> = match json_config.chain_lines {
+        true => chain_line_segments(&scaled)
+            .iter()
+            .map(|polyline| JsonLine {
+                visibility: polyline.visibility.name(),
+                kind: polyline.kind.name(),
+                object_id: polyline.object_id,
+                screen_space: polyline.points.iter().map(point2_array).collect(),
+                mesh_space: polyline
+                    .mesh_space_points
+                    .iter()
+                    .map(point3_array)
+                    .collect(),
+            })
+            .collect(),
+        false => scaled
+            .iter()
+            .map(|line| JsonLine {
+                visibility: line.visibility.name(),
+                kind: line.kind.name(),
+                object_id: line.object_id,
+                screen_space: vec![
+                    point2_array(&line.line_segment.from),
+                    point2_array(&line.line_segment.to),
+                ],
+                mesh_space: vec![
+                    point3_array(&line.mesh_space.from),
+                    point3_array(&line.mesh_space.to),
+                ],
+            })
+            .collect(),
+    };
+
+    let non_finite = lines.iter().position(|line| {
+        let screen_space = line.screen_space.iter().flatten();
+        let mesh_space = line.mesh_space.iter().flatten();
+        !screen_space
+            .chain(mesh_space)
+            .all(|value| value.is_finite())
+    });
+    if let Some(index) = non_finite {
+        return Err(MeshToSvgError::Encoding {
+            output: "json",
+            message: format!("line {} has a NaN or infinite coordinate", index),
+        });
+    }
+
+    let drawing = JsonDrawing {
+        width: svg_config.width,
+        height: svg_config.height,
+        lines,
+    };
+
+    serde_json::to_string(&drawing).map_err(|error| MeshToSvgError::Encoding {
+        output: "json",
+        message: error.to_string(),
+    })
+}
+
+fn point2_array(point: &Point2<f32>) -> [f32; 2] {
+    [point.x, point.y]
+}
+
+fn point3_array(point: &Point3<f32>) -> [f32; 3] {
+    [point.x, point.y, point.z]
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineSegment2, LineSegment3};
+
+    fn segment(from: (f32, f32), to: (f32, f32)) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility: LineVisibility::VISIBLE,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    fn unfitted_config() -> SvgConfig {
+        SvgConfig {
+            margin: 0,
+            fit_lines: false,
+            ..SvgConfig::new_default(20, 10)
+        }
+    }
+
+    #[test]
+    fn writes_segments() {
+        let json = screen_space_lines_to_fitted_json(
+            &[segment((1.0, 2.0), (3.0, 4.0))],
+            &unfitted_config(),
+            &JsonConfig::new_default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"width":20,"height":10,"lines":[{"visibility":"visible","kind":"silhouette","object_id":0,"screen_space":[[1.0,2.0],[3.0,4.0]],"mesh_space":[[1.0,2.0,0.0],[3.0,4.0,0.0]]}]}"#
+        );
+    }
+
+    #[test]
+    fn refuses_non_finite_coordinates() {
+        let lines = [
+            segment((1.0, 2.0), (3.0, 4.0)),
+            segment((3.0, 4.0), (std::f32::NAN, 4.0)),
+        ];
+
+        for &chain_lines in &[false, true] {
+            let error = screen_space_lines_to_fitted_json(
+                &lines,
+                &unfitted_config(),
+                &JsonConfig { chain_lines },
+            )
+            .unwrap_err();
+
+            assert!(matches!(
+                error,
+                MeshToSvgError::Encoding { output: "json", .. }
+            ));
+            assert!(error
+                .to_string()
+                .starts_with("couldn't write the json: line "));
+        }
+    }
+}
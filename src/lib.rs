@@ -3,25 +3,59 @@ extern crate approx;
 extern crate nalgebra as na;
 extern crate web_sys;
 
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
-use lines::{get_visibility, LineSegmentCategorized};
-use mesh::{Mesh, Wireframe};
+use cull::{cull_short_lines, is_too_small, merge_short_pieces, CullConfig};
+use curvature::{find_feature_lines, FeatureLineConfig};
+use error::MeshToSvgError;
+use lines::{get_visibility, mesh_space_segment, EdgeCandidate, EdgeKind, LineSegmentCategorized};
+use mesh::{classify_edge_candidates, Mesh, Wireframe};
+use options::{LineOptions, RenderOptions};
+use renderer::{MeshHandle, Renderer, SceneHandle};
 use scene::{Ray, Scene};
-use svg_renderer::{screen_space_lines_to_fitted_svg, SvgConfig};
+use silhouette::find_smooth_silhouettes;
 use utils::set_panic_hook;
+use validation::MeshReport;
 
 use crate::lines::{dedupe_lines, split_lines_by_intersection, ProjectedSplitLine};
 
 #[macro_use]
 mod utils;
+pub mod colour;
+pub mod cull;
+pub mod curvature;
+pub mod curve_fit;
+pub mod dimensions;
+pub mod dxf_renderer;
+pub mod error;
+pub mod fill;
+pub mod gcode_renderer;
+pub mod hatching;
+pub mod hpgl_renderer;
+pub mod json_renderer;
 pub mod lines;
 pub mod mesh;
+pub mod options;
+pub mod paper;
+pub mod path_order;
+pub mod pdf_renderer;
+pub mod polyline;
+pub mod raster_renderer;
+pub mod renderer;
 pub mod scene;
+pub mod sheet;
+pub mod silhouette;
+pub mod simplify;
 pub mod svg_renderer;
+pub mod term_renderer;
+pub mod validation;
 
 // For the macro relative_eq!
 
+// the arguments of the original api, kept for existing callers. Anything newer is only set through
+// `RenderOptions`, see `mesh_to_svg`
+#[allow(clippy::too_many_arguments)]
 #[wasm_bindgen]
 pub fn mesh_to_svg_lines(
     canvas_width: i32,
@@ -43,73 +77,227 @@ pub fn mesh_to_svg_lines(
     svg_config_obscured_stroke_width: Option<i32>,
     svg_config_obscured_stroke: Option<String>,
     svg_config_fit_lines: Option<bool>,
-) -> String {
+) -> Result<String, MeshToSvgError> {
     set_panic_hook();
 
-    let svg_config = SvgConfig::new(
-        canvas_width,
-        canvas_height,
-        svg_config_width,
-        svg_config_height,
-        svg_config_margin,
-        svg_config_visible_stroke_width,
-        svg_config_visible_stroke,
-        svg_config_hide_obscured,
-        svg_config_obscured_stroke_width,
-        svg_config_obscured_stroke,
-        svg_config_fit_lines,
-    );
-
-    let mesh = Mesh::new_from_wasm(mesh_indices, mesh_vertices, mesh_normals);
-    let wireframe =
-        wireframe_vertices.map(|vertices| Wireframe::new_from_wasm(wireframe_indices, vertices));
-
-    let scene = scene::Scene::new_from_wasm(
+    let options = RenderOptions {
+        width: svg_config_width,
+        height: svg_config_height,
+        margin: svg_config_margin,
+        fit_lines: svg_config_fit_lines,
+        visible: LineOptions {
+            stroke_width: svg_config_visible_stroke_width.map(|width| width as f32),
+            stroke: svg_config_visible_stroke,
+            ..LineOptions::default()
+        },
+        obscured: LineOptions {
+            stroke_width: svg_config_obscured_stroke_width.map(|width| width as f32),
+            stroke: svg_config_obscured_stroke,
+            ..LineOptions::default()
+        },
+        hide_obscured: svg_config_hide_obscured.unwrap_or(false),
+        ..RenderOptions::default()
+    };
+
+    let renderer = options.to_renderer(canvas_width, canvas_height)?;
+
+    let mesh = MeshHandle::new(
+        mesh_indices,
+        mesh_vertices,
+        mesh_normals,
+        wireframe_indices,
+        wireframe_vertices,
+    )?;
+    let scene = SceneHandle::new(
         canvas_width,
         canvas_height,
         view_matrix,
         projection_matrix,
         mesh_world_matrix,
-    );
+    )?;
+
+    renderer.render_svg(&mesh, &scene)
+}
+
+/// Draws the mesh with the options given as a plain object in the shape of `RenderOptions`, with
+/// the svg the size of the scene's canvas unless a size is set
+#[wasm_bindgen]
+pub fn mesh_to_svg(
+    mesh: &MeshHandle,
+    scene: &SceneHandle,
+    options: JsValue,
+) -> Result<String, JsValue> {
+    set_panic_hook();
+
+    let renderer =
+        Renderer::from_options(scene.scene.width as i32, scene.scene.height as i32, options)?;
+
+    Ok(renderer.render_svg(mesh, scene)?)
+}
+
+/// A mesh and its optional wireframe drawn as one part of a scene. Every line found for the
+/// object is tagged with its id
+pub struct SceneObject<'a> {
+    pub id: usize,
+    pub mesh: &'a Mesh,
+    pub wireframe: &'a Option<Wireframe>,
+    /// edges between the mesh's faces kept from an earlier render, found again when `None`
+    pub edge_candidates: Option<&'a [EdgeCandidate]>,
+    /// curvature based lines to draw alongside the silhouettes and creases
+    pub feature_lines: Option<&'a FeatureLineConfig>,
+    /// silhouettes of curved surfaces traced within triangles rather than along their edges
+    pub smooth_silhouettes: bool,
+}
+
+/// What's wrong with the buffers for a mesh, as a `report` listing each kind of problem and where
+/// it is, along with a sentence about each in `messages`. Safe to call on anything uploaded
+#[wasm_bindgen]
+pub fn validate_mesh(
+    mesh_indices: Option<Box<[usize]>>,
+    mesh_vertices: Box<[f32]>,
+    mesh_normals: Box<[f32]>,
+) -> Result<JsValue, JsValue> {
+    set_panic_hook();
+
+    validation_result(&Mesh::validate(
+        mesh_indices.as_deref(),
+        &mesh_vertices,
+        &mesh_normals,
+    ))
+}
+
+/// Same as `validate_mesh`, for the buffers of a wireframe
+#[wasm_bindgen]
+pub fn validate_wireframe(
+    wireframe_indices: Option<Box<[usize]>>,
+    wireframe_vertices: Box<[f32]>,
+) -> Result<JsValue, JsValue> {
+    set_panic_hook();
 
-    // log!("Scene: {}", scene);
+    validation_result(&Wireframe::validate(
+        wireframe_indices.as_deref(),
+        &wireframe_vertices,
+    ))
+}
 
-    let segments = find_categorized_line_segments(&mesh, &wireframe, &scene);
+/// The report with its messages, which js can't call for
+#[derive(Serialize)]
+struct ValidationResult<'a> {
+    report: &'a MeshReport,
+    messages: Vec<String>,
+}
 
-    screen_space_lines_to_fitted_svg(&segments, &svg_config)
+fn validation_result(report: &MeshReport) -> Result<JsValue, JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&ValidationResult {
+        report,
+        messages: report.messages(),
+    })?)
+}
+
+/// Same as `mesh_to_svg`, but returns the fitted lines as json for tooling that needs to know where
+/// each line came from, e.g. to highlight a part when hovering over its lines
+#[wasm_bindgen]
+pub fn mesh_to_json_lines(
+    mesh: &MeshHandle,
+    scene: &SceneHandle,
+    options: JsValue,
+    chain_lines: bool,
+) -> Result<String, JsValue> {
+    set_panic_hook();
+
+    let renderer =
+        Renderer::from_options(scene.scene.width as i32, scene.scene.height as i32, options)?;
+
+    Ok(renderer.render_json(mesh, scene, chain_lines)?)
 }
 
 pub fn find_categorized_line_segments(
     mesh: &Mesh,
     maybe_wireframe: &Option<Wireframe>,
     scene: &Scene,
-) -> Vec<LineSegmentCategorized> {
+) -> Result<Vec<LineSegmentCategorized>, MeshToSvgError> {
+    find_categorized_line_segments_for_objects(
+        &[SceneObject {
+            id: 0,
+            mesh,
+            wireframe: maybe_wireframe,
+            edge_candidates: None,
+            feature_lines: None,
+            smooth_silhouettes: false,
+        }],
+        scene,
+        &CullConfig::new_default(),
+    )
+}
+
+/// Finds the lines of several objects sharing the scene, so that each object can obscure the
+/// lines of the others. Detail below the cull config's thresholds is dropped before testing
+/// visibility, which is the slowest step
+pub fn find_categorized_line_segments_for_objects(
+    objects: &[SceneObject],
+    scene: &Scene,
+    cull_config: &CullConfig,
+) -> Result<Vec<LineSegmentCategorized>, MeshToSvgError> {
     // let start_edges = Instant::now();
 
-    let mut edges = mesh.find_edge_lines(&scene, false);
+    let objects: Vec<&SceneObject> = objects
+        .iter()
+        .filter(|object| !is_too_small(object.mesh, scene, cull_config.min_object_size))
+        .collect();
 
-    // let duration_edges = start_edges.elapsed();
+    let mut edges = Vec::new();
 
-    if let Some(wireframe) = maybe_wireframe {
-        edges.append(&mut wireframe.edges());
+    for object in &objects {
+        let mut object_edges = match object.edge_candidates {
+            Some(candidates) => classify_edge_candidates(candidates, scene, false),
+            None => object.mesh.find_edge_lines(scene, false),
+        };
+
+        if let Some(wireframe) = object.wireframe {
+            object_edges.append(&mut wireframe.edges());
+        }
+
+        if object.smooth_silhouettes {
+            object_edges.retain(|edge| edge.kind != EdgeKind::SILHOUETTE);
+            object_edges.append(&mut find_smooth_silhouettes(object.mesh, scene));
+        }
+
+        if let Some(feature_line_config) = object.feature_lines {
+            object_edges.append(&mut find_feature_lines(
+                object.mesh,
+                scene,
+                feature_line_config,
+            ));
+        }
+
+        for edge in &mut object_edges {
+            edge.object_id = object.id;
+        }
+
+        edges.append(&mut object_edges);
     }
 
+    // let duration_edges = start_edges.elapsed();
+
     // let start_projection = Instant::now();
-    let projected = scene.project_lines(&edges);
+    let mut projected = scene.project_lines(&edges);
+    cull_short_lines(&mut projected, cull_config.min_segment_length);
     // let duration_projection = start_projection.elapsed();
     // eprintln!("projected lines size: {}", projected.len());
     // let start_deduplication = Instant::now();
-    let deduped = dedupe_lines(projected);
+    let deduped = dedupe_lines(projected)?;
     // let duration_deduplication = start_deduplication.elapsed();
     // eprintln!("deduped lines size: {}", deduped.len());
 
     // let start_splitting = Instant::now();
-    let split_lines = split_lines_by_intersection(&deduped);
+    let mut split_lines = split_lines_by_intersection(&deduped);
+    merge_short_pieces(&mut split_lines, cull_config.min_segment_length);
     // eprintln!("split lines size: {}", &split_lines.iter().fold(0, |count, line| count + line.split_screen_space_lines.len()));
     // let duration_splitting = start_splitting.elapsed();
 
     // let start_checking_visibility = Instant::now();
-    let segments = partition_visibility(mesh, scene, &split_lines);
+    let meshes: Vec<&Mesh> = objects.iter().map(|object| object.mesh).collect();
+    let segments = partition_visibility(&meshes, scene, &split_lines)?;
 
     // let duration_checking_visibility = start_checking_visibility.elapsed();
     //
@@ -142,36 +330,34 @@ pub fn find_categorized_line_segments(
     // );
     // eprintln!("overall took {:?}", total);
 
-    segments
+    Ok(segments)
 }
 
 pub fn partition_visibility(
-    mesh: &Mesh,
+    meshes: &[&Mesh],
     scene: &Scene,
-    split_lines: &Vec<ProjectedSplitLine>,
-) -> Vec<LineSegmentCategorized> {
+    split_lines: &[ProjectedSplitLine],
+) -> Result<Vec<LineSegmentCategorized>, MeshToSvgError> {
     let mut ray = Ray::new();
-    let segments: Vec<LineSegmentCategorized> = split_lines
-        .into_iter()
-        .flat_map(|projected_line| {
-            let culled: Vec<LineSegmentCategorized> = projected_line
-                .split_screen_space_lines
-                .iter()
-                .enumerate()
-                .map(|(_j, line_segment)| LineSegmentCategorized {
-                    visibility: get_visibility(
-                        &line_segment,
-                        &projected_line.projected_line,
-                        &scene,
-                        &mut ray,
-                        &mesh,
-                    ),
-                    line_segment: line_segment.to_owned(),
-                })
-                .collect();
-
-            culled
-        })
-        .collect();
-    segments
+    let mut segments = Vec::new();
+
+    for projected_line in split_lines {
+        for line_segment in &projected_line.split_screen_space_lines {
+            segments.push(LineSegmentCategorized {
+                visibility: get_visibility(
+                    line_segment,
+                    &projected_line.projected_line,
+                    scene,
+                    &mut ray,
+                    meshes,
+                )?,
+                line_segment: line_segment.to_owned(),
+                mesh_space: mesh_space_segment(line_segment, &projected_line.projected_line, scene),
+                kind: projected_line.projected_line.kind,
+                object_id: projected_line.projected_line.object_id,
+            });
+        }
+    }
+
+    Ok(segments)
 }
@@ -4,15 +4,50 @@ use na::{Point2, Point3, Vector3};
 use nalgebra::{distance, distance_squared};
 use wasm_bindgen::__rt::core::cmp::Ordering;
 
+use crate::error::MeshToSvgError;
 use crate::mesh::Mesh;
 use crate::scene::{Ray, Scene};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum LineVisibility {
     VISIBLE = 0,
     OBSCURED = 1,
 }
 
+#[derive(Copy, Clone, PartialEq)]
+pub enum EdgeKind {
+    SILHOUETTE = 0,
+    CREASE = 1,
+    WIREFRAME = 2,
+    /// tonal shading strokes across faces, see `find_hatch_lines`
+    HATCH = 3,
+    /// ridges, valleys and suggestive contours of smooth surfaces, see `find_feature_lines`
+    FEATURE = 4,
+}
+
+impl LineVisibility {
+    /// Lowercase name used for class names and serialized output
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineVisibility::VISIBLE => "visible",
+            LineVisibility::OBSCURED => "obscured",
+        }
+    }
+}
+
+impl EdgeKind {
+    /// Lowercase name used for class names and serialized output
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeKind::SILHOUETTE => "silhouette",
+            EdgeKind::CREASE => "crease",
+            EdgeKind::WIREFRAME => "wireframe",
+            EdgeKind::HATCH => "hatch",
+            EdgeKind::FEATURE => "feature",
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct LineSegment2 {
     pub from: Point2<f32>,
@@ -22,7 +57,11 @@ pub struct LineSegment2 {
 #[derive(Copy, Clone)]
 pub struct LineSegmentCategorized {
     pub line_segment: LineSegment2,
+    /// the part of the source edge this segment was split from, in mesh space
+    pub mesh_space: LineSegment3,
     pub visibility: LineVisibility,
+    pub kind: EdgeKind,
+    pub object_id: usize,
 }
 
 #[derive(Copy, Clone)]
@@ -31,6 +70,13 @@ pub struct LineSegment3 {
     pub to: Point3<f32>,
 }
 
+#[derive(Copy, Clone)]
+pub struct EdgeLine {
+    pub line: LineSegment3,
+    pub kind: EdgeKind,
+    pub object_id: usize,
+}
+
 pub struct EdgeCandidate {
     pub edge: LineSegment3,
     pub adjacent_triangle_a_normal: Vector3<f32>,
@@ -40,7 +86,9 @@ pub struct EdgeCandidate {
 #[derive(Copy, Clone)]
 pub struct ProjectedLine {
     pub screen_space: LineSegment2,
-    pub view_space: LineSegment3,
+    pub mesh_space: LineSegment3,
+    pub kind: EdgeKind,
+    pub object_id: usize,
 }
 
 pub struct ProjectedSplitLine {
@@ -117,7 +165,18 @@ pub fn dedupe_lines_naive(lines: Vec<ProjectedLine>) -> Vec<ProjectedLine> {
 }
 
 /// @todo work out how to make this not take Copy of line segments
-pub fn dedupe_lines(lines: Vec<ProjectedLine>) -> Vec<ProjectedLine> {
+pub fn dedupe_lines(lines: Vec<ProjectedLine>) -> Result<Vec<ProjectedLine>, MeshToSvgError> {
+    // lines are ordered by their screen space coordinates, which NaNs have no place in
+    let finite = |point: &Point2<f32>| point.x.is_finite() && point.y.is_finite();
+    if !lines
+        .iter()
+        .all(|line| finite(&line.screen_space.from) && finite(&line.screen_space.to))
+    {
+        return Err(MeshToSvgError::NonFiniteValue {
+            buffer: "projected lines",
+        });
+    }
+
     let mut ordered_from_to: Vec<ProjectedLine> = lines
         .iter()
         .map(|entry| {
@@ -127,7 +186,7 @@ pub fn dedupe_lines(lines: Vec<ProjectedLine>) -> Vec<ProjectedLine> {
                     &mut new_entry.screen_space.from,
                     &mut new_entry.screen_space.to,
                 );
-                std::mem::swap(&mut new_entry.view_space.from, &mut new_entry.view_space.to);
+                std::mem::swap(&mut new_entry.mesh_space.from, &mut new_entry.mesh_space.to);
                 return new_entry;
             }
             entry.clone()
@@ -139,7 +198,7 @@ pub fn dedupe_lines(lines: Vec<ProjectedLine>) -> Vec<ProjectedLine> {
             .from
             .x
             .partial_cmp(&b.screen_space.from.x)
-            .unwrap()
+            .unwrap_or(Ordering::Equal)
     });
 
     let mut unique_lines = Vec::new();
@@ -169,7 +228,7 @@ pub fn dedupe_lines(lines: Vec<ProjectedLine>) -> Vec<ProjectedLine> {
             unique_lines.push(ordered_from_to[curr_index]);
         }
     }
-    unique_lines
+    Ok(unique_lines)
 }
 
 #[derive(Copy, Clone)]
@@ -332,8 +391,8 @@ pub fn get_visibility(
     projected_line: &ProjectedLine,
     scene: &Scene,
     ray: &mut Ray,
-    mesh: &Mesh,
-) -> LineVisibility {
+    meshes: &[&Mesh],
+) -> Result<LineVisibility, MeshToSvgError> {
     let screen_space_length = distance(
         &projected_line.screen_space.from,
         &projected_line.screen_space.to,
@@ -352,17 +411,15 @@ pub fn get_visibility(
         .coords
         .lerp(&projected_line.screen_space.to.coords, scale);
 
-    let test_view_space = projected_line
-        .view_space
-        .from
-        .coords
-        .lerp(&projected_line.view_space.to.coords, scale);
+    // the mesh space point that projects onto the test point, which perspective moves away from
+    // the same fraction of the way along the mesh space line
+    let test_mesh_space = mesh_space_point(projected_line, scale, scene).coords;
 
     // @todo should be a better way to do this?
     let test_screen_space_point = Point2::new(test_screen_space.x, test_screen_space.y);
 
-    let ray_target = scene.unproject_point(&test_screen_space_point);
-    let ray_origin = test_view_space;
+    let ray_target = scene.unproject_point(&test_screen_space_point)?;
+    let ray_origin = test_mesh_space;
     let ray_direction = (&ray_target.coords - &ray_origin).normalize();
     let ray_length = (&ray_origin - &ray_target.coords).norm();
 
@@ -370,8 +427,170 @@ pub fn get_visibility(
     ray.direction = ray_direction;
     ray.length = ray_length;
 
-    match ray.intersects_mesh(&mesh) {
-        false => LineVisibility::VISIBLE,
-        true => LineVisibility::OBSCURED,
+    match meshes.iter().any(|mesh| ray.intersects_mesh(mesh)) {
+        false => Ok(LineVisibility::VISIBLE),
+        true => Ok(LineVisibility::OBSCURED),
+    }
+}
+
+/// The mesh space section of a projected line matching a part of its screen space line
+pub fn mesh_space_segment(
+    line_segment: &LineSegment2,
+    projected_line: &ProjectedLine,
+    scene: &Scene,
+) -> LineSegment3 {
+    let screen_space_length = distance(
+        &projected_line.screen_space.from,
+        &projected_line.screen_space.to,
+    );
+
+    let point_at = |point: &Point2<f32>| {
+        let screen_scale = match screen_space_length {
+            l if l > 0.0 => distance(&projected_line.screen_space.from, point) / l,
+            _ => 0.0,
+        };
+
+        mesh_space_point(projected_line, screen_scale, scene)
+    };
+
+    LineSegment3 {
+        from: point_at(&line_segment.from),
+        to: point_at(&line_segment.to),
+    }
+}
+
+/// The mesh space point a fraction of the way along a projected line's screen space line. This is
+/// perspective correct, as a fraction of the way along the screen space line isn't the same
+/// fraction of the mesh space line once it's been divided by depth
+fn mesh_space_point(
+    projected_line: &ProjectedLine,
+    screen_scale: f32,
+    scene: &Scene,
+) -> Point3<f32> {
+    // the clip space w of each end, which is 1 throughout for orthographic projections
+    let transformation = scene.transformation_matrix();
+    let w = |point: &Point3<f32>| (transformation * point.to_homogeneous()).w;
+    let (w_from, w_to) = (
+        w(&projected_line.mesh_space.from),
+        w(&projected_line.mesh_space.to),
+    );
+
+    // 1 / w is what varies linearly in screen space, ends at the same depth need no correction
+    let along = screen_scale / w_to;
+    let scale = match along / ((1.0 - screen_scale) / w_from + along) {
+        _ if w_from == w_to => screen_scale,
+        scale if scale.is_finite() => scale,
+        _ => screen_scale,
+    };
+
+    Point3::from(
+        projected_line
+            .mesh_space
+            .from
+            .coords
+            .lerp(&projected_line.mesh_space.to.coords, scale),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Matrix4;
+
+    use super::*;
+
+    // looking down -z from the origin with a 90 degree field of view
+    fn perspective_scene() -> Scene {
+        Scene::new(
+            200.0,
+            200.0,
+            Matrix4::identity(),
+            Matrix4::new_perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0),
+            Matrix4::identity(),
+        )
+    }
+
+    // a line receding from the camera, so its far half is squashed on screen
+    fn receding_line(scene: &Scene) -> ProjectedLine {
+        let from = Point3::new(-1.0, 0.0, -2.0);
+        let to = Point3::new(1.0, 0.0, -10.0);
+
+        ProjectedLine {
+            screen_space: LineSegment2 {
+                from: scene.project_point(&from),
+                to: scene.project_point(&to),
+            },
+            mesh_space: LineSegment3 { from, to },
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    // where the receding line crosses z = -4
+    fn crossing(scene: &Scene) -> Point2<f32> {
+        scene.project_point(&Point3::new(-0.5, 0.0, -4.0))
+    }
+
+    #[test]
+    fn splits_mesh_space_perspective_correctly() {
+        let scene = perspective_scene();
+        let line = receding_line(&scene);
+        let screen_midpoint = Point2::from(
+            line.screen_space
+                .from
+                .coords
+                .lerp(&line.screen_space.to.coords, 0.5),
+        );
+
+        let far_half = mesh_space_segment(
+            &LineSegment2 {
+                from: screen_midpoint,
+                to: line.screen_space.to,
+            },
+            &line,
+            &scene,
+        );
+
+        // halfway across the screen is much less than halfway along the line
+        assert_relative_eq!(
+            scene.project_point(&far_half.from),
+            screen_midpoint,
+            epsilon = 1e-3
+        );
+        assert!(far_half.from.z > -6.0);
+        assert_relative_eq!(far_half.to, line.mesh_space.to, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn tests_visibility_at_the_perspective_correct_point() {
+        let scene = perspective_scene();
+        let line = receding_line(&scene);
+        // a wall across the middle of the line at z = -4
+        #[rustfmt::skip]
+        let wall = Mesh::new(
+            Some(vec![0, 1, 2, 0, 2, 3]),
+            vec![
+                -2.0, -1.0, -4.0,
+                 2.0, -1.0, -4.0,
+                 2.0,  1.0, -4.0,
+                -2.0,  1.0, -4.0,
+            ],
+            [0.0, 0.0, 1.0].repeat(4),
+        )
+        .unwrap();
+        let visibility = |from: Point2<f32>, to: Point2<f32>| {
+            get_visibility(
+                &LineSegment2 { from, to },
+                &line,
+                &scene,
+                &mut Ray::new(),
+                &[&wall],
+            )
+            .unwrap()
+        };
+
+        // the middle of the near part on screen is only a third of the way to the wall in mesh
+        // space, where linearly interpolating would put it behind the wall
+        assert!(visibility(line.screen_space.from, crossing(&scene)) == LineVisibility::VISIBLE);
+        assert!(visibility(crossing(&scene), line.screen_space.to) == LineVisibility::OBSCURED);
     }
 }
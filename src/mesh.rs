@@ -1,10 +1,16 @@
 extern crate nalgebra as na;
 
+use std::collections::HashMap;
+
 use itertools::Itertools;
-use na::{Point3, Vector3};
+use na::{Matrix3, Matrix4, Point3, Vector3};
 
-use crate::lines::{EdgeCandidate, LineSegment3};
+use crate::error::{check_buffer, MeshToSvgError};
+use crate::lines::{EdgeCandidate, EdgeKind, EdgeLine, LineSegment3};
 use crate::scene::Scene;
+use crate::validation::{
+    repair_mesh, repair_wireframe, validate_mesh, validate_wireframe, MeshReport,
+};
 
 pub struct Mesh {
     pub indices: Vec<usize>,
@@ -25,23 +31,77 @@ pub struct Facet {
     pub points: Vec<Point3<f32>>,
 }
 
+/// Faces meeting at less than this dot product of their normals form a crease. The value is
+/// arbitrarily chosen
+pub const CREASE_THRESHOLD: f32 = 0.8;
+
+/// Vertices closer than this fraction of the mesh size are treated as one, as stl files and meshes
+/// with split normals repeat vertices along every edge
+pub const WELD_TOLERANCE: f32 = 1e-5;
+
+/// The mesh with coincident vertices merged, so that surface properties can be estimated across
+/// the edges of neighbouring triangles
+pub struct WeldedMesh {
+    pub points: Vec<Point3<f32>>,
+    pub triangles: Vec<[usize; 3]>,
+    /// unit normal of each triangle, facing the same way as the mesh's own normals
+    pub face_normals: Vec<Vector3<f32>>,
+    /// area weighted average of the normals of the triangles around each vertex
+    pub normals: Vec<Vector3<f32>>,
+    /// normals at each corner of each triangle, averaged only over the triangles around the
+    /// vertex that don't meet this one at a crease
+    pub corner_normals: Vec<[Vector3<f32>; 3]>,
+    /// vertices on a crease
+    pub creased: Vec<bool>,
+    /// length of the bounding box diagonal
+    pub size: f32,
+}
+
 impl Wireframe {
     pub fn new_from_wasm(
         indices_data: Option<Box<[usize]>>,
         vertices_data: Box<[f32]>,
-    ) -> Wireframe {
+    ) -> Result<Wireframe, MeshToSvgError> {
         Wireframe::new(
             indices_data.map(|indices| indices.into_vec()),
             vertices_data.into_vec(),
         )
     }
-    pub fn new(maybe_indices: Option<Vec<usize>>, vertices: Vec<f32>) -> Wireframe {
-        let mut points = Vec::with_capacity(vertices.len() / 3);
+
+    /// Lines between pairs of indexed vertices, or between pairs of vertices in order without
+    /// indices
+    pub fn new(
+        maybe_indices: Option<Vec<usize>>,
+        vertices: Vec<f32>,
+    ) -> Result<Wireframe, MeshToSvgError> {
+        check_buffer("wireframe vertices", &vertices, 3)?;
 
         let indices = match maybe_indices {
             Some(x) => x,
             None => (0..vertices.len() / 3).collect(),
         };
+        check_indices("wireframe indices", &indices, 2, vertices.len() / 3)?;
+
+        Ok(Wireframe::from_checked(indices, vertices))
+    }
+
+    /// What's wrong with the buffers for a wireframe, including problems `new` lets through like
+    /// zero length or repeated lines
+    pub fn validate(indices: Option<&[usize]>, vertices: &[f32]) -> MeshReport {
+        validate_wireframe(indices, vertices)
+    }
+
+    /// A wireframe of only the lines that can be drawn, each once, along with what's still wrong
+    /// with it
+    pub fn repair(indices: Option<&[usize]>, vertices: &[f32]) -> (Wireframe, MeshReport) {
+        let (indices, vertices) = repair_wireframe(indices, vertices);
+        let report = validate_wireframe(Some(&indices), &vertices);
+
+        (Wireframe::from_checked(indices, vertices), report)
+    }
+
+    fn from_checked(indices: Vec<usize>, vertices: Vec<f32>) -> Wireframe {
+        let mut points = Vec::with_capacity(vertices.len() / 3);
 
         for i in 0..vertices.len() / 3 {
             points.push(Point3::new(
@@ -58,7 +118,12 @@ impl Wireframe {
         }
     }
 
-    pub fn edges(&self) -> Vec<LineSegment3> {
+    /// The wireframe with its points moved by the matrix
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Wireframe {
+        Wireframe::from_checked(self.indices.clone(), transform_points(&self.points, matrix))
+    }
+
+    pub fn edges(&self) -> Vec<EdgeLine> {
         let mut segments = Vec::with_capacity(&self.points.len() / 2);
 
         for (&from_index, &to_index) in self.indices.iter().tuples() {
@@ -74,7 +139,11 @@ impl Wireframe {
                 self.vertices[to_index * 3 + 2],
             );
 
-            segments.push(LineSegment3 { from, to })
+            segments.push(EdgeLine {
+                line: LineSegment3 { from, to },
+                kind: EdgeKind::WIREFRAME,
+                object_id: 0,
+            })
         }
 
         segments
@@ -86,7 +155,7 @@ impl Mesh {
         indices_data: Box<[usize]>,
         vertices_data: Box<[f32]>,
         normals_data: Box<[f32]>,
-    ) -> Mesh {
+    ) -> Result<Mesh, MeshToSvgError> {
         Mesh::new(
             Some(indices_data.into_vec()),
             vertices_data.into_vec(),
@@ -94,8 +163,56 @@ impl Mesh {
         )
     }
 
-    pub fn new(indices: Option<Vec<usize>>, vertices: Vec<f32>, normals_data: Vec<f32>) -> Mesh {
-        let base_mesh = Wireframe::new(indices, vertices);
+    /// Triangles of indexed vertices, or of vertices in order without indices. Each vertex has
+    /// its own normal
+    pub fn new(
+        indices: Option<Vec<usize>>,
+        vertices: Vec<f32>,
+        normals_data: Vec<f32>,
+    ) -> Result<Mesh, MeshToSvgError> {
+        check_buffer("mesh vertices", &vertices, 3)?;
+        check_buffer("mesh normals", &normals_data, 3)?;
+
+        let vertex_count = vertices.len() / 3;
+        if normals_data.len() / 3 < vertex_count {
+            return Err(MeshToSvgError::MissingNormals {
+                normal_count: normals_data.len() / 3,
+                vertex_count,
+            });
+        }
+
+        let indices = match indices {
+            Some(x) => x,
+            None => (0..vertex_count).collect(),
+        };
+        check_indices("mesh indices", &indices, 3, vertex_count)?;
+
+        Ok(Mesh::from_checked(indices, vertices, normals_data))
+    }
+
+    /// What's wrong with the buffers for a mesh, including problems `new` lets through like zero
+    /// area or duplicate triangles, edges shared by more than two triangles and triangles facing
+    /// the other way to their neighbours
+    pub fn validate(indices: Option<&[usize]>, vertices: &[f32], normals: &[f32]) -> MeshReport {
+        validate_mesh(indices, vertices, normals)
+    }
+
+    /// A mesh of only the triangles that can be drawn, each once, wound the same way as their
+    /// neighbours where possible and with normals worked out for vertices without them, along
+    /// with what's still wrong with it. Edges shared by more than two triangles are left alone
+    pub fn repair(
+        indices: Option<&[usize]>,
+        vertices: &[f32],
+        normals: &[f32],
+    ) -> (Mesh, MeshReport) {
+        let (indices, vertices, normals) = repair_mesh(indices, vertices, normals);
+        let report = validate_mesh(Some(&indices), &vertices, &normals);
+
+        (Mesh::from_checked(indices, vertices, normals), report)
+    }
+
+    fn from_checked(indices: Vec<usize>, vertices: Vec<f32>, normals_data: Vec<f32>) -> Mesh {
+        let base_mesh = Wireframe::from_checked(indices, vertices);
 
         let mut normals = Vec::with_capacity(normals_data.len() / 3);
 
@@ -118,6 +235,26 @@ impl Mesh {
         }
     }
 
+    /// The mesh with its points moved by the matrix and its normals turned to match, see
+    /// `normal_matrix`
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Mesh {
+        let normal_matrix = normal_matrix(matrix);
+        let normals: Vec<f32> = self
+            .normals
+            .iter()
+            .flat_map(|normal| {
+                let normal = normal_matrix * normal;
+                vec![normal.x, normal.y, normal.z]
+            })
+            .collect();
+
+        Mesh::from_checked(
+            self.indices.clone(),
+            transform_points(&self.points, matrix),
+            normals,
+        )
+    }
+
     pub fn compute_adjacency(&self) -> Vec<Option<usize>> {
         let index_count = self.indices.len();
         let mut data: Vec<Option<usize>> = vec![Option::None; index_count];
@@ -178,38 +315,106 @@ impl Mesh {
         edge_candidates
     }
 
-    pub fn find_edge_lines(&self, scene: &Scene, silhouettes_only: bool) -> Vec<LineSegment3> {
-        self.get_silhouette_candidates()
-            .into_iter()
-            .filter(|candidate| {
-                if !silhouettes_only {
-                    let normal_dot_product = candidate
-                        .adjacent_triangle_a_normal
-                        .dot(&candidate.adjacent_triangle_b_normal);
-
-                    // angle between faces is greater than arbitrarily chosen value, the edge should be rendered as it is a "sharp" corner
-                    if normal_dot_product < 0.8 {
-                        return true;
-                    }
+    pub fn find_edge_lines(&self, scene: &Scene, silhouettes_only: bool) -> Vec<EdgeLine> {
+        classify_edge_candidates(&self.get_silhouette_candidates(), scene, silhouettes_only)
+    }
+
+    /// Opposite corners of the axis aligned box around the mesh's points
+    pub fn bounding_box(&self) -> Option<(Point3<f32>, Point3<f32>)> {
+        bounds(&self.points)
+    }
+}
+
+/// Silhouettes and creases among edges found by `get_silhouette_candidates`. The candidates only
+/// depend on the mesh, so they can be kept and classified again whenever the view changes
+pub fn classify_edge_candidates(
+    candidates: &[EdgeCandidate],
+    scene: &Scene,
+    silhouettes_only: bool,
+) -> Vec<EdgeLine> {
+    candidates
+        .iter()
+        .filter_map(|candidate| {
+            let normal_a_world = scene
+                .mesh_world_matrix
+                .transform_vector(&candidate.adjacent_triangle_a_normal);
+            let normal_b_world = scene
+                .mesh_world_matrix
+                .transform_vector(&candidate.adjacent_triangle_b_normal);
+
+            let a_facing = scene.camera_forward_vector().dot(&normal_a_world) > 0.0;
+            let b_facing = scene.camera_forward_vector().dot(&normal_b_world) > 0.0;
+
+            if a_facing != b_facing {
+                return Some(EdgeLine {
+                    line: candidate.edge,
+                    kind: EdgeKind::SILHOUETTE,
+                    object_id: 0,
+                });
+            }
+
+            if !silhouettes_only {
+                let normal_dot_product = candidate
+                    .adjacent_triangle_a_normal
+                    .dot(&candidate.adjacent_triangle_b_normal);
+
+                // angle between faces is greater than arbitrarily chosen value, the edge should be rendered as it is a "sharp" corner
+                if normal_dot_product < CREASE_THRESHOLD {
+                    return Some(EdgeLine {
+                        line: candidate.edge,
+                        kind: EdgeKind::CREASE,
+                        object_id: 0,
+                    });
                 }
+            }
+
+            None
+        })
+        .collect()
+}
 
-                let normal_a_world = scene
-                    .mesh_world_matrix
-                    .transform_vector(&candidate.adjacent_triangle_a_normal);
-                let normal_b_world = scene
-                    .mesh_world_matrix
-                    .transform_vector(&candidate.adjacent_triangle_b_normal);
+/// The candidates moved by the matrix, as they'd be found in the mesh moved by it
+pub fn transform_edge_candidates(
+    candidates: &[EdgeCandidate],
+    matrix: &Matrix4<f32>,
+) -> Vec<EdgeCandidate> {
+    let normal_matrix = normal_matrix(matrix);
+
+    candidates
+        .iter()
+        .map(|candidate| EdgeCandidate {
+            edge: LineSegment3 {
+                from: matrix.transform_point(&candidate.edge.from),
+                to: matrix.transform_point(&candidate.edge.to),
+            },
+            adjacent_triangle_a_normal: normal_matrix * candidate.adjacent_triangle_a_normal,
+            adjacent_triangle_b_normal: normal_matrix * candidate.adjacent_triangle_b_normal,
+        })
+        .collect()
+}
 
-                let a_facing = scene.camera_forward_vector().dot(&normal_a_world) > 0.0;
-                let b_facing = scene.camera_forward_vector().dot(&normal_b_world) > 0.0;
+/// Turns normals to stay perpendicular to the surface under the matrix. It's scaled so that
+/// rotations and uniform scales leave their length alone, which keeps the angles between faces
+/// comparable with the crease threshold
+pub fn normal_matrix(matrix: &Matrix4<f32>) -> Matrix3<f32> {
+    let linear: Matrix3<f32> = matrix.fixed_slice::<na::U3, na::U3>(0, 0).into_owned();
 
-                a_facing != b_facing
-            })
-            .map(|candidate| candidate.edge)
-            .collect()
+    match linear.try_inverse() {
+        Some(inverse) => inverse.transpose() * linear.determinant().abs().cbrt(),
+        None => Matrix3::identity(),
     }
 }
 
+fn transform_points(points: &[Point3<f32>], matrix: &Matrix4<f32>) -> Vec<f32> {
+    points
+        .iter()
+        .flat_map(|point| {
+            let point = matrix.transform_point(point);
+            vec![point.x, point.y, point.z]
+        })
+        .collect()
+}
+
 fn find_match<F>(indices: &[usize], i: usize, equality_tester: F) -> Option<usize>
 where
     F: Fn(usize, usize) -> bool,
@@ -241,6 +446,31 @@ where
     }
 }
 
+/// Checks indices come in whole lines or triangles of vertices that exist
+fn check_indices(
+    buffer: &'static str,
+    indices: &[usize],
+    multiple_of: usize,
+    vertex_count: usize,
+) -> Result<(), MeshToSvgError> {
+    if !indices.chunks_exact(multiple_of).remainder().is_empty() {
+        return Err(MeshToSvgError::BufferLength {
+            buffer,
+            length: indices.len(),
+            multiple_of,
+        });
+    }
+
+    match indices.iter().find(|&&index| index >= vertex_count) {
+        Some(&index) => Err(MeshToSvgError::IndexOutOfRange {
+            buffer,
+            index,
+            vertex_count,
+        }),
+        None => Ok(()),
+    }
+}
+
 fn get_facets(indices: &[usize], points: &[Point3<f32>], normals: &[Vector3<f32>]) -> Vec<Facet> {
     let mut facets: Vec<Facet> = Vec::with_capacity(indices.len() / 3);
 
@@ -263,3 +493,203 @@ fn get_facets(indices: &[usize], points: &[Point3<f32>], normals: &[Vector3<f32>
 
     facets
 }
+
+impl WeldedMesh {
+    pub fn new(mesh: &Mesh) -> WeldedMesh {
+        let size = match bounds(&mesh.points) {
+            Some((min, max)) => (max - min).norm(),
+            None => 0.0,
+        };
+
+        let corners: Vec<Point3<f32>> = mesh
+            .facets
+            .iter()
+            .flat_map(|facet| facet.points.iter().cloned())
+            .collect();
+        let (points, welded) = weld_points(&corners, size);
+
+        let mut triangles = Vec::with_capacity(mesh.facets.len());
+        let mut face_normals = Vec::with_capacity(mesh.facets.len());
+
+        for (facet, corners) in mesh.facets.iter().zip(welded.chunks(3)) {
+            let triangle = [corners[0], corners[1], corners[2]];
+
+            if triangle[0] == triangle[1]
+                || triangle[1] == triangle[2]
+                || triangle[2] == triangle[0]
+            {
+                continue;
+            }
+
+            // area weighted, and facing the same way as the mesh's own normals
+            let normal =
+                (facet.points[1] - facet.points[0]).cross(&(facet.points[2] - facet.points[0]));
+            face_normals.push(match normal.dot(&facet.normal) < 0.0 {
+                true => -normal,
+                false => normal,
+            });
+            triangles.push(triangle);
+        }
+
+        let area_weighted_normals = face_normals.clone();
+        let mut normals = vec![Vector3::zeros(); points.len()];
+        let mut incident_faces: Vec<Vec<usize>> = vec![vec![]; points.len()];
+
+        for (face, triangle) in triangles.iter().enumerate() {
+            for &vertex in triangle {
+                normals[vertex] += face_normals[face];
+                incident_faces[vertex].push(face);
+            }
+        }
+
+        let normals: Vec<Vector3<f32>> = normals
+            .iter()
+            .map(|normal| {
+                normal
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(Vector3::z)
+            })
+            .collect();
+
+        let face_normals: Vec<Vector3<f32>> = face_normals
+            .iter()
+            .map(|normal| {
+                normal
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(Vector3::zeros)
+            })
+            .collect();
+
+        let corner_normals = triangles
+            .iter()
+            .enumerate()
+            .map(|(face, triangle)| {
+                let mut corners = [Vector3::zeros(); 3];
+
+                for (corner, &vertex) in triangle.iter().enumerate() {
+                    let smooth: Vector3<f32> = incident_faces[vertex]
+                        .iter()
+                        .filter(|&&other| {
+                            face_normals[other].dot(&face_normals[face]) >= CREASE_THRESHOLD
+                        })
+                        .map(|&other| area_weighted_normals[other])
+                        .sum();
+
+                    corners[corner] = smooth
+                        .try_normalize(f32::EPSILON)
+                        .unwrap_or(face_normals[face]);
+                }
+
+                corners
+            })
+            .collect();
+
+        let creased = incident_faces
+            .iter()
+            .map(|faces| {
+                faces.iter().any(|&a| {
+                    faces
+                        .iter()
+                        .any(|&b| face_normals[a].dot(&face_normals[b]) < CREASE_THRESHOLD)
+                })
+            })
+            .collect();
+
+        WeldedMesh {
+            points,
+            triangles,
+            face_normals,
+            normals,
+            corner_normals,
+            creased,
+            size,
+        }
+    }
+
+    /// The line across a triangle where a linearly interpolated value is zero
+    pub fn zero_crossing(
+        &self,
+        triangle: &[usize; 3],
+        values: &[f32; 3],
+        kind: EdgeKind,
+    ) -> Option<EdgeLine> {
+        let mut crossings = Vec::with_capacity(2);
+
+        for (a, b) in [(0, 1), (1, 2), (2, 0)].iter().cloned() {
+            // exact zeros count as positive so that a line through a corner is found once
+            if (values[a] >= 0.0) == (values[b] >= 0.0) {
+                continue;
+            }
+
+            let t = values[a] / (values[a] - values[b]);
+            let (from, to) = (self.points[triangle[a]], self.points[triangle[b]]);
+
+            crossings.push(from + (to - from) * t);
+        }
+
+        match crossings[..] {
+            [from, to] => Some(EdgeLine {
+                line: LineSegment3 { from, to },
+                kind,
+                object_id: 0,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Merges points closer together than `WELD_TOLERANCE` of the size of the mesh, returning the
+/// merged points and which of them each of the given points became
+pub fn weld_points(points: &[Point3<f32>], size: f32) -> (Vec<Point3<f32>>, Vec<usize>) {
+    let tolerance = size * WELD_TOLERANCE;
+
+    let mut welded: Vec<Point3<f32>> = Vec::new();
+    let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    let cell = |point: &Point3<f32>| match tolerance > 0.0 {
+        true => (
+            (point.x / tolerance).floor() as i64,
+            (point.y / tolerance).floor() as i64,
+            (point.z / tolerance).floor() as i64,
+        ),
+        // with no size to scale by only identical points are merged
+        false => (0, 0, 0),
+    };
+
+    let weld = |point: &Point3<f32>| -> usize {
+        let (cx, cy, cz) = cell(point);
+
+        for x in cx.saturating_sub(1)..=cx.saturating_add(1) {
+            for y in cy.saturating_sub(1)..=cy.saturating_add(1) {
+                for z in cz.saturating_sub(1)..=cz.saturating_add(1) {
+                    if let Some(candidates) = grid.get(&(x, y, z)) {
+                        for &candidate in candidates {
+                            if na::distance(&welded[candidate], point) <= tolerance {
+                                return candidate;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        welded.push(*point);
+        grid.entry((cx, cy, cz)).or_default().push(welded.len() - 1);
+        welded.len() - 1
+    };
+
+    let ids = points.iter().map(weld).collect();
+
+    (welded, ids)
+}
+
+/// Opposite corners of the axis aligned box around the points
+pub fn bounds(points: &[Point3<f32>]) -> Option<(Point3<f32>, Point3<f32>)> {
+    let first = *points.first()?;
+
+    Some(points.iter().fold((first, first), |(min, max), point| {
+        (
+            Point3::new(min.x.min(point.x), min.y.min(point.y), min.z.min(point.z)),
+            Point3::new(max.x.max(point.x), max.y.max(point.y), max.z.max(point.z)),
+        )
+    }))
+}
@@ -0,0 +1,851 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use na::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::colour::Colour;
+use crate::cull::CullConfig;
+use crate::curvature::FeatureLineConfig;
+use crate::curve_fit::CurveFitConfig;
+use crate::dimensions::DimensionConfig;
+use crate::fill::{FillConfig, FillMode};
+use crate::hatching::HatchConfig;
+use crate::paper::{DrawingScale, LengthUnit, Orientation, PaperSize};
+use crate::renderer::Renderer;
+use crate::sheet::{SheetConfig, TitleBlock};
+use crate::simplify::{SimplifyConfig, SimplifyMethod};
+use crate::svg_renderer::{LineCap, LineJoin, SvgConfig, SvgLineConfig, SvgPageConfig};
+
+/// Decimal places path coordinates can be rounded to, beyond which an f32 has nothing left to give
+const MAX_PRECISION: u32 = 6;
+
+/// Everything about how a drawing is made, in a form that can be read from json, toml or a plain
+/// js object. Anything left out keeps its default, and sections like `hatch` or `fill` turn their
+/// feature on just by being present
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderOptions {
+    /// size of the fitted svg, the size of the canvas when not set
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub margin: Option<i32>,
+    pub fit_lines: Option<bool>,
+    pub visible: LineOptions,
+    pub obscured: LineOptions,
+    pub hide_obscured: bool,
+    pub optimise_path_order: bool,
+    pub group_layers: bool,
+    pub embed_style: bool,
+    pub precision: Option<u32>,
+    pub relative_commands: bool,
+    pub compact_paths: bool,
+    pub fit_curves: bool,
+    pub curve_tolerance: Option<f32>,
+    pub smooth_silhouettes: bool,
+    pub min_segment_length: Option<f32>,
+    pub min_object_size: Option<f32>,
+    /// lays the drawing out on paper instead of fitting it to the svg size
+    pub page: Option<PageOptions>,
+    pub sheet: Option<SheetOptions>,
+    pub dimensions: Option<DimensionOptions>,
+    pub fill: Option<FillOptions>,
+    pub hatch: Option<HatchOptions>,
+    pub feature_lines: Option<FeatureLineOptions>,
+    pub simplify: Option<SimplifyOptions>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LineOptions {
+    pub stroke_width: Option<f32>,
+    /// any css colour, e.g. `currentColor`. Raster and pdf output draw colours they can't read in
+    /// black
+    pub stroke: Option<String>,
+    /// solid when not set, unless `iso_dashes` is on
+    pub dash_array: Option<Vec<f32>>,
+    /// ISO 128 hidden line dashes in proportion to the stroke width, when there's no dash array
+    pub iso_dashes: bool,
+    pub dash_offset: Option<f32>,
+    pub opacity: Option<f32>,
+    /// `butt`, `round` or `square`, round when not set
+    pub linecap: Option<String>,
+    /// `miter`, `round` or `bevel`, round when not set
+    pub linejoin: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PageOptions {
+    /// `a4`, `a3` or `letter`, a4 when not set
+    pub paper_size: Option<String>,
+    pub portrait: bool,
+    pub margin_mm: Option<f32>,
+    /// a ratio like `1:2`, fitted to the page when not set
+    pub scale: Option<String>,
+    pub model_unit_mm: Option<f32>,
+    /// `mm` or `in`
+    pub units: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SheetOptions {
+    /// framed unless turned off
+    pub border: Option<bool>,
+    /// the title block is only drawn when there's a title
+    pub title: Option<String>,
+    pub part_number: Option<String>,
+    pub scale: Option<String>,
+    pub date: Option<String>,
+    pub author: Option<String>,
+    pub view_label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DimensionOptions {
+    pub overall: bool,
+    /// the x, y and z of both points of each dimension
+    pub points: Vec<[f32; 6]>,
+    pub precision: Option<usize>,
+    pub value_scale: Option<f32>,
+    pub unit_suffix: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct FillOptions {
+    /// `flat` or `silhouette`, flat when not set
+    pub mode: Option<String>,
+    pub colour: Option<String>,
+    pub shadow: Option<String>,
+    pub light_direction: Option<[f32; 3]>,
+    pub shades: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct HatchOptions {
+    pub spacing: Option<f32>,
+    pub angle: Option<f32>,
+    pub tones: Option<u32>,
+    pub cross_hatch_from: Option<f32>,
+    pub light_direction: Option<[f32; 3]>,
+    pub stroke_width: Option<f32>,
+    /// any css colour, as for lines
+    pub stroke: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureLineOptions {
+    pub ridges: Option<bool>,
+    pub valleys: Option<bool>,
+    pub suggestive_contours: Option<bool>,
+    pub curvature_threshold: Option<f32>,
+    pub suggestive_threshold: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimplifyOptions {
+    /// `douglas-peucker` or `visvalingam`, douglas-peucker when not set
+    pub method: Option<String>,
+    pub tolerance: Option<f32>,
+}
+
+/// An option with a value that can't be used, named by its path within the options, e.g.
+/// `hatch.spacing`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidOption {
+    pub option: String,
+    pub message: String,
+}
+
+impl Display for InvalidOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}: {}", self.option, self.message)
+    }
+}
+
+impl Error for InvalidOption {}
+
+impl RenderOptions {
+    /// Checks every value can be used with a canvas of the given size, so that a drawing isn't
+    /// made with half of its options
+    pub fn validate(&self, canvas_width: i32, canvas_height: i32) -> Result<(), InvalidOption> {
+        self.to_renderer(canvas_width, canvas_height).map(|_| ())
+    }
+
+    /// The renderer for these options, with the svg the size of the canvas unless a size is set
+    pub fn to_renderer(
+        &self,
+        canvas_width: i32,
+        canvas_height: i32,
+    ) -> Result<Renderer, InvalidOption> {
+        let mut renderer = Renderer::new_with_config(self.svg_config(canvas_width, canvas_height)?);
+
+        renderer.page = self.page_config()?;
+        renderer.smooth_silhouettes = self.smooth_silhouettes;
+        renderer.cull = self.cull_config()?;
+        renderer.feature_lines = self.feature_line_config()?;
+        renderer.simplify = self.simplify_config()?;
+        renderer.dimensions = self.dimension_config()?;
+        renderer.fill = self.fill_config()?;
+        renderer.hatch = self.hatch_config()?;
+
+        if let Some(hatch) = &self.hatch {
+            if let Some(stroke_width) = hatch.stroke_width {
+                positive("hatch.stroke_width", stroke_width)?;
+                renderer.svg_config.hatch.stroke_width = stroke_width;
+            }
+            if let Some(stroke) = &hatch.stroke {
+                renderer.svg_config.hatch.stroke = stroke.clone();
+            }
+        }
+
+        Ok(renderer)
+    }
+
+    pub fn svg_config(
+        &self,
+        canvas_width: i32,
+        canvas_height: i32,
+    ) -> Result<SvgConfig, InvalidOption> {
+        if let Some(width) = self.width {
+            positive("width", width as f32)?;
+        }
+        if let Some(height) = self.height {
+            positive("height", height as f32)?;
+        }
+        let fit_lines = self.fit_lines.unwrap_or(true);
+        let width = self.width.unwrap_or(canvas_width);
+        let height = self.height.unwrap_or(canvas_height);
+
+        if let Some(margin) = self.margin {
+            not_negative("margin", margin as f32)?;
+
+            // the margin is taken from both sides of the svg
+            if fit_lines && margin * 2 >= width.min(height) {
+                return Err(InvalidOption {
+                    option: "margin".to_owned(),
+                    message: format!(
+                        "leaves nothing to draw in a {} by {} svg, got {}",
+                        width, height, margin
+                    ),
+                });
+            }
+        }
+        self.visible.validate("visible")?;
+        self.obscured.validate("obscured")?;
+
+        if let Some(precision) = self.precision {
+            if precision > MAX_PRECISION {
+                return Err(InvalidOption {
+                    option: "precision".to_owned(),
+                    message: format!("must be at most {}, got {}", MAX_PRECISION, precision),
+                });
+            }
+        }
+
+        let mut svg_config = SvgConfig {
+            width,
+            height,
+            // unfitted lines keep their canvas positions, so there's nothing to leave a margin in
+            margin: match fit_lines {
+                true => self.margin.unwrap_or(100),
+                false => 0,
+            },
+            fit_lines,
+            optimise_path_order: self.optimise_path_order,
+            group_layers: self.group_layers,
+            embed_style: self.embed_style,
+            precision: self.precision,
+            relative_commands: self.relative_commands,
+            compact_paths: self.compact_paths,
+            ..SvgConfig::new_default(canvas_width, canvas_height)
+        };
+
+        if self.fit_curves {
+            let mut curve_fit = CurveFitConfig::new_default();
+
+            if let Some(tolerance) = self.curve_tolerance {
+                positive("curve_tolerance", tolerance)?;
+                curve_fit.tolerance = tolerance;
+            }
+
+            svg_config.curve_fit = Some(curve_fit);
+        }
+
+        self.visible.apply_to("visible", &mut svg_config.visible)?;
+        match self.hide_obscured {
+            true => svg_config.obscured = None,
+            false => {
+                if let Some(obscured) = &mut svg_config.obscured {
+                    self.obscured.apply_to("obscured", obscured)?;
+                }
+            }
+        }
+
+        svg_config.sheet = self.sheet.as_ref().map(SheetOptions::to_config);
+
+        Ok(svg_config)
+    }
+
+    pub fn page_config(&self) -> Result<Option<SvgPageConfig>, InvalidOption> {
+        let page = match &self.page {
+            Some(page) => page,
+            None => return Ok(None),
+        };
+
+        let mut page_config = SvgPageConfig::new_default();
+
+        if let Some(paper_size) = &page.paper_size {
+            page_config.page.size = PaperSize::from_name(paper_size)
+                .ok_or_else(|| unsupported("page.paper_size", paper_size, "a4, a3 or letter"))?;
+        }
+        if page.portrait {
+            page_config.page.orientation = Orientation::Portrait;
+        }
+        if let Some(margin) = page.margin_mm {
+            not_negative("page.margin_mm", margin)?;
+
+            // the margin is taken from both sides of the page
+            let (page_width, page_height) = page_config.page.dimensions_mm();
+            if margin * 2.0 >= page_width.min(page_height) {
+                return Err(InvalidOption {
+                    option: "page.margin_mm".to_owned(),
+                    message: format!(
+                        "leaves nothing to draw on a {} by {}mm page, got {}",
+                        page_width, page_height, margin
+                    ),
+                });
+            }
+
+            page_config.page.margin_mm = margin;
+        }
+        if let Some(scale) = &page.scale {
+            page_config.scale = DrawingScale::from_ratio(scale)
+                .ok_or_else(|| unsupported("page.scale", scale, "a ratio like 1:2"))?;
+        }
+        if let Some(model_unit_mm) = page.model_unit_mm {
+            positive("page.model_unit_mm", model_unit_mm)?;
+            page_config.model_unit_mm = model_unit_mm;
+        }
+        if let Some(units) = &page.units {
+            page_config.units = LengthUnit::from_name(units)
+                .ok_or_else(|| unsupported("page.units", units, "mm or in"))?;
+        }
+
+        Ok(Some(page_config))
+    }
+
+    pub fn cull_config(&self) -> Result<CullConfig, InvalidOption> {
+        let mut cull_config = CullConfig::new_default();
+
+        if let Some(length) = self.min_segment_length {
+            not_negative("min_segment_length", length)?;
+            cull_config.min_segment_length = length;
+        }
+        if let Some(size) = self.min_object_size {
+            not_negative("min_object_size", size)?;
+            cull_config.min_object_size = size;
+        }
+
+        Ok(cull_config)
+    }
+
+    pub fn feature_line_config(&self) -> Result<Option<FeatureLineConfig>, InvalidOption> {
+        let feature_lines = match &self.feature_lines {
+            Some(feature_lines) => feature_lines,
+            None => return Ok(None),
+        };
+
+        let mut feature_line_config = FeatureLineConfig::new_default();
+
+        if let Some(ridges) = feature_lines.ridges {
+            feature_line_config.ridges = ridges;
+        }
+        if let Some(valleys) = feature_lines.valleys {
+            feature_line_config.valleys = valleys;
+        }
+        if let Some(suggestive_contours) = feature_lines.suggestive_contours {
+            feature_line_config.suggestive_contours = suggestive_contours;
+        }
+        if let Some(threshold) = feature_lines.curvature_threshold {
+            not_negative("feature_lines.curvature_threshold", threshold)?;
+            feature_line_config.curvature_threshold = threshold;
+        }
+        if let Some(threshold) = feature_lines.suggestive_threshold {
+            not_negative("feature_lines.suggestive_threshold", threshold)?;
+            feature_line_config.suggestive_threshold = threshold;
+        }
+
+        Ok(Some(feature_line_config))
+    }
+
+    pub fn simplify_config(&self) -> Result<Option<SimplifyConfig>, InvalidOption> {
+        let simplify = match &self.simplify {
+            Some(simplify) => simplify,
+            None => return Ok(None),
+        };
+
+        let mut simplify_config = SimplifyConfig::new_default();
+
+        if let Some(method) = &simplify.method {
+            simplify_config.method = SimplifyMethod::from_name(method).ok_or_else(|| {
+                unsupported("simplify.method", method, "douglas-peucker or visvalingam")
+            })?;
+        }
+        if let Some(tolerance) = simplify.tolerance {
+            positive("simplify.tolerance", tolerance)?;
+            simplify_config.tolerance = tolerance;
+        }
+
+        Ok(Some(simplify_config))
+    }
+
+    pub fn dimension_config(&self) -> Result<Option<DimensionConfig>, InvalidOption> {
+        let dimensions = match &self.dimensions {
+            Some(dimensions) => dimensions,
+            None => return Ok(None),
+        };
+
+        let mut dimension_config = DimensionConfig::new_default();
+
+        dimension_config.overall = dimensions.overall;
+        dimension_config.point_to_point = dimensions
+            .points
+            .iter()
+            .map(|p| (Point3::new(p[0], p[1], p[2]), Point3::new(p[3], p[4], p[5])))
+            .collect();
+        if let Some(precision) = dimensions.precision {
+            dimension_config.precision = precision;
+        }
+        if let Some(value_scale) = dimensions.value_scale {
+            positive("dimensions.value_scale", value_scale)?;
+            dimension_config.value_scale = value_scale;
+        }
+        if let Some(unit_suffix) = &dimensions.unit_suffix {
+            dimension_config.unit_suffix = unit_suffix.clone();
+        }
+
+        Ok(Some(dimension_config))
+    }
+
+    pub fn hatch_config(&self) -> Result<Option<HatchConfig>, InvalidOption> {
+        self.hatch.as_ref().map(HatchOptions::to_config).transpose()
+    }
+
+    pub fn fill_config(&self) -> Result<Option<FillConfig>, InvalidOption> {
+        let fill = match &self.fill {
+            Some(fill) => fill,
+            None => return Ok(None),
+        };
+
+        let mut fill_config = FillConfig::new_default();
+
+        if let Some(mode) = &fill.mode {
+            fill_config.mode = FillMode::from_name(mode)
+                .ok_or_else(|| unsupported("fill.mode", mode, "flat or silhouette"))?;
+        }
+        if let Some(lit) = &fill.colour {
+            fill_config.colour = colour("fill.colour", lit)?;
+        }
+        if let Some(shadow) = &fill.shadow {
+            fill_config.shadow = colour("fill.shadow", shadow)?;
+        }
+        if let Some(light) = fill.light_direction {
+            fill_config.light_direction = direction("fill.light_direction", light)?;
+        }
+        if let Some(shades) = fill.shades {
+            positive("fill.shades", shades as f32)?;
+        }
+        fill_config.shades = fill.shades;
+
+        Ok(Some(fill_config))
+    }
+}
+
+impl LineOptions {
+    fn validate(&self, section: &str) -> Result<(), InvalidOption> {
+        if let Some(stroke_width) = self.stroke_width {
+            positive(&format!("{}.stroke_width", section), stroke_width)?;
+        }
+        if let Some(opacity) = self.opacity {
+            if !(0.0..=1.0).contains(&opacity) {
+                return Err(InvalidOption {
+                    option: format!("{}.opacity", section),
+                    message: format!("must be between 0 and 1, got {}", opacity),
+                });
+            }
+        }
+        if let Some(dash_array) = &self.dash_array {
+            for &dash in dash_array {
+                not_negative(&format!("{}.dash_array", section), dash)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets whatever's given on the line config, named by its section in any error
+    pub fn apply_to(
+        &self,
+        section: &str,
+        line_config: &mut SvgLineConfig,
+    ) -> Result<(), InvalidOption> {
+        self.validate(section)?;
+
+        if let Some(stroke_width) = self.stroke_width {
+            line_config.stroke_width = stroke_width;
+        }
+        if let Some(stroke) = &self.stroke {
+            line_config.stroke = stroke.clone();
+        }
+        if let Some(dash_array) = &self.dash_array {
+            line_config.dash_array = dash_array.clone();
+        }
+        if let Some(opacity) = self.opacity {
+            line_config.opacity = opacity;
+        }
+        if self.iso_dashes && self.dash_array.is_none() {
+            line_config.dash_array = SvgLineConfig::iso_hidden_dash_array(line_config.stroke_width);
+        }
+        if let Some(dash_offset) = self.dash_offset {
+            line_config.dash_offset = dash_offset;
+        }
+        if let Some(linecap) = &self.linecap {
+            line_config.linecap = LineCap::from_name(linecap).ok_or_else(|| {
+                unsupported(
+                    &format!("{}.linecap", section),
+                    linecap,
+                    "butt, round or square",
+                )
+            })?;
+        }
+        if let Some(linejoin) = &self.linejoin {
+            line_config.linejoin = LineJoin::from_name(linejoin).ok_or_else(|| {
+                unsupported(
+                    &format!("{}.linejoin", section),
+                    linejoin,
+                    "miter, round or bevel",
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SheetOptions {
+    fn to_config(&self) -> SheetConfig {
+        let mut sheet = SheetConfig::new_default();
+
+        sheet.border = self.border.unwrap_or(true);
+        sheet.view_label = self.view_label.clone();
+        sheet.title_block = self.title.clone().map(|title| TitleBlock {
+            part_number: self.part_number.clone(),
+            scale: self.scale.clone(),
+            date: self.date.clone(),
+            author: self.author.clone(),
+            ..TitleBlock::new(title)
+        });
+
+        sheet
+    }
+}
+
+impl HatchOptions {
+    fn to_config(&self) -> Result<HatchConfig, InvalidOption> {
+        let mut hatch_config = HatchConfig::new_default();
+
+        if let Some(spacing) = self.spacing {
+            positive("hatch.spacing", spacing)?;
+            hatch_config.spacing = spacing;
+        }
+        if let Some(angle) = self.angle {
+            hatch_config.angle = angle;
+        }
+        if let Some(tones) = self.tones {
+            positive("hatch.tones", tones as f32)?;
+            hatch_config.tones = tones;
+        }
+        if let Some(cross_hatch_from) = self.cross_hatch_from {
+            if !(0.0..=1.0).contains(&cross_hatch_from) {
+                return Err(InvalidOption {
+                    option: "hatch.cross_hatch_from".to_owned(),
+                    message: format!("must be between 0 and 1, got {}", cross_hatch_from),
+                });
+            }
+            hatch_config.cross_hatch_from = Some(cross_hatch_from);
+        }
+        if let Some(light) = self.light_direction {
+            hatch_config.light_direction = direction("hatch.light_direction", light)?;
+        }
+
+        Ok(hatch_config)
+    }
+}
+
+fn positive(option: &str, value: f32) -> Result<(), InvalidOption> {
+    match value > 0.0 {
+        true => Ok(()),
+        false => Err(InvalidOption {
+            option: option.to_owned(),
+            message: format!("must be greater than 0, got {}", value),
+        }),
+    }
+}
+
+fn not_negative(option: &str, value: f32) -> Result<(), InvalidOption> {
+    match value >= 0.0 {
+        true => Ok(()),
+        false => Err(InvalidOption {
+            option: option.to_owned(),
+            message: format!("can't be negative, got {}", value),
+        }),
+    }
+}
+
+fn colour(option: &str, css: &str) -> Result<Colour, InvalidOption> {
+    Colour::parse(css).ok_or_else(|| {
+        unsupported(
+            option,
+            css,
+            "a hex, rgb(), rgba() or named colour like black",
+        )
+    })
+}
+
+fn unsupported(option: &str, value: &str, expected: &str) -> InvalidOption {
+    InvalidOption {
+        option: option.to_owned(),
+        message: format!("expected {}, got \"{}\"", expected, value),
+    }
+}
+
+fn direction(option: &str, value: [f32; 3]) -> Result<Vector3<f32>, InvalidOption> {
+    let direction = Vector3::new(value[0], value[1], value[2]);
+
+    match direction.norm() > 0.0 {
+        true => Ok(direction),
+        false => Err(InvalidOption {
+            option: option.to_owned(),
+            message: "must have a length".to_owned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_option(options: &RenderOptions) -> String {
+        match options.validate(800, 600) {
+            Ok(()) => panic!("expected the options to be invalid"),
+            Err(invalid_option) => invalid_option.option,
+        }
+    }
+
+    #[test]
+    fn accepts_the_defaults() {
+        assert!(RenderOptions::default().validate(800, 600).is_ok());
+    }
+
+    #[test]
+    fn reads_json() {
+        let options: RenderOptions = serde_json::from_str(
+            r##"{ "width": 800, "visible": { "stroke": "#333", "linecap": "butt" }, "hatch": {} }"##,
+        )
+        .unwrap();
+
+        let renderer = options.to_renderer(400, 300).unwrap();
+
+        assert_eq!(renderer.svg_config.width, 800);
+        assert_eq!(renderer.svg_config.height, 300);
+        assert_eq!(renderer.svg_config.visible.stroke, "#333");
+        assert!(renderer.svg_config.visible.linecap == LineCap::Butt);
+        assert!(renderer.hatch.is_some());
+        assert!(renderer.fill.is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(serde_json::from_str::<RenderOptions>(r#"{ "widht": 800 }"#).is_err());
+    }
+
+    #[test]
+    fn names_the_invalid_option() {
+        let options = RenderOptions {
+            obscured: LineOptions {
+                opacity: Some(1.5),
+                ..LineOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(
+            options.validate(800, 600),
+            Err(InvalidOption {
+                option: "obscured.opacity".to_owned(),
+                message: "must be between 0 and 1, got 1.5".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                width: Some(0),
+                ..RenderOptions::default()
+            }),
+            "width"
+        );
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                margin: Some(-1),
+                ..RenderOptions::default()
+            }),
+            "margin"
+        );
+        // taken from both sides of the 600 pixel height
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                margin: Some(300),
+                ..RenderOptions::default()
+            }),
+            "margin"
+        );
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                precision: Some(7),
+                ..RenderOptions::default()
+            }),
+            "precision"
+        );
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                min_segment_length: Some(-0.5),
+                ..RenderOptions::default()
+            }),
+            "min_segment_length"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_fill_colours() {
+        // fills are shaded between their colours, so they have to be read
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                fill: Some(FillOptions {
+                    shadow: Some("#12".to_owned()),
+                    ..FillOptions::default()
+                }),
+                ..RenderOptions::default()
+            }),
+            "fill.shadow"
+        );
+    }
+
+    #[test]
+    fn passes_css_strokes_through() {
+        for stroke in &[
+            "currentColor",
+            "steelblue",
+            "hsl(210, 50%, 40%)",
+            "rgb(0 0 0)",
+        ] {
+            let options = RenderOptions {
+                visible: LineOptions {
+                    stroke: Some(stroke.to_string()),
+                    ..LineOptions::default()
+                },
+                hatch: Some(HatchOptions {
+                    stroke: Some(stroke.to_string()),
+                    ..HatchOptions::default()
+                }),
+                ..RenderOptions::default()
+            };
+
+            let renderer = options.to_renderer(800, 600).unwrap();
+
+            assert_eq!(renderer.svg_config.visible.stroke, *stroke);
+            assert_eq!(renderer.svg_config.hatch.stroke, *stroke);
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_names() {
+        let options = RenderOptions {
+            simplify: Some(SimplifyOptions {
+                method: Some("ramer".to_owned()),
+                ..SimplifyOptions::default()
+            }),
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(
+            options.validate(800, 600).unwrap_err().to_string(),
+            "invalid simplify.method: expected douglas-peucker or visvalingam, got \"ramer\""
+        );
+    }
+
+    #[test]
+    fn rejects_bad_sections() {
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                page: Some(PageOptions {
+                    margin_mm: Some(105.0),
+                    ..PageOptions::default()
+                }),
+                ..RenderOptions::default()
+            }),
+            "page.margin_mm"
+        );
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                hatch: Some(HatchOptions {
+                    light_direction: Some([0.0, 0.0, 0.0]),
+                    ..HatchOptions::default()
+                }),
+                ..RenderOptions::default()
+            }),
+            "hatch.light_direction"
+        );
+        assert_eq!(
+            invalid_option(&RenderOptions {
+                fill: Some(FillOptions {
+                    shades: Some(0),
+                    ..FillOptions::default()
+                }),
+                ..RenderOptions::default()
+            }),
+            "fill.shades"
+        );
+    }
+
+    #[test]
+    fn hides_obscured_lines() {
+        let options = RenderOptions {
+            hide_obscured: true,
+            ..RenderOptions::default()
+        };
+
+        assert!(options.svg_config(100, 100).unwrap().obscured.is_none());
+    }
+
+    #[test]
+    fn drops_the_margin_of_unfitted_lines() {
+        let options = RenderOptions {
+            margin: Some(20),
+            fit_lines: Some(false),
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(options.svg_config(100, 100).unwrap().margin, 0);
+    }
+}
@@ -0,0 +1,369 @@
+use na::{Point2, Vector2};
+
+use crate::error::MeshToSvgError;
+use crate::lines::LineSegmentCategorized;
+use crate::scene::Scene;
+
+pub const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+#[derive(Copy, Clone)]
+pub enum PaperSize {
+    A4,
+    A3,
+    Letter,
+    Custom { width_mm: f32, height_mm: f32 },
+}
+
+#[derive(Copy, Clone)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+#[derive(Copy, Clone)]
+pub enum DrawingScale {
+    /// scale the drawing to fill the printable area
+    Fit,
+    /// a fixed ratio of paper to model length, e.g. `Exact(0.5)` for 1:2
+    Exact(f32),
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum LengthUnit {
+    Millimetres,
+    Inches,
+}
+
+#[derive(Clone)]
+pub struct Page {
+    pub size: PaperSize,
+    pub orientation: Orientation,
+    pub margin_mm: f32,
+}
+
+impl Page {
+    pub fn new_default() -> Page {
+        Page {
+            size: PaperSize::A4,
+            orientation: Orientation::Landscape,
+            margin_mm: 10.0,
+        }
+    }
+
+    /// Width and height in mm, accounting for orientation
+    pub fn dimensions_mm(&self) -> (f32, f32) {
+        let (short, long) = match self.size {
+            PaperSize::A4 => (210.0, 297.0),
+            PaperSize::A3 => (297.0, 420.0),
+            PaperSize::Letter => (215.9, 279.4),
+            PaperSize::Custom {
+                width_mm,
+                height_mm,
+            } => (width_mm.min(height_mm), width_mm.max(height_mm)),
+        };
+
+        match self.orientation {
+            Orientation::Portrait => (short, long),
+            Orientation::Landscape => (long, short),
+        }
+    }
+}
+
+impl PaperSize {
+    pub fn from_name(name: &str) -> Option<PaperSize> {
+        match name.to_lowercase().as_str() {
+            "a4" => Some(PaperSize::A4),
+            "a3" => Some(PaperSize::A3),
+            "letter" => Some(PaperSize::Letter),
+            _ => None,
+        }
+    }
+}
+
+impl LengthUnit {
+    pub fn from_name(name: &str) -> Option<LengthUnit> {
+        match name.to_lowercase().as_str() {
+            "mm" => Some(LengthUnit::Millimetres),
+            "in" => Some(LengthUnit::Inches),
+            _ => None,
+        }
+    }
+
+    /// Length of one unit in mm
+    pub fn mm(&self) -> f32 {
+        match self {
+            LengthUnit::Millimetres => 1.0,
+            LengthUnit::Inches => 25.4,
+        }
+    }
+
+    /// Suffix used for css and svg lengths
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            LengthUnit::Millimetres => "mm",
+            LengthUnit::Inches => "in",
+        }
+    }
+}
+
+impl DrawingScale {
+    /// Parses ratios in the conventional drawing notation, e.g. `1:2` or `5:1`
+    pub fn from_ratio(ratio: &str) -> Option<DrawingScale> {
+        let mut parts = ratio.split(':');
+        let paper: f32 = parts.next()?.trim().parse().ok()?;
+        let model: f32 = parts.next()?.trim().parse().ok()?;
+
+        if parts.next().is_some() || paper <= 0.0 || model <= 0.0 {
+            return None;
+        }
+
+        Some(DrawingScale::Exact(paper / model))
+    }
+
+    /// The scale in drawing notation, e.g. `1:2`, or `None` when fitted to the page
+    pub fn ratio_label(&self) -> Option<String> {
+        // rounded so that float error doesn't show up as 1:4.9999995
+        let round = |value: f32| (value * 1000.0).round() / 1000.0;
+
+        match self {
+            DrawingScale::Fit => None,
+            DrawingScale::Exact(ratio) if *ratio < 1.0 => Some(format!("1:{}", round(1.0 / ratio))),
+            DrawingScale::Exact(ratio) => Some(format!("{}:1", round(*ratio))),
+        }
+    }
+}
+
+/// Maps screen space onto a page in mm (y down), centring the drawing within the margins
+pub struct PageLayout {
+    /// mm on paper per screen space unit
+    pub scale: f32,
+    offset: Vector2<f32>,
+}
+
+impl PageLayout {
+    /// `model_unit_mm` is the real world length of one world space unit, which is only needed for
+    /// exact scales
+    pub fn new(
+        screen_space_lines: &[LineSegmentCategorized],
+        scene: &Scene,
+        page: &Page,
+        drawing_scale: DrawingScale,
+        model_unit_mm: f32,
+    ) -> Result<PageLayout, MeshToSvgError> {
+        let (page_width, page_height) = page.dimensions_mm();
+
+        PageLayout::new_in_area(
+            screen_space_lines,
+            scene,
+            &Point2::new(page.margin_mm, page.margin_mm),
+            &Vector2::new(
+                page_width - page.margin_mm * 2.0,
+                page_height - page.margin_mm * 2.0,
+            ),
+            drawing_scale,
+            model_unit_mm,
+        )
+    }
+
+    /// Same as `new`, but centres the drawing within an area of the page given in mm.
+    ///
+    /// An exact scale is measured in world units, so it needs an orthographic scene
+    pub fn new_in_area(
+        screen_space_lines: &[LineSegmentCategorized],
+        scene: &Scene,
+        area_origin: &Point2<f32>,
+        area_size: &Vector2<f32>,
+        drawing_scale: DrawingScale,
+        model_unit_mm: f32,
+    ) -> Result<PageLayout, MeshToSvgError> {
+        let (min_bound, max_bound) = match bounds(screen_space_lines) {
+            Some(bounds) => bounds,
+            None => (
+                Vector2::new(0.0, 0.0),
+                Vector2::new(scene.width, scene.height),
+            ),
+        };
+        let viewport = max_bound - min_bound;
+
+        let scale = match drawing_scale {
+            DrawingScale::Fit => fit_scale(area_size, &viewport),
+            DrawingScale::Exact(_) if !scene.is_orthographic() => {
+                return Err(MeshToSvgError::NotOrthographic {
+                    output: "an exact drawing scale",
+                });
+            }
+            DrawingScale::Exact(ratio) => ratio * model_unit_mm / scene.screen_space_scale(),
+        };
+
+        let area_centre = area_origin.coords + area_size * 0.5;
+        let drawing_centre = (min_bound + max_bound) * 0.5;
+
+        Ok(PageLayout {
+            scale,
+            offset: area_centre - drawing_centre * scale,
+        })
+    }
+
+    pub fn to_page(&self, point: &Point2<f32>) -> Point2<f32> {
+        point * self.scale + self.offset
+    }
+}
+
+// a drawing with no extent along an axis is only fitted along the other one, and a single point
+// isn't scaled at all
+pub fn fit_scale(area_size: &Vector2<f32>, viewport: &Vector2<f32>) -> f32 {
+    let fit = |area: f32, extent: f32| match extent > 0.0 {
+        true => area / extent,
+        false => f32::INFINITY,
+    };
+    let scale = f32::min(fit(area_size.x, viewport.x), fit(area_size.y, viewport.y));
+
+    match scale.is_finite() {
+        true => scale,
+        false => 1.0,
+    }
+}
+
+fn bounds(lines: &[LineSegmentCategorized]) -> Option<(Vector2<f32>, Vector2<f32>)> {
+    let mut points = lines
+        .iter()
+        .flat_map(|line| vec![line.line_segment.from, line.line_segment.to]);
+
+    let first = points.next()?.coords;
+
+    Some(points.fold((first, first), |(min, max), point| {
+        (
+            Vector2::new(min.x.min(point.x), min.y.min(point.y)),
+            Vector2::new(max.x.max(point.x), max.y.max(point.y)),
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use na::{Matrix4, Point3};
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineSegment2, LineSegment3, LineVisibility};
+
+    fn segment(from: (f32, f32), to: (f32, f32)) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility: LineVisibility::VISIBLE,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    #[test]
+    fn orients_pages() {
+        let mut page = Page::new_default();
+        assert_eq!(page.dimensions_mm(), (297.0, 210.0));
+
+        page.orientation = Orientation::Portrait;
+        assert_eq!(page.dimensions_mm(), (210.0, 297.0));
+
+        page.size = PaperSize::Custom {
+            width_mm: 300.0,
+            height_mm: 100.0,
+        };
+        assert_eq!(page.dimensions_mm(), (100.0, 300.0));
+    }
+
+    #[test]
+    fn parses_drawing_scales() {
+        let ratio = |text: &str| match DrawingScale::from_ratio(text) {
+            Some(DrawingScale::Exact(ratio)) => Some(ratio),
+            _ => None,
+        };
+
+        assert_eq!(ratio("1:2"), Some(0.5));
+        assert_eq!(ratio(" 5 : 1 "), Some(5.0));
+        assert_eq!(ratio("1:0"), None);
+        assert_eq!(ratio("1:2:3"), None);
+        assert_eq!(ratio("2"), None);
+        assert_eq!(ratio("a:b"), None);
+
+        assert_eq!(
+            DrawingScale::Exact(0.2).ratio_label(),
+            Some("1:5".to_owned())
+        );
+        assert_eq!(
+            DrawingScale::Exact(2.5).ratio_label(),
+            Some("2.5:1".to_owned())
+        );
+        assert_eq!(DrawingScale::Fit.ratio_label(), None);
+    }
+
+    #[test]
+    fn fits_and_centres_within_the_margins() {
+        let lines = vec![segment((0.0, 0.0), (100.0, 50.0))];
+
+        let layout = PageLayout::new(
+            &lines,
+            &Scene::new_test(),
+            &Page::new_default(),
+            DrawingScale::Fit,
+            1.0,
+        )
+        .unwrap();
+
+        // the 277mm wide printable area is the tighter fit
+        assert_relative_eq!(layout.scale, 2.77);
+        assert_relative_eq!(
+            layout.to_page(&Point2::new(50.0, 25.0)),
+            Point2::new(148.5, 105.0)
+        );
+        assert_relative_eq!(
+            layout.to_page(&Point2::new(0.0, 0.0)).x,
+            10.0,
+            epsilon = 1e-4
+        );
+    }
+
+    #[test]
+    fn fits_degenerate_drawings() {
+        let scene = Scene::new_test();
+        let page = Page::new_default();
+        let layout = |lines: &[LineSegmentCategorized]| {
+            PageLayout::new(lines, &scene, &page, DrawingScale::Fit, 1.0).unwrap()
+        };
+
+        // an empty drawing fits the scene's canvas
+        assert_relative_eq!(layout(&[]).scale, 190.0 / 600.0);
+        // a horizontal line only fits along x
+        assert_relative_eq!(layout(&[segment((0.0, 5.0), (100.0, 5.0))]).scale, 2.77);
+        // and a single point is centred unscaled
+        let point = layout(&[segment((5.0, 5.0), (5.0, 5.0))]);
+        assert_relative_eq!(point.scale, 1.0);
+        assert_relative_eq!(
+            point.to_page(&Point2::new(5.0, 5.0)),
+            Point2::new(148.5, 105.0)
+        );
+    }
+
+    #[test]
+    fn exact_scales_need_orthographic_scenes() {
+        let lines = vec![segment((0.0, 0.0), (100.0, 50.0))];
+        let mut scene = Scene::new_test();
+        let page = Page::new_default();
+
+        let layout = PageLayout::new(&lines, &scene, &page, DrawingScale::Exact(0.5), 2.0).unwrap();
+        assert_relative_eq!(layout.scale * scene.screen_space_scale(), 1.0);
+
+        scene.projection_matrix = Matrix4::new_perspective(4.0 / 3.0, 0.8, 0.1, 1000.0);
+        assert!(
+            PageLayout::new(&lines, &scene, &page, DrawingScale::Exact(0.5), 2.0).err()
+                == Some(MeshToSvgError::NotOrthographic {
+                    output: "an exact drawing scale"
+                })
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use na::{distance, Point2};
+
+use crate::lines::LineSegmentCategorized;
+use crate::polyline::{chain_line_segments, ordered_line_classes, Polyline};
+
+// each 2-opt pass is quadratic in the number of paths, so cap the passes to keep huge drawings
+// from stalling when every pass only finds a marginal improvement
+const MAX_TWO_OPT_PASSES: usize = 10;
+
+pub struct PlotStatistics {
+    pub draw_length: f32,
+    pub travel_length: f32,
+}
+
+/// Chains the (already scaled) lines into polylines and orders them for a pen plotter, returning
+/// the optimised paths alongside the resulting draw and pen-up travel lengths
+pub fn optimise_plot_order(lines: &[LineSegmentCategorized]) -> (Vec<Polyline>, PlotStatistics) {
+    let ordered = optimise_path_order(chain_line_segments(lines));
+    let statistics = plot_statistics(&ordered);
+
+    (ordered, statistics)
+}
+
+/// Reorders and reverses polylines to minimise the pen-up travel between them, starting from the
+/// origin. Paths of each visibility and edge kind are kept together so that pen changes only
+/// happen once per class
+pub fn optimise_path_order(polylines: Vec<Polyline>) -> Vec<Polyline> {
+    let classes = ordered_line_classes(polylines.iter().map(|p| (p.visibility, p.kind)));
+
+    optimise_path_order_by(polylines, |polyline| {
+        classes
+            .iter()
+            .position(|&class| class == (polyline.visibility, polyline.kind))
+    })
+}
+
+/// Same as `optimise_path_order`, but with the paths kept together by a key of their own and the
+/// keys drawn in ascending order, for output that draws its classes in a different order
+pub fn optimise_path_order_by<K, F>(polylines: Vec<Polyline>, group_key: F) -> Vec<Polyline>
+where
+    K: Ord,
+    F: Fn(&Polyline) -> K,
+{
+    let mut keyed: Vec<(K, Polyline)> = polylines
+        .into_iter()
+        .map(|polyline| (group_key(&polyline), polyline))
+        .collect();
+    // stable, so paths keep their order within each group
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut groups: Vec<(K, Vec<Polyline>)> = Vec::new();
+    for (key, polyline) in keyed {
+        match groups.last_mut() {
+            Some((last_key, group)) if *last_key == key => group.push(polyline),
+            _ => groups.push((key, vec![polyline])),
+        }
+    }
+
+    let mut ordered = Vec::new();
+    let mut position = Point2::origin();
+
+    for (_, group) in groups {
+        let mut group_order = nearest_neighbour_order(group, &position);
+        two_opt(&mut group_order, &position);
+
+        position = *group_order.last().unwrap().end();
+        ordered.append(&mut group_order);
+    }
+
+    ordered
+}
+
+pub fn plot_statistics(polylines: &[Polyline]) -> PlotStatistics {
+    let mut position = Point2::origin();
+    let mut draw_length = 0.0;
+    let mut travel_length = 0.0;
+
+    for polyline in polylines {
+        travel_length += distance(&position, polyline.start());
+        draw_length += polyline.length();
+        position = *polyline.end();
+    }
+
+    PlotStatistics {
+        draw_length,
+        travel_length,
+    }
+}
+
+fn nearest_neighbour_order(mut remaining: Vec<Polyline>, start: &Point2<f32>) -> Vec<Polyline> {
+    let mut ordered = Vec::with_capacity(remaining.len());
+    let mut position = *start;
+
+    while !remaining.is_empty() {
+        let mut best_index = 0;
+        let mut best_distance = f32::INFINITY;
+        // the vertex to enter through; 0 for the start, or the last vertex to enter reversed
+        let mut best_entry = 0;
+
+        for (index, polyline) in remaining.iter().enumerate() {
+            let candidates: Vec<usize> = if polyline.is_closed() {
+                // closed loops can be entered at any vertex
+                (0..polyline.points.len() - 1).collect()
+            } else {
+                vec![0, polyline.points.len() - 1]
+            };
+
+            for entry in candidates {
+                let entry_distance = distance(&position, &polyline.points[entry]);
+                if entry_distance < best_distance {
+                    best_distance = entry_distance;
+                    best_index = index;
+                    best_entry = entry;
+                }
+            }
+        }
+
+        let mut polyline = remaining.swap_remove(best_index);
+
+        if polyline.is_closed() {
+            rotate_closed(&mut polyline, best_entry);
+        } else if best_entry != 0 {
+            polyline.reverse();
+        }
+
+        position = *polyline.end();
+        ordered.push(polyline);
+    }
+
+    ordered
+}
+
+fn rotate_closed(polyline: &mut Polyline, entry: usize) {
+    if entry == 0 {
+        return;
+    }
+
+    // drop the duplicated closing vertex, rotate, then close the loop again
+    polyline.points.pop();
+    polyline.points.rotate_left(entry);
+    let start = polyline.points[0];
+    polyline.points.push(start);
+
+    polyline.mesh_space_points.pop();
+    polyline.mesh_space_points.rotate_left(entry);
+    let mesh_space_start = polyline.mesh_space_points[0];
+    polyline.mesh_space_points.push(mesh_space_start);
+}
+
+/// Improves an existing order by reversing runs of paths whenever doing so shortens the travel
+/// into and out of the run
+fn two_opt(order: &mut [Polyline], start: &Point2<f32>) {
+    let count = order.len();
+
+    for _ in 0..MAX_TWO_OPT_PASSES {
+        let mut improved = false;
+
+        for i in 0..count {
+            for j in i..count {
+                let before = match i {
+                    0 => *start,
+                    _ => *order[i - 1].end(),
+                };
+
+                let current = distance(&before, order[i].start())
+                    + match order.get(j + 1) {
+                        Some(next) => distance(order[j].end(), next.start()),
+                        None => 0.0,
+                    };
+
+                let reversed = distance(&before, order[j].end())
+                    + match order.get(j + 1) {
+                        Some(next) => distance(order[i].start(), next.start()),
+                        None => 0.0,
+                    };
+
+                if reversed + f32::EPSILON < current {
+                    order[i..=j].reverse();
+                    for polyline in &mut order[i..=j] {
+                        polyline.reverse();
+                    }
+                    improved = true;
+                }
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineVisibility};
+
+    fn polyline(points: &[(f32, f32)], kind: EdgeKind) -> Polyline {
+        Polyline {
+            points: points.iter().map(|&(x, y)| Point2::new(x, y)).collect(),
+            mesh_space_points: points
+                .iter()
+                .map(|&(x, y)| Point3::new(x, y, 1.0))
+                .collect(),
+            visibility: LineVisibility::VISIBLE,
+            kind,
+            object_id: 0,
+        }
+    }
+
+    fn ends(polyline: &Polyline) -> (Point2<f32>, Point2<f32>) {
+        (*polyline.start(), *polyline.end())
+    }
+
+    // short strokes along a line, given out of order and alternately reversed
+    fn scattered_strokes() -> Vec<Polyline> {
+        [5, 0, 8, 3, 9, 1, 6, 2, 7, 4]
+            .iter()
+            .map(|&i| {
+                let x = i as f32 * 10.0;
+                match i % 2 {
+                    0 => polyline(&[(x, 0.0), (x + 5.0, 0.0)], EdgeKind::SILHOUETTE),
+                    _ => polyline(&[(x + 5.0, 0.0), (x, 0.0)], EdgeKind::SILHOUETTE),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shortens_travel() {
+        let strokes = scattered_strokes();
+        let before = plot_statistics(&strokes);
+
+        let ordered = optimise_path_order(strokes);
+        let after = plot_statistics(&ordered);
+
+        assert_eq!(ordered.len(), 10);
+        assert_relative_eq!(after.draw_length, before.draw_length);
+        assert!(after.travel_length < before.travel_length);
+        // from the origin along the strokes, with only the gaps between them left to travel
+        assert_relative_eq!(after.travel_length, 45.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn reversed_paths_keep_their_ends() {
+        let strokes = scattered_strokes();
+
+        let ordered = optimise_path_order(strokes.clone());
+
+        for path in &ordered {
+            let original = strokes
+                .iter()
+                .find(|stroke| {
+                    let (start, end) = ends(stroke);
+                    (start, end) == ends(path) || (end, start) == ends(path)
+                })
+                .expect("every path comes from a stroke");
+
+            if ends(original) != ends(path) {
+                let mut reversed = original.clone();
+                reversed.reverse();
+                assert_eq!(reversed.points, path.points);
+                assert_eq!(reversed.mesh_space_points, path.mesh_space_points);
+            }
+        }
+    }
+
+    #[test]
+    fn enters_closed_loops_at_their_nearest_point() {
+        let square = polyline(
+            &[
+                (10.0, 10.0),
+                (20.0, 10.0),
+                (20.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 10.0),
+            ],
+            EdgeKind::CREASE,
+        );
+
+        let ordered = optimise_path_order(vec![square.clone()]);
+
+        assert!(ordered[0].is_closed());
+        assert_eq!(*ordered[0].start(), Point2::new(10.0, 0.0));
+        assert_relative_eq!(ordered[0].length(), square.length());
+        assert_eq!(ordered[0].mesh_space_points[0], Point3::new(10.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn keeps_classes_together() {
+        let paths = vec![
+            polyline(&[(0.0, 0.0), (1.0, 0.0)], EdgeKind::SILHOUETTE),
+            polyline(&[(1.0, 0.0), (2.0, 0.0)], EdgeKind::CREASE),
+            polyline(&[(2.0, 0.0), (3.0, 0.0)], EdgeKind::SILHOUETTE),
+        ];
+
+        let kinds: Vec<EdgeKind> = optimise_path_order(paths)
+            .iter()
+            .map(|path| path.kind)
+            .collect();
+
+        assert!(kinds == vec![EdgeKind::SILHOUETTE, EdgeKind::SILHOUETTE, EdgeKind::CREASE]);
+    }
+
+    #[test]
+    fn two_opt_uncrosses_travel() {
+        // the second and third paths are visited in the wrong order and the wrong way around
+        let mut order = vec![
+            polyline(&[(0.0, 0.0), (1.0, 0.0)], EdgeKind::SILHOUETTE),
+            polyline(&[(4.0, 0.0), (3.0, 0.0)], EdgeKind::SILHOUETTE),
+            polyline(&[(2.0, 0.0), (1.5, 0.0)], EdgeKind::SILHOUETTE),
+        ];
+        let before = plot_statistics(&order).travel_length;
+
+        two_opt(&mut order, &Point2::origin());
+
+        assert!(plot_statistics(&order).travel_length < before);
+        assert_eq!(
+            ends(&order[1]),
+            (Point2::new(1.5, 0.0), Point2::new(2.0, 0.0))
+        );
+        assert_eq!(
+            ends(&order[2]),
+            (Point2::new(3.0, 0.0), Point2::new(4.0, 0.0))
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use crate::colour::Colour;
+use crate::error::MeshToSvgError;
+use crate::lines::{LineSegmentCategorized, LineVisibility};
+use crate::paper::{DrawingScale, Page, PageLayout, POINTS_PER_MM};
+use crate::polyline::{chain_line_segments, Polyline};
+use crate::scene::Scene;
+
+pub struct PdfLineConfig {
+    pub stroke_width_mm: f32,
+    /// any css colour, as for svg strokes
+    pub stroke: String,
+    /// alternating dash and gap lengths in mm, solid when empty
+    pub dash_array_mm: Vec<f32>,
+}
+
+pub struct PdfConfig {
+    pub page: Page,
+    pub scale: DrawingScale,
+    /// real world length of one world space unit, used for exact scales
+    pub model_unit_mm: f32,
+    pub visible: PdfLineConfig,
+    pub obscured: Option<PdfLineConfig>,
+}
+
+impl PdfConfig {
+    pub fn new_default() -> PdfConfig {
+        PdfConfig {
+            page: Page::new_default(),
+            scale: DrawingScale::Fit,
+            model_unit_mm: 1.0,
+            visible: PdfLineConfig {
+                stroke_width_mm: 0.5,
+                stroke: "black".to_owned(),
+                dash_array_mm: vec![],
+            },
+            // ISO 128 dashed line for hidden outlines, 12d dashes with 3d gaps
+            obscured: Some(PdfLineConfig {
+                stroke_width_mm: 0.25,
+                stroke: "black".to_owned(),
+                dash_array_mm: vec![3.0, 0.75],
+            }),
+        }
+    }
+}
+
+/// Writes a single page vector pdf of the lines laid out according to the config
+pub fn screen_space_lines_to_pdf(
+    screen_space_lines: &[LineSegmentCategorized],
+    scene: &Scene,
+    config: &PdfConfig,
+) -> Result<Vec<u8>, MeshToSvgError> {
+    let layout = PageLayout::new(
+        screen_space_lines,
+        scene,
+        &config.page,
+        config.scale,
+        config.model_unit_mm,
+    )?;
+    let (page_width, page_height) = config.page.dimensions_mm();
+
+    let (visible, obscured): (Vec<Polyline>, Vec<Polyline>) =
+        chain_line_segments(screen_space_lines)
+            .into_iter()
+            .partition(|polyline| polyline.visibility == LineVisibility::VISIBLE);
+
+    let mut content = "1 J 1 j\n".to_owned();
+
+    if let Some(obscured_config) = &config.obscured {
+        content.push_str(&stroke_polylines(
+            &obscured,
+            obscured_config,
+            &layout,
+            page_height,
+        ));
+    }
+    content.push_str(&stroke_polylines(
+        &visible,
+        &config.visible,
+        &layout,
+        page_height,
+    ));
+
+    let objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_owned(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {width:.2} {height:.2}] /Contents 4 0 R /Resources << >> >>",
+            width = page_width * POINTS_PER_MM,
+            height = page_height * POINTS_PER_MM,
+        ),
+        format!(
+            "<< /Length {length} >>\nstream\n{content}endstream",
+            length = content.len(),
+            content = content,
+        ),
+    ];
+
+    Ok(write_document(&objects))
+}
+
+fn stroke_polylines(
+    polylines: &[Polyline],
+    line_config: &PdfLineConfig,
+    layout: &PageLayout,
+    page_height: f32,
+) -> String {
+    if polylines.is_empty() {
+        return "".to_owned();
+    }
+
+    let (r, g, b) = Colour::parse(&line_config.stroke)
+        .unwrap_or(Colour::BLACK)
+        .to_unit_rgb();
+
+    let dashes: Vec<String> = line_config
+        .dash_array_mm
+        .iter()
+        .map(|dash| format!("{:.2}", dash * POINTS_PER_MM))
+        .collect();
+
+    let mut content = format!(
+        "q\n{r:.3} {g:.3} {b:.3} RG\n{width:.3} w\n[{dashes}] 0 d\n",
+        r = r,
+        g = g,
+        b = b,
+        width = line_config.stroke_width_mm * POINTS_PER_MM,
+        dashes = dashes.join(" "),
+    );
+
+    for polyline in polylines {
+        for (i, point) in polyline.points.iter().enumerate() {
+            let on_page = layout.to_page(point);
+
+            // pdf user space is in points with the origin at the bottom left
+            content.push_str(
+                format!(
+                    "{x:.2} {y:.2} {operator}\n",
+                    x = on_page.x * POINTS_PER_MM,
+                    y = (page_height - on_page.y) * POINTS_PER_MM,
+                    operator = if i == 0 { "m" } else { "l" },
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    // a single stroke for the whole path lets dashes restart at each subpath
+    content.push_str("S\nQ\n");
+
+    content
+}
+
+fn write_document(objects: &[String]) -> Vec<u8> {
+    let mut document = "%PDF-1.4\n".to_owned();
+    let mut offsets = Vec::with_capacity(objects.len());
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(document.len());
+        document.push_str(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_str());
+    }
+
+    let xref_offset = document.len();
+
+    document.push_str(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_str());
+    for offset in offsets {
+        document.push_str(format!("{:010} 00000 n \n", offset).as_str());
+    }
+
+    document.push_str(
+        format!(
+            "trailer\n<< /Size {size} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            size = objects.len() + 1,
+            xref_offset = xref_offset,
+        )
+        .as_str(),
+    );
+
+    document.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use na::{Point2, Point3};
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineSegment2, LineSegment3};
+
+    fn segment(
+        from: (f32, f32),
+        to: (f32, f32),
+        visibility: LineVisibility,
+    ) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    fn drawing() -> Vec<LineSegmentCategorized> {
+        vec![
+            segment((0.0, 0.0), (100.0, 0.0), LineVisibility::VISIBLE),
+            segment((0.0, 0.0), (50.0, 0.0), LineVisibility::OBSCURED),
+        ]
+    }
+
+    fn render(config: &PdfConfig) -> String {
+        let pdf = screen_space_lines_to_pdf(&drawing(), &Scene::new_test(), config).unwrap();
+
+        String::from_utf8(pdf).unwrap()
+    }
+
+    #[test]
+    fn writes_a_cross_referenced_document() {
+        let pdf = render(&PdfConfig::new_default());
+
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("/MediaBox [0 0 841.89 595.28]"));
+
+        let xref = pdf.find("xref\n").unwrap();
+        let startxref: usize = pdf
+            .split("startxref\n")
+            .nth(1)
+            .and_then(|rest| rest.lines().next())
+            .and_then(|offset| offset.parse().ok())
+            .unwrap();
+        assert_eq!(startxref, xref);
+
+        let offsets: Vec<usize> = pdf[xref..]
+            .lines()
+            .filter(|line| line.ends_with(" 00000 n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        assert_eq!(offsets.len(), 4);
+        for (i, offset) in offsets.iter().enumerate() {
+            assert!(pdf[*offset..].starts_with(&format!("{} 0 obj\n", i + 1)));
+        }
+
+        let content = pdf.find(">>\nstream\n").unwrap() + ">>\nstream\n".len();
+        let length = format!("/Length {} >>", pdf.find("endstream").unwrap() - content);
+        assert!(pdf.contains(&length));
+    }
+
+    #[test]
+    fn strokes_on_the_page_in_points() {
+        let mut config = PdfConfig::new_default();
+        config.visible.stroke = "red".to_owned();
+        let pdf = render(&config);
+
+        // obscured lines are stroked first, dashed, so visible lines cover them
+        let obscured = pdf.find("0.709 w\n[8.50 2.13] 0 d").unwrap();
+        let visible = pdf.find("1.000 0.000 0.000 RG\n1.417 w\n[] 0 d").unwrap();
+        assert!(obscured < visible);
+
+        // centred within the margins, with y measured up from the bottom of the page
+        assert!(pdf[visible..].contains("28.35 297.64 m\n813.54 297.64 l\nS\nQ\n"));
+    }
+
+    #[test]
+    fn leaves_out_obscured_lines() {
+        let config = PdfConfig {
+            obscured: None,
+            ..PdfConfig::new_default()
+        };
+
+        let pdf = render(&config);
+
+        assert_eq!(pdf.matches(" RG\n").count(), 1);
+        assert!(!pdf.contains("[8.50 2.13] 0 d"));
+    }
+}
@@ -0,0 +1,434 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use na::{distance, Point2, Point3};
+
+use crate::lines::{EdgeKind, LineSegment2, LineSegment3, LineSegmentCategorized, LineVisibility};
+
+/// Endpoints closer than this (in screen space units) are considered to be the same vertex when
+/// chaining
+pub const WELD_EPSILON: f32 = 0.001;
+
+#[derive(Clone)]
+pub struct Polyline {
+    pub points: Vec<Point2<f32>>,
+    /// the mesh space position of each point. Where lines cross in screen space these come from
+    /// the segment the chain continued along
+    pub mesh_space_points: Vec<Point3<f32>>,
+    pub visibility: LineVisibility,
+    pub kind: EdgeKind,
+    pub object_id: usize,
+}
+
+impl Polyline {
+    pub fn start(&self) -> &Point2<f32> {
+        &self.points[0]
+    }
+
+    pub fn end(&self) -> &Point2<f32> {
+        &self.points[self.points.len() - 1]
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.points.len() > 2 && relative_eq!(self.start(), self.end())
+    }
+
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|pair| distance(&pair[0], &pair[1]))
+            .sum()
+    }
+
+    pub fn reverse(&mut self) {
+        self.points.reverse();
+        self.mesh_space_points.reverse();
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = LineSegmentCategorized> + '_ {
+        self.points
+            .windows(2)
+            .zip(self.mesh_space_points.windows(2))
+            .map(move |(pair, mesh_space_pair)| LineSegmentCategorized {
+                line_segment: LineSegment2 {
+                    from: pair[0],
+                    to: pair[1],
+                },
+                mesh_space: LineSegment3 {
+                    from: mesh_space_pair[0],
+                    to: mesh_space_pair[1],
+                },
+                visibility: self.visibility,
+                kind: self.kind,
+                object_id: self.object_id,
+            })
+    }
+}
+
+/// Links segments that share endpoints (in either orientation) into maximal polylines. Chains only
+/// ever join segments of the same visibility, edge kind and object, and every input segment is
+/// emitted exactly once, apart from segments that weld down to a single point and repeats of a
+/// segment between the same two points, which are dropped.
+pub fn chain_line_segments(segments: &[LineSegmentCategorized]) -> Vec<Polyline> {
+    let mut polylines = Vec::new();
+
+    let classes = ordered_line_classes(segments.iter().map(|s| (s.visibility, s.kind)));
+
+    for (visibility, kind) in classes {
+        let class: Vec<&LineSegmentCategorized> = segments
+            .iter()
+            .filter(|segment| segment.visibility == visibility && segment.kind == kind)
+            .collect();
+
+        let mut object_ids = Vec::new();
+        for segment in &class {
+            if !object_ids.contains(&segment.object_id) {
+                object_ids.push(segment.object_id);
+            }
+        }
+
+        for object_id in object_ids {
+            let group: Vec<&LineSegmentCategorized> = class
+                .iter()
+                .filter(|segment| segment.object_id == object_id)
+                .cloned()
+                .collect();
+
+            polylines.extend(
+                chain_group(&group)
+                    .into_iter()
+                    .map(|(points, mesh_space_points)| Polyline {
+                        points,
+                        mesh_space_points,
+                        visibility,
+                        kind,
+                        object_id,
+                    }),
+            );
+        }
+    }
+
+    polylines
+}
+
+/// The distinct visibility and edge kind combinations in order of appearance, visible lines first
+pub fn ordered_line_classes<I>(classes: I) -> Vec<(LineVisibility, EdgeKind)>
+where
+    I: Iterator<Item = (LineVisibility, EdgeKind)>,
+{
+    let mut distinct = Vec::new();
+
+    for class in classes {
+        if !distinct.contains(&class) {
+            distinct.push(class);
+        }
+    }
+
+    // stable sort, so kinds retain their order of appearance
+    distinct.sort_by_key(|&(visibility, _)| visibility as u8);
+
+    distinct
+}
+
+// screen space points alongside their mesh space positions
+type Chain = (Vec<Point2<f32>>, Vec<Point3<f32>>);
+
+struct SegmentGraph {
+    vertices: Vec<Point2<f32>>,
+    grid: HashMap<(i64, i64), Vec<usize>>,
+    // each edge is stored as (vertex_a, vertex_b)
+    edges: Vec<(usize, usize)>,
+    // the mesh space line of each edge, running from vertex_a to vertex_b
+    edge_mesh_space: Vec<LineSegment3>,
+    incident: Vec<Vec<usize>>,
+}
+
+impl SegmentGraph {
+    fn new(capacity: usize) -> SegmentGraph {
+        SegmentGraph {
+            vertices: Vec::with_capacity(capacity),
+            grid: HashMap::with_capacity(capacity),
+            edges: Vec::with_capacity(capacity),
+            edge_mesh_space: Vec::with_capacity(capacity),
+            incident: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn cell(point: &Point2<f32>) -> (i64, i64) {
+        (
+            (point.x / WELD_EPSILON).floor() as i64,
+            (point.y / WELD_EPSILON).floor() as i64,
+        )
+    }
+
+    fn find_or_insert_vertex(&mut self, point: &Point2<f32>) -> usize {
+        let (cx, cy) = SegmentGraph::cell(point);
+
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                if let Some(candidates) = self.grid.get(&(x, y)) {
+                    for &candidate in candidates {
+                        if distance(&self.vertices[candidate], point) <= WELD_EPSILON {
+                            return candidate;
+                        }
+                    }
+                }
+            }
+        }
+
+        let index = self.vertices.len();
+        self.vertices.push(*point);
+        self.incident.push(Vec::with_capacity(2));
+        self.grid.entry((cx, cy)).or_default().push(index);
+
+        index
+    }
+
+    fn add_edge(&mut self, segment: &LineSegmentCategorized) {
+        let a = self.find_or_insert_vertex(&segment.line_segment.from);
+        let b = self.find_or_insert_vertex(&segment.line_segment.to);
+
+        // segments that collapse to a single vertex contribute nothing to the drawing
+        if a == b {
+            return;
+        }
+
+        // overlapping duplicates would otherwise turn both ends into junctions and break the chain
+        if self.incident[a]
+            .iter()
+            .any(|&edge| self.other_vertex(edge, a) == b)
+        {
+            return;
+        }
+
+        let edge_index = self.edges.len();
+        self.edges.push((a, b));
+        self.edge_mesh_space.push(segment.mesh_space);
+        self.incident[a].push(edge_index);
+        self.incident[b].push(edge_index);
+    }
+
+    fn other_vertex(&self, edge: usize, vertex: usize) -> usize {
+        let (a, b) = self.edges[edge];
+        if a == vertex {
+            b
+        } else {
+            a
+        }
+    }
+
+    fn walk(&self, start_vertex: usize, start_edge: usize, visited: &mut [bool]) -> Chain {
+        let mut points = vec![self.vertices[start_vertex]];
+        let mut mesh_space_points = vec![self.mesh_space_point_at(start_edge, start_vertex)];
+        let mut vertex = start_vertex;
+        let mut edge = start_edge;
+
+        loop {
+            visited[edge] = true;
+            let previous = vertex;
+            vertex = self.other_vertex(edge, vertex);
+            points.push(self.vertices[vertex]);
+            mesh_space_points.push(self.mesh_space_point_at(edge, vertex));
+
+            // at junctions carry on along the straightest continuation so that crossing lines
+            // stay continuous
+            let heading = (self.vertices[vertex] - self.vertices[previous]).normalize();
+            let next = self.incident[vertex]
+                .iter()
+                .filter(|&&e| !visited[e])
+                .map(|&e| {
+                    let direction = (self.vertices[self.other_vertex(e, vertex)]
+                        - self.vertices[vertex])
+                        .normalize();
+                    (e, heading.dot(&direction))
+                })
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+            match next {
+                Some((next, _)) => edge = next,
+                None => break,
+            }
+        }
+
+        (points, mesh_space_points)
+    }
+
+    /// The mesh space position of the edge's end at the given vertex
+    fn mesh_space_point_at(&self, edge: usize, vertex: usize) -> Point3<f32> {
+        let line = &self.edge_mesh_space[edge];
+        if self.edges[edge].0 == vertex {
+            line.from
+        } else {
+            line.to
+        }
+    }
+}
+
+fn chain_group(segments: &[&LineSegmentCategorized]) -> Vec<Chain> {
+    let mut graph = SegmentGraph::new(segments.len() * 2);
+
+    for segment in segments {
+        graph.add_edge(segment);
+    }
+
+    let mut visited = vec![false; graph.edges.len()];
+    let mut chains = Vec::new();
+
+    // a vertex with an odd number of segments must be the end of at least one chain, so starting
+    // there first keeps the number of chains low
+    let odd_vertices = (0..graph.vertices.len()).filter(|&v| graph.incident[v].len() % 2 == 1);
+
+    for vertex in odd_vertices {
+        while let Some(&edge) = graph.incident[vertex].iter().find(|&&e| !visited[e]) {
+            chains.push(graph.walk(vertex, edge, &mut visited));
+        }
+    }
+
+    // whatever is left over is made up of closed loops
+    for edge in 0..graph.edges.len() {
+        if !visited[edge] {
+            let (start, _) = graph.edges[edge];
+            chains.push(graph.walk(start, edge, &mut visited));
+        }
+    }
+
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(from: (f32, f32), to: (f32, f32), kind: EdgeKind) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility: LineVisibility::VISIBLE,
+            kind,
+            object_id: 0,
+        }
+    }
+
+    fn segment_count(polylines: &[Polyline]) -> usize {
+        polylines
+            .iter()
+            .map(|polyline| polyline.points.len() - 1)
+            .sum()
+    }
+
+    #[test]
+    fn chains_segments_either_way_around() {
+        let segments = vec![
+            segment((0.0, 0.0), (1.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((2.0, 0.0), (1.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((2.0, 0.0), (2.0, 1.0), EdgeKind::SILHOUETTE),
+        ];
+
+        let polylines = chain_line_segments(&segments);
+
+        assert_eq!(polylines.len(), 1);
+        assert_eq!(polylines[0].points.len(), 4);
+        assert_relative_eq!(polylines[0].length(), 3.0);
+        assert_eq!(
+            polylines[0].points.len(),
+            polylines[0].mesh_space_points.len()
+        );
+    }
+
+    #[test]
+    fn welds_nearby_ends() {
+        let segments = vec![
+            segment((0.0, 0.0), (1.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((1.0, WELD_EPSILON / 2.0), (2.0, 0.0), EdgeKind::SILHOUETTE),
+        ];
+
+        assert_eq!(chain_line_segments(&segments).len(), 1);
+    }
+
+    #[test]
+    fn closes_loops() {
+        let segments = vec![
+            segment((0.0, 0.0), (1.0, 0.0), EdgeKind::CREASE),
+            segment((1.0, 0.0), (1.0, 1.0), EdgeKind::CREASE),
+            segment((1.0, 1.0), (0.0, 0.0), EdgeKind::CREASE),
+        ];
+
+        let polylines = chain_line_segments(&segments);
+
+        assert_eq!(polylines.len(), 1);
+        assert!(polylines[0].is_closed());
+    }
+
+    #[test]
+    fn keeps_classes_apart() {
+        let mut obscured = segment((1.0, 0.0), (2.0, 0.0), EdgeKind::SILHOUETTE);
+        obscured.visibility = LineVisibility::OBSCURED;
+        let mut other_object = segment((2.0, 0.0), (3.0, 0.0), EdgeKind::SILHOUETTE);
+        other_object.object_id = 1;
+
+        let segments = vec![
+            obscured,
+            segment((0.0, 0.0), (1.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((0.0, 0.0), (0.0, 1.0), EdgeKind::CREASE),
+            other_object,
+        ];
+
+        let polylines = chain_line_segments(&segments);
+
+        assert_eq!(polylines.len(), 4);
+        // visible lines come first
+        assert!(polylines[0].visibility == LineVisibility::VISIBLE);
+        assert!(polylines[3].visibility == LineVisibility::OBSCURED);
+    }
+
+    #[test]
+    fn emits_every_segment_once_at_branches() {
+        let segments = vec![
+            segment((0.0, 0.0), (1.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((1.0, 0.0), (2.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((1.0, 0.0), (1.0, 1.0), EdgeKind::SILHOUETTE),
+            segment((1.0, 0.0), (1.0, -1.0), EdgeKind::SILHOUETTE),
+        ];
+
+        let polylines = chain_line_segments(&segments);
+
+        assert_eq!(segment_count(&polylines), 4);
+        assert!(polylines.len() >= 2);
+    }
+
+    #[test]
+    fn drops_points_and_repeats() {
+        let segments = vec![
+            segment((0.0, 0.0), (1.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((1.0, 0.0), (0.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((1.0, 0.0), (1.0, WELD_EPSILON / 2.0), EdgeKind::SILHOUETTE),
+        ];
+
+        let polylines = chain_line_segments(&segments);
+
+        assert_eq!(polylines.len(), 1);
+        assert_eq!(segment_count(&polylines), 1);
+    }
+
+    #[test]
+    fn splits_back_into_segments() {
+        let segments = vec![
+            segment((0.0, 0.0), (1.0, 0.0), EdgeKind::SILHOUETTE),
+            segment((1.0, 0.0), (1.0, 1.0), EdgeKind::SILHOUETTE),
+        ];
+
+        let mut polyline = chain_line_segments(&segments).remove(0);
+        polyline.reverse();
+        let split: Vec<LineSegmentCategorized> = polyline.segments().collect();
+
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].line_segment.to, split[1].line_segment.from);
+        assert_eq!(split[0].mesh_space.to, split[1].mesh_space.from);
+    }
+}
@@ -0,0 +1,480 @@
+use na::{distance, Point2, Vector2};
+
+use crate::colour::Colour;
+use crate::error::MeshToSvgError;
+use crate::lines::{EdgeKind, LineSegmentCategorized, LineVisibility};
+use crate::polyline::{chain_line_segments, Polyline};
+use crate::svg_renderer::{scale_screen_space_lines, LineCap, SvgConfig, SvgLineConfig};
+
+// svg user units are css pixels, which are defined at 96 per inch
+const SVG_DPI: f32 = 96.0;
+
+pub struct RasterConfig {
+    pub dpi: f32,
+    /// any css colour, use `transparent` for no background
+    pub background: String,
+}
+
+impl RasterConfig {
+    pub fn new_default() -> RasterConfig {
+        RasterConfig {
+            dpi: SVG_DPI,
+            background: "white".to_owned(),
+        }
+    }
+}
+
+/// Non-premultiplied 8 bit RGBA pixels, row by row from the top left
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Fails when the image has more bytes than can be addressed
+    fn new(width: u32, height: u32, background: Colour) -> Result<RgbaImage, MeshToSvgError> {
+        let pixel_count = (width as usize)
+            .checked_mul(height as usize)
+            .filter(|pixel_count| pixel_count.checked_mul(4).is_some())
+            .ok_or_else(|| MeshToSvgError::Encoding {
+                output: "png",
+                message: format!("a {} by {} pixel image is too large", width, height),
+            })?;
+
+        Ok(RgbaImage {
+            width,
+            height,
+            pixels: [background.r, background.g, background.b, background.a].repeat(pixel_count),
+        })
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        pixel_index(x, y, self.width)
+    }
+
+    /// Source-over composite of a colour with the given coverage
+    fn blend(&mut self, x: u32, y: u32, colour: Colour, coverage: f32) {
+        let index = self.index(x, y) * 4;
+        let source_alpha = colour.a as f32 / 255.0 * coverage;
+        let destination_alpha = self.pixels[index + 3] as f32 / 255.0;
+        let out_alpha = source_alpha + destination_alpha * (1.0 - source_alpha);
+
+        if out_alpha <= 0.0 {
+            return;
+        }
+
+        for (channel, &source) in [colour.r, colour.g, colour.b].iter().enumerate() {
+            let blended = (source as f32 * source_alpha
+                + self.pixels[index + channel] as f32 * destination_alpha * (1.0 - source_alpha))
+                / out_alpha;
+
+            self.pixels[index + channel] = blended.round() as u8;
+        }
+
+        self.pixels[index + 3] = (out_alpha * 255.0).round() as u8;
+    }
+}
+
+/// Renders the lines fitted to the svg config, at the size the svg would be displayed at the
+/// configured dpi. Stroke widths and colours are taken from the svg line configs
+pub fn screen_space_lines_to_raster(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+    raster_config: &RasterConfig,
+) -> Result<RgbaImage, MeshToSvgError> {
+    let pixel_ratio = raster_config.dpi / SVG_DPI;
+    let scaled = scale_screen_space_lines(screen_space_lines, svg_config);
+
+    let mut image = RgbaImage::new(
+        (svg_config.width as f32 * pixel_ratio).ceil() as u32,
+        (svg_config.height as f32 * pixel_ratio).ceil() as u32,
+        Colour::parse(&raster_config.background).unwrap_or(Colour::WHITE),
+    )?;
+
+    let (visible, obscured): (Vec<Polyline>, Vec<Polyline>) = chain_line_segments(&scaled)
+        .into_iter()
+        .partition(|polyline| polyline.visibility == LineVisibility::VISIBLE);
+    let (hatch, visible): (Vec<Polyline>, Vec<Polyline>) = visible
+        .into_iter()
+        .partition(|polyline| polyline.kind == EdgeKind::HATCH);
+
+    // painted in the same order as the svg paths, so visible lines sit on top
+    if let Some(obscured_config) = &svg_config.obscured {
+        stroke_polylines(&mut image, &obscured, obscured_config, pixel_ratio);
+    }
+    stroke_polylines(&mut image, &hatch, &svg_config.hatch, pixel_ratio);
+    stroke_polylines(&mut image, &visible, &svg_config.visible, pixel_ratio);
+
+    Ok(image)
+}
+
+pub fn screen_space_lines_to_png(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+    raster_config: &RasterConfig,
+) -> Result<Vec<u8>, MeshToSvgError> {
+    encode_png(&screen_space_lines_to_raster(
+        screen_space_lines,
+        svg_config,
+        raster_config,
+    )?)
+}
+
+/// Fails for an image the png format can't hold, e.g. one with a width or height of 0
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, MeshToSvgError> {
+    let mut buffer = Vec::new();
+
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, image.width, image.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&image.pixels))
+            .map_err(|error| MeshToSvgError::Encoding {
+                output: "png",
+                message: error.to_string(),
+            })?;
+    }
+
+    Ok(buffer)
+}
+
+/// Strokes all polylines as a single path: coverage is accumulated in a mask first so that
+/// overlapping segments and joins don't darken translucent strokes. Joins are always round
+fn stroke_polylines(
+    image: &mut RgbaImage,
+    polylines: &[Polyline],
+    line_config: &SvgLineConfig,
+    pixel_ratio: f32,
+) {
+    let colour = Colour::parse(&line_config.stroke).unwrap_or(Colour::BLACK);
+    let half_width = line_config.stroke_width * pixel_ratio / 2.0;
+
+    let mut coverage = vec![0.0f32; image.pixels.len() / 4];
+
+    for polyline in polylines {
+        let points: Vec<Point2<f32>> = polyline
+            .points
+            .iter()
+            .map(|point| point * pixel_ratio)
+            .collect();
+
+        for dash in dash_runs(&points, line_config, pixel_ratio) {
+            let last = dash.len() - 2;
+
+            for (i, pair) in dash.windows(2).enumerate() {
+                // only the ends of a dash are capped, in between segments meet with round joins
+                let cap_at = |end: usize| match i == end {
+                    true => line_config.linecap,
+                    false => LineCap::Round,
+                };
+
+                rasterize_segment(
+                    &mut coverage,
+                    image.width,
+                    image.height,
+                    &pair[0],
+                    &pair[1],
+                    half_width,
+                    (cap_at(0), cap_at(last)),
+                );
+            }
+        }
+    }
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let pixel_coverage = coverage[image.index(x, y)];
+            if pixel_coverage > 0.0 {
+                image.blend(x, y, colour, pixel_coverage * line_config.opacity);
+            }
+        }
+    }
+}
+
+/// Splits a polyline into the runs of points drawn by the dash pattern, which starts afresh for
+/// every polyline as it does for each svg subpath
+fn dash_runs(
+    points: &[Point2<f32>],
+    line_config: &SvgLineConfig,
+    pixel_ratio: f32,
+) -> Vec<Vec<Point2<f32>>> {
+    // as in svg, an odd number of values is repeated to make an even number
+    let mut pattern: Vec<f32> = line_config
+        .dash_array
+        .iter()
+        .map(|dash| dash.max(0.0) * pixel_ratio)
+        .collect();
+    if pattern.len() % 2 == 1 {
+        pattern.extend(pattern.clone());
+    }
+
+    let pattern_length: f32 = pattern.iter().sum();
+
+    if pattern_length <= 0.0 {
+        return vec![points.to_vec()];
+    }
+
+    // find where in the pattern the offset starts
+    let mut index = 0;
+    let mut remaining = pattern[0];
+    let mut offset = (line_config.dash_offset * pixel_ratio).rem_euclid(pattern_length);
+    while offset > 0.0 {
+        if offset < remaining {
+            remaining -= offset;
+            offset = 0.0;
+        } else {
+            offset -= remaining;
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+    }
+
+    let mut runs = Vec::new();
+    let mut current = vec![points[0]];
+
+    for pair in points.windows(2) {
+        let segment_length = distance(&pair[0], &pair[1]);
+        let mut travelled = 0.0;
+
+        while segment_length - travelled > remaining {
+            travelled += remaining;
+            let point = pair[0] + (pair[1] - pair[0]) * (travelled / segment_length);
+
+            // even indices are dashes, odd ones gaps
+            if index % 2 == 0 {
+                current.push(point);
+                runs.push(current);
+                current = vec![];
+            } else {
+                current = vec![point];
+            }
+
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+
+        remaining -= segment_length - travelled;
+
+        if index % 2 == 0 {
+            current.push(pair[1]);
+        }
+    }
+
+    if index % 2 == 0 {
+        runs.push(current);
+    }
+
+    runs.retain(|run| run.len() > 1);
+    runs
+}
+
+// in usize, as the row offset can overflow a u32 for images that fit in memory
+fn pixel_index(x: u32, y: u32, width: u32) -> usize {
+    y as usize * width as usize + x as usize
+}
+
+/// Anti-aliased by the distance from each pixel centre to the segment, with the given caps
+/// beyond each end
+fn rasterize_segment(
+    coverage: &mut [f32],
+    width: u32,
+    height: u32,
+    from: &Point2<f32>,
+    to: &Point2<f32>,
+    half_width: f32,
+    (start_cap, end_cap): (LineCap, LineCap),
+) {
+    let reach = half_width * std::f32::consts::SQRT_2 + 1.0;
+
+    let min_x = (from.x.min(to.x) - reach).floor().max(0.0) as u32;
+    let min_y = (from.y.min(to.y) - reach).floor().max(0.0) as u32;
+    let max_x = ((from.x.max(to.x) + reach).ceil().max(0.0) as u32).min(width);
+    let max_y = ((from.y.max(to.y) + reach).ceil().max(0.0) as u32).min(height);
+
+    let length = distance(from, to);
+    let direction: Vector2<f32> = match length {
+        l if l > 0.0 => (to - from) / l,
+        _ => Vector2::new(1.0, 0.0),
+    };
+
+    // a one pixel wide ramp centred on the edge of the stroke
+    let ramp = |distance: f32| (half_width + 0.5 - distance).clamp(0.0, 1.0);
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let centre = Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+            let along = (centre - from).dot(&direction);
+            let across = (centre - (from + direction * along)).norm();
+
+            let (overshoot, cap) = if along < 0.0 {
+                (-along, start_cap)
+            } else {
+                ((along - length).max(0.0), end_cap)
+            };
+
+            let pixel_coverage = match cap {
+                _ if overshoot <= 0.0 => ramp(across),
+                LineCap::Round => ramp(across.hypot(overshoot)),
+                LineCap::Butt => ramp(across) * (0.5 - overshoot).clamp(0.0, 1.0),
+                LineCap::Square => ramp(across) * ramp(overshoot),
+            };
+
+            let index = pixel_index(x, y, width);
+            if pixel_coverage > coverage[index] {
+                coverage[index] = pixel_coverage;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::*;
+    use crate::lines::{LineSegment2, LineSegment3};
+
+    fn segment(from: (f32, f32), to: (f32, f32)) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility: LineVisibility::VISIBLE,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    fn unfitted_config(width: i32, height: i32) -> SvgConfig {
+        SvgConfig {
+            margin: 0,
+            fit_lines: false,
+            visible: SvgLineConfig::new(2.0, "red".to_owned(), vec![]),
+            ..SvgConfig::new_default(width, height)
+        }
+    }
+
+    fn pixel(image: &RgbaImage, x: u32, y: u32) -> &[u8] {
+        let index = image.index(x, y) * 4;
+
+        &image.pixels[index..index + 4]
+    }
+
+    #[test]
+    fn rejects_images_too_large_to_address() {
+        assert!(
+            RgbaImage::new(std::u32::MAX, std::u32::MAX, Colour::WHITE).err()
+                == Some(MeshToSvgError::Encoding {
+                    output: "png",
+                    message: "a 4294967295 by 4294967295 pixel image is too large".to_owned(),
+                })
+        );
+    }
+
+    #[test]
+    fn fills_the_background() {
+        let mut raster_config = RasterConfig::new_default();
+        let image =
+            screen_space_lines_to_raster(&[], &unfitted_config(4, 3), &raster_config).unwrap();
+
+        assert_eq!((image.width, image.height), (4, 3));
+        assert_eq!(image.pixels, [255, 255, 255, 255].repeat(12));
+
+        raster_config.background = "transparent".to_owned();
+        raster_config.dpi = 192.0;
+        let image =
+            screen_space_lines_to_raster(&[], &unfitted_config(4, 3), &raster_config).unwrap();
+
+        assert_eq!((image.width, image.height), (8, 6));
+        assert!(image.pixels.chunks(4).all(|pixel| pixel[3] == 0));
+    }
+
+    #[test]
+    fn draws_unreadable_strokes_in_black() {
+        let mut config = unfitted_config(20, 10);
+        config.visible.stroke = "currentColor".to_owned();
+
+        let image = screen_space_lines_to_raster(
+            &[segment((2.0, 5.5), (18.0, 5.5))],
+            &config,
+            &RasterConfig::new_default(),
+        )
+        .unwrap();
+
+        assert_eq!(pixel(&image, 10, 5), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn strokes_lines_with_round_caps() {
+        let image = screen_space_lines_to_raster(
+            &[segment((2.0, 5.5), (18.0, 5.5))],
+            &unfitted_config(20, 10),
+            &RasterConfig::new_default(),
+        )
+        .unwrap();
+        let blended = |x: u32, y: u32| (1..255).contains(&pixel(&image, x, y)[1]);
+
+        // the row the line runs through is fully covered, and the rows either side are half covered
+        assert_eq!(pixel(&image, 10, 5), [255, 0, 0, 255]);
+        assert!(blended(10, 4) && blended(10, 6));
+        assert_eq!(pixel(&image, 10, 7), [255, 255, 255, 255]);
+        // the round cap covers the pixel past each end, but reaches no further
+        assert_eq!(pixel(&image, 1, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 18, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 0, 5), [255, 255, 255, 255]);
+        assert_eq!(pixel(&image, 19, 5), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn splits_lines_into_dashes() {
+        let points = vec![Point2::new(0.0, 0.0), Point2::new(10.0, 0.0)];
+        let dashes = |dash_array: Vec<f32>, dash_offset: f32| {
+            let line_config = SvgLineConfig {
+                dash_offset,
+                ..SvgLineConfig::new(1.0, "black".to_owned(), dash_array)
+            };
+
+            dash_runs(&points, &line_config, 1.0)
+                .iter()
+                .map(|run| (run[0].x, run[run.len() - 1].x))
+                .collect::<Vec<(f32, f32)>>()
+        };
+
+        assert_eq!(dashes(vec![], 0.0), [(0.0, 10.0)]);
+        assert_eq!(dashes(vec![2.0, 3.0], 0.0), [(0.0, 2.0), (5.0, 7.0)]);
+        // an odd number of values repeats, so dashes and gaps alternate
+        assert_eq!(
+            dashes(vec![2.0], 0.0),
+            [(0.0, 2.0), (4.0, 6.0), (8.0, 10.0)]
+        );
+        assert_eq!(
+            dashes(vec![2.0, 3.0], 1.0),
+            [(0.0, 1.0), (4.0, 6.0), (9.0, 10.0)]
+        );
+    }
+
+    #[test]
+    fn encodes_png() {
+        let image = RgbaImage::new(2, 1, Colour::BLACK).unwrap();
+        let png = encode_png(&image).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        let empty = RgbaImage::new(0, 0, Colour::BLACK).unwrap();
+        let error = encode_png(&empty).unwrap_err();
+        assert!(matches!(
+            error,
+            MeshToSvgError::Encoding { output: "png", .. }
+        ));
+        assert!(error.to_string().starts_with("couldn't write the png: "));
+    }
+}
@@ -0,0 +1,892 @@
+use na::Matrix4;
+use wasm_bindgen::prelude::*;
+
+use crate::cull::CullConfig;
+use crate::curvature::FeatureLineConfig;
+use crate::dimensions::{find_dimensions, DimensionConfig};
+use crate::error::MeshToSvgError;
+use crate::fill::{find_filled_faces, FillConfig};
+use crate::hatching::{find_hatch_lines, HatchConfig};
+use crate::json_renderer::{screen_space_lines_to_fitted_json, JsonConfig};
+use crate::lines::{EdgeCandidate, LineSegmentCategorized};
+use crate::mesh::{transform_edge_candidates, Mesh, Wireframe};
+use crate::options::{
+    FillOptions, HatchOptions, InvalidOption, LineOptions, RenderOptions, SimplifyOptions,
+};
+use crate::scene::Scene;
+use crate::simplify::{simplify_line_segments, SimplifyConfig};
+use crate::svg_renderer::{
+    screen_space_lines_to_fitted_svg, screen_space_lines_to_page_svg, SvgConfig, SvgLineConfig,
+    SvgPageConfig,
+};
+use crate::utils::set_panic_hook;
+use crate::validation::MeshReport;
+use crate::{find_categorized_line_segments_for_objects, validation_result, SceneObject};
+
+/// A mesh uploaded once, along with the edges between its faces which only depend on the mesh and
+/// are the slowest part of it to work out
+#[wasm_bindgen]
+pub struct MeshHandle {
+    #[wasm_bindgen(skip)]
+    pub mesh: Mesh,
+    #[wasm_bindgen(skip)]
+    pub wireframe: Option<Wireframe>,
+    #[wasm_bindgen(skip)]
+    pub edge_candidates: Vec<EdgeCandidate>,
+    /// what's still wrong with the mesh and wireframe, for handles made by `repaired`
+    #[wasm_bindgen(skip)]
+    pub repair_reports: Option<(MeshReport, Option<MeshReport>)>,
+}
+
+/// Several meshes drawn together as the parts of one scene, so that each part hides the lines of
+/// the others behind it. Lines are tagged with the object id `add` returned for their part.
+///
+/// Parts are placed by the scene's mesh world matrix unless they're given their own
+#[wasm_bindgen]
+pub struct Assembly {
+    #[wasm_bindgen(skip)]
+    pub parts: Vec<MeshHandle>,
+    /// each part's own mesh world matrix, used instead of the scene's
+    #[wasm_bindgen(skip)]
+    pub mesh_world_matrices: Vec<Option<Matrix4<f32>>>,
+}
+
+/// The canvas and matrices, updated in place as the view changes
+#[wasm_bindgen]
+pub struct SceneHandle {
+    #[wasm_bindgen(skip)]
+    pub scene: Scene,
+}
+
+/// How lines are found and drawn, set up once and reused for every render
+#[wasm_bindgen]
+pub struct Renderer {
+    #[wasm_bindgen(skip)]
+    pub svg_config: SvgConfig,
+    /// lays the lines out on a page instead of fitting them to the svg size
+    #[wasm_bindgen(skip)]
+    pub page: Option<SvgPageConfig>,
+    #[wasm_bindgen(skip)]
+    pub feature_lines: Option<FeatureLineConfig>,
+    #[wasm_bindgen(skip)]
+    pub smooth_silhouettes: bool,
+    #[wasm_bindgen(skip)]
+    pub cull: CullConfig,
+    #[wasm_bindgen(skip)]
+    pub hatch: Option<HatchConfig>,
+    #[wasm_bindgen(skip)]
+    pub simplify: Option<SimplifyConfig>,
+    #[wasm_bindgen(skip)]
+    pub dimensions: Option<DimensionConfig>,
+    #[wasm_bindgen(skip)]
+    pub fill: Option<FillConfig>,
+}
+
+#[wasm_bindgen]
+impl MeshHandle {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        mesh_indices: Box<[usize]>,
+        mesh_vertices: Box<[f32]>,
+        mesh_normals: Box<[f32]>,
+        wireframe_indices: Option<Box<[usize]>>,
+        wireframe_vertices: Option<Box<[f32]>>,
+    ) -> Result<MeshHandle, MeshToSvgError> {
+        set_panic_hook();
+
+        Ok(MeshHandle::from_mesh(
+            Mesh::new_from_wasm(mesh_indices, mesh_vertices, mesh_normals)?,
+            wireframe_vertices
+                .map(|vertices| Wireframe::new_from_wasm(wireframe_indices, vertices))
+                .transpose()?,
+        ))
+    }
+
+    /// The mesh and wireframe with what can be safely fixed fixed, see `Mesh::repair`. Whatever
+    /// `validate_mesh` found beyond that is still there
+    pub fn repaired(
+        mesh_indices: Option<Box<[usize]>>,
+        mesh_vertices: Box<[f32]>,
+        mesh_normals: Box<[f32]>,
+        wireframe_indices: Option<Box<[usize]>>,
+        wireframe_vertices: Option<Box<[f32]>>,
+    ) -> MeshHandle {
+        set_panic_hook();
+
+        let (mesh, report) = Mesh::repair(mesh_indices.as_deref(), &mesh_vertices, &mesh_normals);
+        let (wireframe, wireframe_report) = match wireframe_vertices {
+            Some(vertices) => {
+                let (wireframe, report) =
+                    Wireframe::repair(wireframe_indices.as_deref(), &vertices);
+                (Some(wireframe), Some(report))
+            }
+            None => (None, None),
+        };
+
+        MeshHandle {
+            repair_reports: Some((report, wireframe_report)),
+            ..MeshHandle::from_mesh(mesh, wireframe)
+        }
+    }
+
+    /// What `repaired` couldn't fix in the mesh, in the same shape as `validate_mesh` returns, or
+    /// undefined for a handle that wasn't repaired
+    pub fn repair_report(&self) -> Result<JsValue, JsValue> {
+        match &self.repair_reports {
+            Some((report, _)) => validation_result(report),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    /// Same as `repair_report` for the wireframe, undefined when there isn't one
+    pub fn wireframe_repair_report(&self) -> Result<JsValue, JsValue> {
+        match &self.repair_reports {
+            Some((_, Some(report))) => validation_result(report),
+            _ => Ok(JsValue::UNDEFINED),
+        }
+    }
+}
+
+impl MeshHandle {
+    pub fn from_mesh(mesh: Mesh, wireframe: Option<Wireframe>) -> MeshHandle {
+        MeshHandle {
+            edge_candidates: mesh.get_silhouette_candidates(),
+            mesh,
+            wireframe,
+            repair_reports: None,
+        }
+    }
+
+    /// The mesh, wireframe and edge candidates moved by the matrix, without finding the edge
+    /// candidates again
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> MeshHandle {
+        MeshHandle {
+            mesh: self.mesh.transformed(matrix),
+            wireframe: self
+                .wireframe
+                .as_ref()
+                .map(|wireframe| wireframe.transformed(matrix)),
+            edge_candidates: transform_edge_candidates(&self.edge_candidates, matrix),
+            repair_reports: None,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Assembly {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Assembly {
+        set_panic_hook();
+
+        Assembly {
+            parts: Vec::new(),
+            mesh_world_matrices: Vec::new(),
+        }
+    }
+
+    /// Adds a part, returning the object id its lines are tagged with
+    pub fn add(&mut self, mesh: MeshHandle) -> usize {
+        self.parts.push(mesh);
+        self.mesh_world_matrices.push(None);
+
+        self.parts.len() - 1
+    }
+
+    /// Places the part with its own mesh world matrix, or by the scene's again when `None`
+    pub fn set_mesh_world_matrix(
+        &mut self,
+        part: usize,
+        mesh_world_matrix: Option<Box<[f32]>>,
+    ) -> Result<(), MeshToSvgError> {
+        if part >= self.parts.len() {
+            return Err(MeshToSvgError::InvalidOption(InvalidOption {
+                option: "part".to_owned(),
+                message: format!("there are only {} parts, got {}", self.parts.len(), part),
+            }));
+        }
+
+        self.mesh_world_matrices[part] = mesh_world_matrix
+            .map(|matrix| Scene::matrix_from_boxed_float_array("mesh world matrix", matrix))
+            .transpose()?;
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+}
+
+impl Assembly {
+    /// Calls `render` with the parts in the scene. Parts with their own mesh world matrices are
+    /// all moved into world space first, and drawn with a scene that leaves them there
+    pub fn with_placed_parts<T, F>(&self, scene: &Scene, render: F) -> T
+    where
+        F: FnOnce(&[&MeshHandle], &Scene) -> T,
+    {
+        if self.mesh_world_matrices.iter().all(Option::is_none) {
+            let parts: Vec<&MeshHandle> = self.parts.iter().collect();
+            return render(&parts, scene);
+        }
+
+        let placed: Vec<MeshHandle> = self
+            .parts
+            .iter()
+            .zip(&self.mesh_world_matrices)
+            .map(|(part, matrix)| {
+                part.transformed(matrix.as_ref().unwrap_or(&scene.mesh_world_matrix))
+            })
+            .collect();
+        let world_scene = Scene::new(
+            scene.width,
+            scene.height,
+            scene.view_matrix,
+            scene.projection_matrix,
+            Matrix4::identity(),
+        );
+
+        render(&placed.iter().collect::<Vec<_>>(), &world_scene)
+    }
+}
+
+impl Default for Assembly {
+    fn default() -> Assembly {
+        Assembly::new()
+    }
+}
+
+#[wasm_bindgen]
+impl SceneHandle {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        canvas_width: i32,
+        canvas_height: i32,
+        view_matrix: Box<[f32]>,
+        projection_matrix: Box<[f32]>,
+        mesh_world_matrix: Box<[f32]>,
+    ) -> Result<SceneHandle, MeshToSvgError> {
+        Ok(SceneHandle {
+            scene: Scene::new_from_wasm(
+                canvas_width,
+                canvas_height,
+                view_matrix,
+                projection_matrix,
+                mesh_world_matrix,
+            )?,
+        })
+    }
+
+    pub fn set_canvas_size(&mut self, canvas_width: i32, canvas_height: i32) {
+        self.scene.width = canvas_width as f32;
+        self.scene.height = canvas_height as f32;
+    }
+
+    pub fn set_view_matrix(&mut self, view_matrix: Box<[f32]>) -> Result<(), MeshToSvgError> {
+        self.scene.view_matrix = Scene::matrix_from_boxed_float_array("view matrix", view_matrix)?;
+
+        Ok(())
+    }
+
+    pub fn set_projection_matrix(
+        &mut self,
+        projection_matrix: Box<[f32]>,
+    ) -> Result<(), MeshToSvgError> {
+        self.scene.projection_matrix =
+            Scene::matrix_from_boxed_float_array("projection matrix", projection_matrix)?;
+
+        Ok(())
+    }
+
+    pub fn set_mesh_world_matrix(
+        &mut self,
+        mesh_world_matrix: Box<[f32]>,
+    ) -> Result<(), MeshToSvgError> {
+        self.scene.mesh_world_matrix =
+            Scene::matrix_from_boxed_float_array("mesh world matrix", mesh_world_matrix)?;
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Renderer {
+    /// Fits the lines to an svg of the given size, with the same defaults as `mesh_to_svg_lines`
+    #[wasm_bindgen(constructor)]
+    pub fn new(svg_width: i32, svg_height: i32) -> Renderer {
+        set_panic_hook();
+
+        Renderer::new_with_config(SvgConfig::new_default(svg_width, svg_height))
+    }
+
+    /// A renderer set up from a plain object in the shape of `RenderOptions`
+    pub fn from_options(
+        canvas_width: i32,
+        canvas_height: i32,
+        options: JsValue,
+    ) -> Result<Renderer, JsValue> {
+        set_panic_hook();
+
+        let options: RenderOptions = serde_wasm_bindgen::from_value(options)?;
+
+        Ok(options
+            .to_renderer(canvas_width, canvas_height)
+            .map_err(MeshToSvgError::from)?)
+    }
+
+    pub fn set_margin(&mut self, margin: i32) -> Result<(), MeshToSvgError> {
+        RenderOptions {
+            margin: Some(margin),
+            ..RenderOptions::default()
+        }
+        .validate(self.svg_config.width, self.svg_config.height)?;
+
+        self.svg_config.margin = margin;
+
+        Ok(())
+    }
+
+    pub fn set_fit_lines(&mut self, fit_lines: bool) {
+        self.svg_config.fit_lines = fit_lines;
+    }
+
+    pub fn set_visible_stroke(
+        &mut self,
+        stroke_width: f32,
+        stroke: String,
+    ) -> Result<(), MeshToSvgError> {
+        let style = LineOptions {
+            stroke_width: Some(stroke_width),
+            stroke: Some(stroke),
+            ..LineOptions::default()
+        };
+
+        Ok(style.apply_to("visible", &mut self.svg_config.visible)?)
+    }
+
+    /// Draws obscured lines solid in the given stroke, see `set_obscured_style` for dashes
+    pub fn set_obscured_stroke(
+        &mut self,
+        stroke_width: f32,
+        stroke: String,
+    ) -> Result<(), MeshToSvgError> {
+        let style = LineOptions {
+            stroke_width: Some(stroke_width),
+            stroke: Some(stroke),
+            ..LineOptions::default()
+        };
+        let mut obscured = SvgLineConfig::new_default_obscured();
+
+        style.apply_to("obscured", &mut obscured)?;
+        self.svg_config.obscured = Some(obscured);
+
+        Ok(())
+    }
+
+    /// Restyles visible lines from a plain object in the shape of `LineOptions`, leaving out
+    /// anything not given
+    pub fn set_visible_style(&mut self, style: JsValue) -> Result<(), JsValue> {
+        let style: LineOptions = serde_wasm_bindgen::from_value(style)?;
+
+        Ok(style
+            .apply_to("visible", &mut self.svg_config.visible)
+            .map_err(MeshToSvgError::from)?)
+    }
+
+    /// Same as `set_visible_style` for obscured lines, drawing them if they were hidden
+    pub fn set_obscured_style(&mut self, style: JsValue) -> Result<(), JsValue> {
+        let style: LineOptions = serde_wasm_bindgen::from_value(style)?;
+        let mut obscured = self
+            .svg_config
+            .obscured
+            .clone()
+            .unwrap_or_else(SvgLineConfig::new_default_obscured);
+
+        style
+            .apply_to("obscured", &mut obscured)
+            .map_err(MeshToSvgError::from)?;
+        self.svg_config.obscured = Some(obscured);
+
+        Ok(())
+    }
+
+    pub fn set_hide_obscured(&mut self) {
+        self.svg_config.obscured = None;
+    }
+
+    pub fn set_optimise_path_order(&mut self, optimise_path_order: bool) {
+        self.svg_config.optimise_path_order = optimise_path_order;
+    }
+
+    pub fn set_group_layers(&mut self, group_layers: bool) {
+        self.svg_config.group_layers = group_layers;
+    }
+
+    pub fn set_precision(&mut self, precision: Option<u32>) -> Result<(), MeshToSvgError> {
+        RenderOptions {
+            precision,
+            ..RenderOptions::default()
+        }
+        .validate(self.svg_config.width, self.svg_config.height)?;
+
+        self.svg_config.precision = precision;
+
+        Ok(())
+    }
+
+    pub fn set_compact_paths(&mut self, compact_paths: bool) {
+        self.svg_config.relative_commands = compact_paths;
+        self.svg_config.compact_paths = compact_paths;
+    }
+
+    /// Fits arcs and béziers within the tolerance, or only draws lines when `None`
+    pub fn set_curve_tolerance(&mut self, tolerance: Option<f32>) -> Result<(), MeshToSvgError> {
+        let options = RenderOptions {
+            fit_curves: tolerance.is_some(),
+            curve_tolerance: tolerance,
+            ..RenderOptions::default()
+        };
+
+        self.svg_config.curve_fit = options.svg_config(1, 1)?.curve_fit;
+
+        Ok(())
+    }
+
+    pub fn set_smooth_silhouettes(&mut self, smooth_silhouettes: bool) {
+        self.smooth_silhouettes = smooth_silhouettes;
+    }
+
+    pub fn set_feature_lines(&mut self, feature_lines: bool) {
+        self.feature_lines = match feature_lines {
+            true => Some(FeatureLineConfig::new_default()),
+            false => None,
+        };
+    }
+
+    pub fn set_min_segment_length(
+        &mut self,
+        min_segment_length: f32,
+    ) -> Result<(), MeshToSvgError> {
+        let options = RenderOptions {
+            min_segment_length: Some(min_segment_length),
+            ..RenderOptions::default()
+        };
+
+        self.cull.min_segment_length = options.cull_config()?.min_segment_length;
+
+        Ok(())
+    }
+
+    pub fn set_min_object_size(&mut self, min_object_size: f32) -> Result<(), MeshToSvgError> {
+        let options = RenderOptions {
+            min_object_size: Some(min_object_size),
+            ..RenderOptions::default()
+        };
+
+        self.cull.min_object_size = options.cull_config()?.min_object_size;
+
+        Ok(())
+    }
+
+    /// Simplifies with `douglas-peucker` or `visvalingam`, or not at all when `None`
+    pub fn set_simplify(
+        &mut self,
+        method: Option<String>,
+        tolerance: Option<f32>,
+    ) -> Result<(), MeshToSvgError> {
+        let options = RenderOptions {
+            simplify: method.map(|method| SimplifyOptions {
+                method: Some(method),
+                tolerance,
+            }),
+            ..RenderOptions::default()
+        };
+
+        self.simplify = options.simplify_config()?;
+
+        Ok(())
+    }
+
+    /// Hatches with lines the given distance apart, or not at all when `None`
+    pub fn set_hatch_spacing(&mut self, spacing: Option<f32>) -> Result<(), MeshToSvgError> {
+        let options = RenderOptions {
+            hatch: spacing.map(|spacing| HatchOptions {
+                spacing: Some(spacing),
+                ..HatchOptions::default()
+            }),
+            ..RenderOptions::default()
+        };
+
+        self.hatch = options.hatch_config()?;
+
+        Ok(())
+    }
+
+    /// Fills faces `flat` or as a `silhouette`, or not at all when `None`
+    pub fn set_fill(
+        &mut self,
+        fill: Option<String>,
+        colour: Option<String>,
+    ) -> Result<(), MeshToSvgError> {
+        let options = RenderOptions {
+            fill: fill.map(|fill| FillOptions {
+                mode: Some(fill),
+                colour,
+                ..FillOptions::default()
+            }),
+            ..RenderOptions::default()
+        };
+
+        self.fill = options.fill_config()?;
+
+        Ok(())
+    }
+
+    pub fn render_svg(
+        &self,
+        mesh: &MeshHandle,
+        scene: &SceneHandle,
+    ) -> Result<String, MeshToSvgError> {
+        self.render_parts_svg(&[mesh], &scene.scene)
+    }
+
+    /// Draws every part of the assembly, grouped by part when `set_group_layers` is on
+    pub fn render_assembly_svg(
+        &self,
+        assembly: &Assembly,
+        scene: &SceneHandle,
+    ) -> Result<String, MeshToSvgError> {
+        assembly.with_placed_parts(&scene.scene, |parts, scene| {
+            self.render_parts_svg(parts, scene)
+        })
+    }
+
+    /// The lines `render_svg` would draw as json, see `screen_space_lines_to_fitted_json`
+    pub fn render_json(
+        &self,
+        mesh: &MeshHandle,
+        scene: &SceneHandle,
+        chain_lines: bool,
+    ) -> Result<String, MeshToSvgError> {
+        self.render_parts_json(&[mesh], &scene.scene, chain_lines)
+    }
+
+    /// The lines of every part of the assembly as json, each tagged with its part's object id
+    pub fn render_assembly_json(
+        &self,
+        assembly: &Assembly,
+        scene: &SceneHandle,
+        chain_lines: bool,
+    ) -> Result<String, MeshToSvgError> {
+        assembly.with_placed_parts(&scene.scene, |parts, scene| {
+            self.render_parts_json(parts, scene, chain_lines)
+        })
+    }
+}
+
+impl Renderer {
+    pub fn new_with_config(svg_config: SvgConfig) -> Renderer {
+        Renderer {
+            svg_config,
+            page: None,
+            feature_lines: None,
+            smooth_silhouettes: false,
+            cull: CullConfig::new_default(),
+            hatch: None,
+            simplify: None,
+            dimensions: None,
+            fill: None,
+        }
+    }
+
+    /// The classified lines with any hatching, simplified when set up to be
+    pub fn find_lines(
+        &self,
+        mesh: &MeshHandle,
+        scene: &Scene,
+    ) -> Result<Vec<LineSegmentCategorized>, MeshToSvgError> {
+        self.find_parts_lines(&[mesh], scene)
+    }
+
+    /// Same as `find_lines` for several parts sharing the scene, with each part's lines tagged with
+    /// its index as the object id
+    pub fn find_parts_lines(
+        &self,
+        parts: &[&MeshHandle],
+        scene: &Scene,
+    ) -> Result<Vec<LineSegmentCategorized>, MeshToSvgError> {
+        let objects: Vec<SceneObject> = parts
+            .iter()
+            .enumerate()
+            .map(|(id, part)| SceneObject {
+                id,
+                mesh: &part.mesh,
+                wireframe: &part.wireframe,
+                edge_candidates: Some(&part.edge_candidates),
+                feature_lines: self.feature_lines.as_ref(),
+                smooth_silhouettes: self.smooth_silhouettes,
+            })
+            .collect();
+
+        let mut segments = find_categorized_line_segments_for_objects(&objects, scene, &self.cull)?;
+
+        if let Some(hatch_config) = &self.hatch {
+            let mut hatches = find_hatch_lines(&objects, scene, &segments, hatch_config)?;
+            segments.append(&mut hatches);
+        }
+
+        if let Some(simplify_config) = &self.simplify {
+            segments = simplify_line_segments(&segments, simplify_config);
+        }
+
+        Ok(segments)
+    }
+
+    /// The svg config fitted from the scene's canvas, with the dimensions and fills for the lines
+    pub fn svg_config_for_lines(
+        &self,
+        mesh: &MeshHandle,
+        scene: &Scene,
+        segments: &[LineSegmentCategorized],
+    ) -> Result<SvgConfig, MeshToSvgError> {
+        self.svg_config_for_parts(&[mesh], scene, segments)
+    }
+
+    /// Same as `svg_config_for_lines`, with the faces of every part filled
+    pub fn svg_config_for_parts(
+        &self,
+        parts: &[&MeshHandle],
+        scene: &Scene,
+        segments: &[LineSegmentCategorized],
+    ) -> Result<SvgConfig, MeshToSvgError> {
+        let mut svg_config = self.svg_config.clone();
+        svg_config.source_canvas_width = scene.width as i32;
+        svg_config.source_canvas_height = scene.height as i32;
+
+        if let Some(dimension_config) = &self.dimensions {
+            svg_config.dimensions = find_dimensions(segments, scene, dimension_config)?;
+        }
+
+        if let Some(fill_config) = &self.fill {
+            let meshes: Vec<&Mesh> = parts.iter().map(|part| &part.mesh).collect();
+            svg_config.fills = find_filled_faces(&meshes, scene, fill_config);
+        }
+
+        Ok(svg_config)
+    }
+
+    fn render_parts_svg(
+        &self,
+        parts: &[&MeshHandle],
+        scene: &Scene,
+    ) -> Result<String, MeshToSvgError> {
+        let segments = self.find_parts_lines(parts, scene)?;
+        let svg_config = self.svg_config_for_parts(parts, scene, &segments)?;
+
+        match &self.page {
+            Some(page_config) => {
+                screen_space_lines_to_page_svg(&segments, scene, &svg_config, page_config)
+            }
+            None => Ok(screen_space_lines_to_fitted_svg(&segments, &svg_config)),
+        }
+    }
+
+    // json is always fitted to the svg size, as its screen space points are matched up with the
+    // fitted svg
+    fn render_parts_json(
+        &self,
+        parts: &[&MeshHandle],
+        scene: &Scene,
+        chain_lines: bool,
+    ) -> Result<String, MeshToSvgError> {
+        let segments = self.find_parts_lines(parts, scene)?;
+        let svg_config = self.svg_config_for_parts(parts, scene, &segments)?;
+
+        screen_space_lines_to_fitted_json(&segments, &svg_config, &JsonConfig { chain_lines })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::{Point2, Vector2, Vector3};
+
+    use super::*;
+
+    // maps x and y from -10 to 10 across a 200 by 200 canvas, so one unit is ten pixels
+    fn front_scene(mesh_world_matrix: Matrix4<f32>) -> Scene {
+        #[rustfmt::skip]
+        let projection_matrix = Matrix4::new(
+            0.1, 0.0,  0.0, 0.0,
+            0.0, 0.1,  0.0, 0.0,
+            0.0, 0.0, -0.1, 0.0,
+            0.0, 0.0,  0.0, 1.0,
+        );
+
+        Scene::new(
+            200.0,
+            200.0,
+            Matrix4::identity(),
+            projection_matrix,
+            mesh_world_matrix,
+        )
+    }
+
+    // a unit cube around the origin, with each face's own vertices so its normals are flat
+    fn cube() -> MeshHandle {
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut indices = Vec::new();
+
+        for axis in 0..3 {
+            for &side in &[-0.5f32, 0.5] {
+                let mut normal = Vector3::zeros();
+                normal[axis] = side * 2.0;
+                let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+                let first = vertices.len() / 3;
+                for &(a, b) in &[(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)] {
+                    let mut point = Vector3::zeros();
+                    point[axis] = side;
+                    point[u] = a;
+                    point[v] = b;
+                    vertices.extend_from_slice(&[point.x, point.y, point.z]);
+                    normals.extend_from_slice(&[normal.x, normal.y, normal.z]);
+                }
+
+                indices.extend_from_slice(&[
+                    first,
+                    first + 1,
+                    first + 2,
+                    first,
+                    first + 2,
+                    first + 3,
+                ]);
+            }
+        }
+
+        MeshHandle::from_mesh(Mesh::new(Some(indices), vertices, normals).unwrap(), None)
+    }
+
+    // seen from a corner, so that silhouettes and creases both show
+    fn corner_on() -> Matrix4<f32> {
+        Matrix4::from_euler_angles(0.6, 0.8, 0.0)
+    }
+
+    fn lines_of(
+        renderer: &Renderer,
+        assembly: &Assembly,
+        scene: &Scene,
+        object_id: usize,
+    ) -> Vec<(Point2<f32>, Point2<f32>)> {
+        let mut lines: Vec<(Point2<f32>, Point2<f32>)> = assembly
+            .with_placed_parts(scene, |parts, scene| {
+                renderer.find_parts_lines(parts, scene)
+            })
+            .unwrap()
+            .iter()
+            .filter(|segment| segment.object_id == object_id)
+            .map(|segment| (segment.line_segment.from, segment.line_segment.to))
+            .collect();
+        lines.sort_by(|a, b| {
+            (a.0.x, a.0.y, a.1.x, a.1.y)
+                .partial_cmp(&(b.0.x, b.0.y, b.1.x, b.1.y))
+                .unwrap()
+        });
+
+        lines
+    }
+
+    #[test]
+    fn margins_leave_room_to_draw() {
+        let mut renderer = Renderer::new(200, 100);
+
+        assert!(renderer.set_margin(49).is_ok());
+        assert_eq!(renderer.svg_config.margin, 49);
+        assert!(renderer.set_margin(50).is_err());
+        assert!(renderer.set_margin(-1).is_err());
+        assert_eq!(renderer.svg_config.margin, 49);
+    }
+
+    #[test]
+    fn rejects_invalid_settings_without_changing_anything() {
+        let mut renderer = Renderer::new(200, 200);
+
+        assert!(renderer.set_precision(Some(20)).is_err());
+        assert_eq!(renderer.svg_config.precision, None);
+        assert!(renderer.set_curve_tolerance(Some(-1.0)).is_err());
+        assert!(renderer.svg_config.curve_fit.is_none());
+        assert!(renderer.set_fill(Some("shaded".to_owned()), None).is_err());
+        assert!(renderer.fill.is_none());
+    }
+
+    #[test]
+    fn parts_can_be_placed_by_their_own_matrix() {
+        let renderer = Renderer::new(200, 200);
+        let mut assembly = Assembly::new();
+        assembly.add(cube());
+        assembly.add(cube());
+
+        let shift = |x: f32| Matrix4::new_translation(&Vector3::new(x, 0.0, 0.0)) * corner_on();
+        assembly
+            .set_mesh_world_matrix(1, Some(shift(5.0).as_slice().into()))
+            .unwrap();
+
+        // the second part ignores the scene's matrix, so it's ten units right of the first
+        let scene = front_scene(shift(-5.0));
+        let first = lines_of(&renderer, &assembly, &scene, 0);
+        let second = lines_of(&renderer, &assembly, &scene, 1);
+
+        assert!(!first.is_empty());
+        assert_eq!(first.len(), second.len());
+        for (first, second) in first.iter().zip(&second) {
+            assert_relative_eq!(first.0 + Vector2::new(100.0, 0.0), second.0, epsilon = 1e-3);
+            assert_relative_eq!(first.1 + Vector2::new(100.0, 0.0), second.1, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn placed_parts_match_the_scene_placing_them() {
+        let renderer = Renderer::new(200, 200);
+        let placement = Matrix4::new_scaling(3.0) * corner_on();
+
+        let mut by_scene = Assembly::new();
+        by_scene.add(cube());
+
+        let mut by_part = Assembly::new();
+        by_part.add(cube());
+        by_part
+            .set_mesh_world_matrix(0, Some(placement.as_slice().into()))
+            .unwrap();
+
+        let expected = lines_of(&renderer, &by_scene, &front_scene(placement), 0);
+        let placed = lines_of(&renderer, &by_part, &front_scene(Matrix4::identity()), 0);
+
+        assert!(!expected.is_empty());
+        assert_eq!(expected.len(), placed.len());
+        for (expected, placed) in expected.iter().zip(&placed) {
+            assert_relative_eq!(expected.0, placed.0, epsilon = 1e-3);
+            assert_relative_eq!(expected.1, placed.1, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn only_existing_parts_can_be_placed() {
+        let mut assembly = Assembly::new();
+        assembly.add(cube());
+
+        assert!(assembly.set_mesh_world_matrix(0, None).is_ok());
+        assert_eq!(
+            assembly
+                .set_mesh_world_matrix(1, None)
+                .unwrap_err()
+                .to_string(),
+            "invalid part: there are only 1 parts, got 1"
+        );
+        assert!(assembly
+            .set_mesh_world_matrix(0, Some(vec![1.0; 15].into()))
+            .is_err());
+    }
+}
@@ -3,7 +3,8 @@ use std::fmt::{Display, Formatter};
 
 use na::{distance_squared, Matrix4, Point2, Point3, Vector3};
 
-use crate::lines::{LineSegment2, LineSegment3, ProjectedLine};
+use crate::error::{check_buffer, MeshToSvgError};
+use crate::lines::{EdgeLine, LineSegment2, LineSegment3, ProjectedLine};
 use crate::mesh::{Facet, Mesh};
 
 pub struct Scene {
@@ -21,18 +22,20 @@ impl Scene {
         view: Box<[f32]>,
         projection: Box<[f32]>,
         mesh_world: Box<[f32]>,
-    ) -> Scene {
-        let view_matrix = Scene::matrix_from_boxed_float_array(view);
-        let projection_matrix = Scene::matrix_from_boxed_float_array(projection);
-        let mesh_world_matrix = Scene::matrix_from_boxed_float_array(mesh_world);
-
-        Scene::new(
+    ) -> Result<Scene, MeshToSvgError> {
+        let view_matrix = Scene::matrix_from_boxed_float_array("view matrix", view)?;
+        let projection_matrix =
+            Scene::matrix_from_boxed_float_array("projection matrix", projection)?;
+        let mesh_world_matrix =
+            Scene::matrix_from_boxed_float_array("mesh world matrix", mesh_world)?;
+
+        Ok(Scene::new(
             width as f32,
             height as f32,
             view_matrix,
             projection_matrix,
             mesh_world_matrix,
-        )
+        ))
     }
 
     pub fn new(
@@ -66,6 +69,24 @@ impl Scene {
         )
     }
 
+    // an orthographic projection has no perspective divide, so the bottom row is left as identity
+    pub fn is_orthographic(&self) -> bool {
+        relative_eq!(self.projection_matrix[(3, 2)], 0.0)
+            && relative_eq!(self.projection_matrix[(3, 3)], 1.0)
+    }
+
+    // the number of screen space units per world unit. This is only exact for orthographic
+    // projections, as with perspective the scale varies with depth
+    pub fn screen_space_scale(&self) -> f32 {
+        self.projection_matrix[(0, 0)] * self.width / 2.0
+    }
+
+    // as above for the screen space y axis, which only matches the x scale when the projection
+    // has the same aspect ratio as the canvas
+    pub fn screen_space_vertical_scale(&self) -> f32 {
+        self.projection_matrix[(1, 1)] * self.height / 2.0
+    }
+
     // these values are hardcoded by manually setting a value in babylonjs and reading out the values with `log!("{}", scene);`
     pub fn new_test() -> Scene {
         #[rustfmt::skip]
@@ -105,11 +126,23 @@ impl Scene {
         )
     }
 
-    fn matrix_from_boxed_float_array(data: Box<[f32]>) -> Matrix4<f32> {
-        Matrix4::new(
+    /// Reads a column major matrix, as used by webgl and three.js
+    pub fn matrix_from_boxed_float_array(
+        matrix: &'static str,
+        data: Box<[f32]>,
+    ) -> Result<Matrix4<f32>, MeshToSvgError> {
+        if data.len() != 16 {
+            return Err(MeshToSvgError::MatrixLength {
+                matrix,
+                length: data.len(),
+            });
+        }
+        check_buffer(matrix, &data, 16)?;
+
+        Ok(Matrix4::new(
             data[0], data[4], data[8], data[12], data[1], data[5], data[9], data[13], data[2],
             data[6], data[10], data[14], data[3], data[7], data[11], data[15],
-        )
+        ))
     }
 
     pub fn project_point(&self, point: &Point3<f32>) -> Point2<f32> {
@@ -121,8 +154,11 @@ impl Scene {
         )
     }
 
-    pub fn unproject_point(&self, point: &Point2<f32>) -> Point3<f32> {
-        let inverted = &self.transformation_matrix().try_inverse().unwrap();
+    pub fn unproject_point(&self, point: &Point2<f32>) -> Result<Point3<f32>, MeshToSvgError> {
+        let inverted = &self
+            .transformation_matrix()
+            .try_inverse()
+            .ok_or(MeshToSvgError::NonInvertibleTransform)?;
 
         let projection_point = Point3::new(
             (point.x / self.width) * 2.0 - 1.0,
@@ -130,7 +166,26 @@ impl Scene {
             -1.0,
         );
 
-        inverted.transform_point(&projection_point)
+        Ok(inverted.transform_point(&projection_point))
+    }
+
+    /// Unit vector from a mesh space point towards the viewer along its line of sight, which works
+    /// for perspective as well as orthographic views
+    pub fn towards_viewer(&self, point: &Point3<f32>) -> Vector3<f32> {
+        let transformation = self.transformation_matrix();
+        let inverse = match transformation.try_inverse() {
+            Some(inverse) => inverse,
+            None => return Vector3::zeros(),
+        };
+
+        // -1 is the near end of clip space, so this is a point closer to the viewer
+        let projected = transformation.transform_point(point);
+        let nearer =
+            inverse.transform_point(&Point3::new(projected.x, projected.y, projected.z - 1.0));
+
+        (nearer - point)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::zeros)
     }
 
     pub fn project_line(&self, line: &LineSegment3) -> LineSegment2 {
@@ -140,15 +195,17 @@ impl Scene {
         }
     }
 
-    pub fn project_lines(&self, lines: &[LineSegment3]) -> Vec<ProjectedLine> {
+    pub fn project_lines(&self, lines: &[EdgeLine]) -> Vec<ProjectedLine> {
         let projected_lines: Vec<ProjectedLine> = lines
             .into_iter()
-            .map(|line| {
-                let screen_space = self.project_line(line);
+            .map(|edge_line| {
+                let screen_space = self.project_line(&edge_line.line);
 
                 ProjectedLine {
                     screen_space,
-                    view_space: line.to_owned(),
+                    mesh_space: edge_line.line.to_owned(),
+                    kind: edge_line.kind,
+                    object_id: edge_line.object_id,
                 }
             })
             .collect();
@@ -206,18 +263,24 @@ impl Ray {
     }
 
     fn intersects_facet(&self, facet: &Facet) -> Option<f32> {
+        self.intersects_triangle(&facet.points)
+    }
+
+    /// Distance along the ray to where it passes through the triangle, if it does within its
+    /// length
+    pub fn intersects_triangle(&self, points: &[Point3<f32>]) -> Option<f32> {
         let length_squared = self.length * self.length;
 
         // if the ray does not reach the facet, it cannot intersect. Exit early
-        if distance_squared(&self.origin, &facet.points[0]) > length_squared
-            && distance_squared(&self.origin, &facet.points[1]) > length_squared
-            && distance_squared(&self.origin, &facet.points[2]) > length_squared
+        if distance_squared(&self.origin, &points[0]) > length_squared
+            && distance_squared(&self.origin, &points[1]) > length_squared
+            && distance_squared(&self.origin, &points[2]) > length_squared
         {
             return None;
         }
 
-        let edge_1 = &facet.points[1] - &facet.points[0];
-        let edge_2 = &facet.points[2] - &facet.points[0];
+        let edge_1 = &points[1] - &points[0];
+        let edge_2 = &points[2] - &points[0];
 
         let pvec: Vector3<f32> = self.direction.cross(&edge_2);
 
@@ -230,7 +293,7 @@ impl Ray {
 
         let invdet = 1.0 / det;
 
-        let tvec = &self.origin - &facet.points[0];
+        let tvec = &self.origin - &points[0];
 
         let bv = tvec.dot(&pvec) * invdet;
 
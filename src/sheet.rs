@@ -0,0 +1,381 @@
+use na::Point2;
+
+use crate::utils::escape_xml;
+
+// sizes in mm, loosely following the ISO 7200 title block and ISO 3098 lettering
+const TITLE_BLOCK_WIDTH: f32 = 120.0;
+const ROW_HEIGHT: f32 = 10.0;
+const TITLE_SIZE: f32 = 5.0;
+const CAPTION_SIZE: f32 = 1.8;
+const VALUE_SIZE: f32 = 3.5;
+const LABEL_SIZE: f32 = 3.5;
+const TEXT_PADDING: f32 = 1.5;
+// space between the bottom of the drawing and the baseline of its label
+const LABEL_GAP: f32 = 6.0;
+const BORDER_STROKE_WIDTH: f32 = 0.7;
+const GRID_STROKE_WIDTH: f32 = 0.35;
+
+#[derive(Clone)]
+pub struct TitleBlock {
+    pub title: String,
+    pub part_number: Option<String>,
+    pub scale: Option<String>,
+    pub date: Option<String>,
+    pub author: Option<String>,
+}
+
+/// A frame drawn around the lines, with an optional title block in the bottom right corner and a
+/// label centred under the view
+#[derive(Clone)]
+pub struct SheetConfig {
+    pub border: bool,
+    pub title_block: Option<TitleBlock>,
+    pub view_label: Option<String>,
+    /// space kept clear between the frame and the drawing, only used for page layouts
+    pub padding_mm: f32,
+    pub stroke: String,
+    pub font_family: String,
+}
+
+impl TitleBlock {
+    pub fn new(title: String) -> TitleBlock {
+        TitleBlock {
+            title,
+            part_number: None,
+            scale: None,
+            date: None,
+            author: None,
+        }
+    }
+
+    /// Caption and value of each field that has been set
+    fn fields(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            ("PART NUMBER", &self.part_number),
+            ("SCALE", &self.scale),
+            ("DATE", &self.date),
+            ("AUTHOR", &self.author),
+        ]
+        .into_iter()
+        .filter_map(|(caption, value)| value.as_ref().map(|value| (caption, value.as_str())))
+        .collect()
+    }
+
+    fn height_mm(&self) -> f32 {
+        match self.fields().is_empty() {
+            true => ROW_HEIGHT,
+            false => ROW_HEIGHT * 2.0,
+        }
+    }
+}
+
+impl SheetConfig {
+    pub fn new_default() -> SheetConfig {
+        SheetConfig {
+            border: true,
+            title_block: None,
+            view_label: None,
+            padding_mm: 5.0,
+            stroke: "black".to_owned(),
+            font_family: "sans-serif".to_owned(),
+        }
+    }
+
+    /// Height along the bottom of the frame that the drawing must keep clear of
+    pub fn reserved_height_mm(&self) -> f32 {
+        let title_block_height = match &self.title_block {
+            Some(title_block) => title_block.height_mm(),
+            None => 0.0,
+        };
+
+        let label_height = match self.view_label {
+            Some(_) => LABEL_GAP + LABEL_SIZE,
+            None => 0.0,
+        };
+
+        title_block_height + label_height
+    }
+}
+
+/// Svg elements for the sheet, in user units. `frame_min` and `frame_max` are the corners of the
+/// border and `drawing_bounds` the extent of the lines, which the view label is centred under
+pub fn sheet_elements(
+    config: &SheetConfig,
+    frame_min: &Point2<f32>,
+    frame_max: &Point2<f32>,
+    units_per_mm: f32,
+    drawing_bounds: Option<(Point2<f32>, Point2<f32>)>,
+) -> String {
+    let mm = |length: f32| length * units_per_mm;
+    let mut elements = Vec::new();
+
+    if config.border {
+        elements.push(format!(
+            "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" stroke-width=\"{stroke_width}\" />",
+            x = frame_min.x,
+            y = frame_min.y,
+            width = frame_max.x - frame_min.x,
+            height = frame_max.y - frame_min.y,
+            stroke_width = mm(BORDER_STROKE_WIDTH),
+        ));
+    }
+
+    if let Some(title_block) = &config.title_block {
+        let width = mm(TITLE_BLOCK_WIDTH).min(frame_max.x - frame_min.x);
+        let height = mm(title_block.height_mm());
+        let origin = Point2::new(frame_max.x - width, frame_max.y - height);
+
+        elements.push(format!(
+            "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" stroke-width=\"{stroke_width}\" />",
+            x = origin.x,
+            y = origin.y,
+            width = width,
+            height = height,
+            stroke_width = mm(BORDER_STROKE_WIDTH),
+        ));
+
+        elements.push(text_element(
+            &title_block.title,
+            &Point2::new(
+                origin.x + mm(TEXT_PADDING),
+                origin.y + mm((ROW_HEIGHT + TITLE_SIZE * 0.7) / 2.0),
+            ),
+            mm(TITLE_SIZE),
+            "start",
+            config,
+        ));
+
+        let fields = title_block.fields();
+
+        if !fields.is_empty() {
+            let row_top = origin.y + mm(ROW_HEIGHT);
+            let cell_width = width / fields.len() as f32;
+
+            let mut grid = format!(
+                "M {x1} {y} L {x2} {y}",
+                x1 = origin.x,
+                x2 = origin.x + width,
+                y = row_top
+            );
+
+            for (i, (caption, value)) in fields.iter().enumerate() {
+                let cell_x = origin.x + cell_width * i as f32;
+
+                if i > 0 {
+                    grid.push_str(
+                        format!(
+                            " M {x} {y1} L {x} {y2}",
+                            x = cell_x,
+                            y1 = row_top,
+                            y2 = origin.y + height
+                        )
+                        .as_str(),
+                    );
+                }
+
+                elements.push(text_element(
+                    caption,
+                    &Point2::new(
+                        cell_x + mm(TEXT_PADDING),
+                        row_top + mm(TEXT_PADDING + CAPTION_SIZE),
+                    ),
+                    mm(CAPTION_SIZE),
+                    "start",
+                    config,
+                ));
+                elements.push(text_element(
+                    value,
+                    &Point2::new(
+                        cell_x + mm(TEXT_PADDING),
+                        origin.y + height - mm(TEXT_PADDING),
+                    ),
+                    mm(VALUE_SIZE),
+                    "start",
+                    config,
+                ));
+            }
+
+            elements.push(format!(
+                "<path d=\"{grid}\" stroke-width=\"{stroke_width}\" />",
+                grid = grid,
+                stroke_width = mm(GRID_STROKE_WIDTH),
+            ));
+        }
+    }
+
+    if let (Some(label), Some((min, max))) = (&config.view_label, drawing_bounds) {
+        elements.push(text_element(
+            label,
+            &Point2::new((min.x + max.x) / 2.0, max.y + mm(LABEL_GAP)),
+            mm(LABEL_SIZE),
+            "middle",
+            config,
+        ));
+    }
+
+    format!(
+        "<g id=\"sheet\" class=\"sheet\" fill=\"none\" stroke=\"{stroke}\">\n{elements}\n</g>",
+        stroke = escape_xml(&config.stroke),
+        elements = elements.join("\n"),
+    )
+}
+
+fn text_element(
+    text: &str,
+    position: &Point2<f32>,
+    size: f32,
+    anchor: &str,
+    config: &SheetConfig,
+) -> String {
+    format!(
+        "<text x=\"{x}\" y=\"{y}\" font-size=\"{size}\" font-family=\"{font_family}\" text-anchor=\"{anchor}\" fill=\"{fill}\" stroke=\"none\">{text}</text>",
+        x = position.x,
+        y = position.y,
+        size = size,
+        font_family = escape_xml(&config.font_family),
+        anchor = anchor,
+        fill = escape_xml(&config.stroke),
+        text = escape_xml(text),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(config: &SheetConfig, drawing_bounds: Option<(Point2<f32>, Point2<f32>)>) -> String {
+        sheet_elements(
+            config,
+            &Point2::new(10.0, 10.0),
+            &Point2::new(290.0, 200.0),
+            1.0,
+            drawing_bounds,
+        )
+    }
+
+    fn with_title_block(title_block: TitleBlock) -> SheetConfig {
+        SheetConfig {
+            border: false,
+            title_block: Some(title_block),
+            ..SheetConfig::new_default()
+        }
+    }
+
+    #[test]
+    fn reserves_room_for_the_title_block_and_label() {
+        let mut config = SheetConfig::new_default();
+        assert_eq!(config.reserved_height_mm(), 0.0);
+
+        let mut title_block = TitleBlock::new("Bracket".to_owned());
+        config.title_block = Some(title_block.clone());
+        assert_eq!(config.reserved_height_mm(), 10.0);
+
+        title_block.author = Some("A. Drafter".to_owned());
+        config.title_block = Some(title_block);
+        assert_eq!(config.reserved_height_mm(), 20.0);
+
+        config.view_label = Some("FRONT".to_owned());
+        assert_eq!(config.reserved_height_mm(), 29.5);
+    }
+
+    #[test]
+    fn draws_the_border_around_the_frame() {
+        let svg = sheet(&SheetConfig::new_default(), None);
+
+        assert_eq!(
+            svg,
+            "<g id=\"sheet\" class=\"sheet\" fill=\"none\" stroke=\"black\">\n<rect x=\"10\" y=\"10\" width=\"280\" height=\"190\" stroke-width=\"0.7\" />\n</g>"
+        );
+    }
+
+    #[test]
+    fn lays_out_a_title_only_block_in_the_corner() {
+        let svg = sheet(
+            &with_title_block(TitleBlock::new("Bracket".to_owned())),
+            None,
+        );
+        let elements: Vec<&str> = svg.lines().skip(1).collect();
+
+        assert_eq!(
+            elements,
+            [
+                "<rect x=\"170\" y=\"190\" width=\"120\" height=\"10\" stroke-width=\"0.7\" />",
+                "<text x=\"171.5\" y=\"196.75\" font-size=\"5\" font-family=\"sans-serif\" text-anchor=\"start\" fill=\"black\" stroke=\"none\">Bracket</text>",
+                "</g>",
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_the_field_row_into_cells() {
+        let title_block = TitleBlock {
+            part_number: Some("BR-01".to_owned()),
+            date: Some("2020-01-01".to_owned()),
+            ..TitleBlock::new("Bracket".to_owned())
+        };
+
+        let svg = sheet(&with_title_block(title_block), None);
+        let texts: Vec<&str> = svg
+            .lines()
+            .filter(|element| element.starts_with("<text"))
+            .collect();
+
+        assert!(svg.contains("<rect x=\"170\" y=\"180\" width=\"120\" height=\"20\""));
+        assert!(svg.contains(
+            "<path d=\"M 170 190 L 290 190 M 230 190 L 230 200\" stroke-width=\"0.35\" />"
+        ));
+        // captions above values, each cell in the order the fields are listed
+        assert_eq!(texts.len(), 5);
+        assert!(texts[1].starts_with("<text x=\"171.5\" y=\"193.3\" font-size=\"1.8\""));
+        assert!(texts[1].ends_with(">PART NUMBER</text>"));
+        assert!(texts[2].starts_with("<text x=\"171.5\" y=\"198.5\" font-size=\"3.5\""));
+        assert!(texts[2].ends_with(">BR-01</text>"));
+        assert!(texts[3].starts_with("<text x=\"231.5\" y=\"193.3\""));
+        assert!(texts[3].ends_with(">DATE</text>"));
+        assert!(texts[4].ends_with(">2020-01-01</text>"));
+    }
+
+    #[test]
+    fn fits_the_title_block_within_narrow_frames() {
+        let svg = sheet_elements(
+            &with_title_block(TitleBlock::new("Bracket".to_owned())),
+            &Point2::new(0.0, 0.0),
+            &Point2::new(80.0, 50.0),
+            1.0,
+            None,
+        );
+
+        assert!(svg.contains("<rect x=\"0\" y=\"40\" width=\"80\" height=\"10\""));
+    }
+
+    #[test]
+    fn centres_the_view_label_under_the_drawing() {
+        let config = SheetConfig {
+            border: false,
+            view_label: Some("SECTION <A>".to_owned()),
+            ..SheetConfig::new_default()
+        };
+
+        let svg = sheet(
+            &config,
+            Some((Point2::new(50.0, 50.0), Point2::new(150.0, 100.0))),
+        );
+
+        assert!(svg.contains("<text x=\"100\" y=\"106\" font-size=\"3.5\" font-family=\"sans-serif\" text-anchor=\"middle\" fill=\"black\" stroke=\"none\">SECTION &lt;A&gt;</text>"));
+        // there's nothing to centre it under without any lines
+        assert!(!sheet(&config, None).contains("<text"));
+    }
+
+    #[test]
+    fn scales_to_user_units() {
+        let svg = sheet_elements(
+            &SheetConfig::new_default(),
+            &Point2::new(0.0, 0.0),
+            &Point2::new(100.0, 100.0),
+            2.0,
+            None,
+        );
+
+        assert!(svg.contains("stroke-width=\"1.4\""));
+    }
+}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use na::{Point3, Vector3};
+
+use crate::lines::{EdgeKind, EdgeLine, LineSegment3};
+use crate::mesh::{Mesh, WeldedMesh, CREASE_THRESHOLD};
+use crate::scene::{Ray, Scene};
+
+// fraction of the size of the mesh lines are lifted beyond the surface, to stay clear of it
+// despite rounding
+const LIFT_CLEARANCE: f32 = 0.001;
+const LIFT_SAMPLES: usize = 4;
+// the most grid cells across the mesh, so large faces don't cover too many of them
+const GRID_CELLS: f32 = 64.0;
+
+/// Silhouettes of curved surfaces found where the interpolated vertex normals turn side on to the
+/// viewer within each triangle, rather than at the triangle edges where facing flips. This follows
+/// the surface the mesh approximates instead of zigzagging along its tessellation. Normals aren't
+/// interpolated across creases, so silhouettes along creases still follow the mesh edges.
+///
+/// The smooth silhouette runs partly over faces turned away from the viewer, where the faceted
+/// surface in front would hide it from the visibility test. Each end of a line is lifted towards
+/// the viewer along its line of sight until it's clear of that surface, which leaves it in the same
+/// place on screen. It's never lifted past another part of the mesh in front or further than the
+/// size of its triangle, so anything else in front still hides it
+pub fn find_smooth_silhouettes(mesh: &Mesh, scene: &Scene) -> Vec<EdgeLine> {
+    let welded = WeldedMesh::new(mesh);
+    let grid = FaceGrid::new(&welded);
+    let clearance = welded.size * LIFT_CLEARANCE;
+
+    let towards_viewer: Vec<Vector3<f32>> = welded
+        .points
+        .iter()
+        .map(|point| scene.towards_viewer(point))
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut edge_faces: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+
+    for (face, triangle) in welded.triangles.iter().enumerate() {
+        let corners = &welded.corner_normals[face];
+        let values = [
+            corners[0].dot(&towards_viewer[triangle[0]]),
+            corners[1].dot(&towards_viewer[triangle[1]]),
+            corners[2].dot(&towards_viewer[triangle[2]]),
+        ];
+
+        if let Some(mut line) = welded.zero_crossing(triangle, &values, EdgeKind::SILHOUETTE) {
+            let longest_edge = (0..3)
+                .map(|i| {
+                    na::distance(
+                        &welded.points[triangle[i]],
+                        &welded.points[triangle[(i + 1) % 3]],
+                    )
+                })
+                .fold(0.0, f32::max);
+
+            // the surface can be furthest in front anywhere along the line, so it's checked at
+            // points along it and the whole line is lifted by the most any of them needs
+            let (from, to) = (line.line.from, line.line.to);
+            let depth = (0..=LIFT_SAMPLES)
+                .map(|i| {
+                    let point = from + (to - from) * (i as f32 / LIFT_SAMPLES as f32);
+                    depth_beneath_surface(&welded, &grid, face, &point, scene, longest_edge)
+                })
+                .fold(0.0, f32::max);
+            let lift = (depth + clearance).min(longest_edge);
+
+            line.line.from += scene.towards_viewer(&from) * lift;
+            line.line.to += scene.towards_viewer(&to) * lift;
+            lines.push(line);
+        }
+
+        for i in 0..3 {
+            let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+            edge_faces
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push(face);
+        }
+    }
+
+    for ((a, b), faces) in edge_faces {
+        if faces.len() != 2 {
+            continue;
+        }
+
+        let (normal_a, normal_b) = (welded.face_normals[faces[0]], welded.face_normals[faces[1]]);
+        if normal_a.dot(&normal_b) >= CREASE_THRESHOLD {
+            continue;
+        }
+
+        let midpoint = welded.points[a] + (welded.points[b] - welded.points[a]) * 0.5;
+        let view = scene.towards_viewer(&midpoint);
+
+        if (normal_a.dot(&view) > 0.0) != (normal_b.dot(&view) > 0.0) {
+            lines.push(EdgeLine {
+                line: LineSegment3 {
+                    from: welded.points[a],
+                    to: welded.points[b],
+                },
+                kind: EdgeKind::SILHOUETTE,
+                object_id: 0,
+            });
+        }
+    }
+
+    lines
+}
+
+/// How far the point's line of sight runs through the faceted surface before leaving it, up to
+/// `max_depth`. Faces turned away from the line of sight are where it enters another part of the
+/// mesh in front, which should still hide the point, so only faces before the first of those count
+fn depth_beneath_surface(
+    welded: &WeldedMesh,
+    grid: &FaceGrid,
+    face: usize,
+    point: &Point3<f32>,
+    scene: &Scene,
+    max_depth: f32,
+) -> f32 {
+    let mut ray = Ray::new();
+    ray.origin = *point;
+    ray.direction = scene.towards_viewer(point);
+    ray.length = max_depth;
+
+    let mut hits: Vec<(f32, bool)> = grid
+        .faces_near(point, &(point + ray.direction * max_depth))
+        .into_iter()
+        .filter(|other| *other != face)
+        .filter_map(|other| {
+            let corners = &welded.triangles[other];
+            let distance = ray.intersects_triangle(&[
+                welded.points[corners[0]],
+                welded.points[corners[1]],
+                welded.points[corners[2]],
+            ])?;
+            let leaving = welded.face_normals[other].dot(&ray.direction) > 0.0;
+
+            Some((distance, leaving))
+        })
+        .filter(|(distance, _)| *distance > 0.0)
+        .collect();
+    hits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    hits.iter()
+        .take_while(|(_, leaving)| *leaving)
+        .last()
+        .map_or(0.0, |(distance, _)| *distance)
+}
+
+/// Faces bucketed by the grid cells their bounding boxes overlap, so that short rays are only
+/// tested against the faces around them
+struct FaceGrid {
+    cell_size: f32,
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+}
+
+impl FaceGrid {
+    fn new(welded: &WeldedMesh) -> FaceGrid {
+        let edges = welded.triangles.len() * 3;
+        let total_length: f32 = welded
+            .triangles
+            .iter()
+            .flat_map(|triangle| (0..3).map(move |i| (triangle[i], triangle[(i + 1) % 3])))
+            .map(|(a, b)| na::distance(&welded.points[a], &welded.points[b]))
+            .sum();
+
+        let mut grid = FaceGrid {
+            cell_size: (total_length / edges.max(1) as f32).max(welded.size / GRID_CELLS),
+            cells: HashMap::new(),
+        };
+
+        for (face, triangle) in welded.triangles.iter().enumerate() {
+            let corners: Vec<Point3<f32>> = triangle.iter().map(|&i| welded.points[i]).collect();
+            for cell in grid.cells_covering(&corners) {
+                grid.cells.entry(cell).or_default().push(face);
+            }
+        }
+
+        grid
+    }
+
+    fn cell(&self, point: &Point3<f32>) -> (i64, i64, i64) {
+        match self.cell_size > 0.0 {
+            true => (
+                (point.x / self.cell_size).floor() as i64,
+                (point.y / self.cell_size).floor() as i64,
+                (point.z / self.cell_size).floor() as i64,
+            ),
+            // a mesh with no size fits in one cell
+            false => (0, 0, 0),
+        }
+    }
+
+    /// Cells overlapping the bounding box of the points
+    fn cells_covering(&self, points: &[Point3<f32>]) -> Vec<(i64, i64, i64)> {
+        let cells: Vec<(i64, i64, i64)> = points.iter().map(|point| self.cell(point)).collect();
+        let min = |axis: fn(&(i64, i64, i64)) -> i64| cells.iter().map(axis).min().unwrap_or(0);
+        let max = |axis: fn(&(i64, i64, i64)) -> i64| cells.iter().map(axis).max().unwrap_or(0);
+
+        let mut covered = Vec::new();
+        for x in min(|cell| cell.0)..=max(|cell| cell.0) {
+            for y in min(|cell| cell.1)..=max(|cell| cell.1) {
+                for z in min(|cell| cell.2)..=max(|cell| cell.2) {
+                    covered.push((x, y, z));
+                }
+            }
+        }
+
+        covered
+    }
+
+    /// Faces that may cross the line between the two points, each once and in order
+    fn faces_near(&self, from: &Point3<f32>, to: &Point3<f32>) -> Vec<usize> {
+        let mut faces: Vec<usize> = self
+            .cells_covering(&[*from, *to])
+            .iter()
+            .filter_map(|cell| self.cells.get(cell))
+            .flatten()
+            .copied()
+            .collect();
+        faces.sort_unstable();
+        faces.dedup();
+
+        faces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Matrix4;
+
+    use super::*;
+
+    fn front_scene() -> Scene {
+        #[rustfmt::skip]
+        let projection_matrix = Matrix4::new(
+            0.1, 0.0,  0.0, 0.0,
+            0.0, 0.1,  0.0, 0.0,
+            0.0, 0.0, -0.1, 0.0,
+            0.0, 0.0,  0.0, 1.0,
+        );
+
+        Scene::new(
+            200.0,
+            200.0,
+            Matrix4::identity(),
+            projection_matrix,
+            Matrix4::identity(),
+        )
+    }
+
+    fn sphere(radius: f32) -> Mesh {
+        // an odd number of rows leaves no vertices on the equator, side on to the viewer
+        let (columns, rows) = (24, 11);
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let theta = row as f32 / rows as f32 * std::f32::consts::PI;
+                let phi = column as f32 / columns as f32 * 2.0 * std::f32::consts::PI;
+                let normal = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                vertices.extend_from_slice(&[
+                    normal.x * radius,
+                    normal.y * radius,
+                    normal.z * radius,
+                ]);
+                normals.extend_from_slice(&[normal.x, normal.y, normal.z]);
+            }
+        }
+
+        let index = |column: usize, row: usize| row * (columns + 1) + column;
+        let mut indices = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                indices.extend_from_slice(&[
+                    index(column, row),
+                    index(column + 1, row),
+                    index(column + 1, row + 1),
+                    index(column, row),
+                    index(column + 1, row + 1),
+                    index(column, row + 1),
+                ]);
+            }
+        }
+
+        Mesh::new(Some(indices), vertices, normals).unwrap()
+    }
+
+    #[test]
+    fn grid_finds_every_face_that_may_cross_a_line() {
+        let welded = WeldedMesh::new(&sphere(5.0));
+        let grid = FaceGrid::new(&welded);
+
+        let (from, to) = (Point3::new(4.0, 1.0, 2.0), Point3::new(4.5, 0.5, 3.0));
+        let near = grid.faces_near(&from, &to);
+
+        for (face, triangle) in welded.triangles.iter().enumerate() {
+            let overlaps = (0..3).all(|axis| {
+                let corners = triangle.iter().map(|&i| welded.points[i][axis]);
+                let low = corners.clone().fold(std::f32::INFINITY, f32::min);
+                let high = corners.fold(std::f32::NEG_INFINITY, f32::max);
+                low <= from[axis].max(to[axis]) && high >= from[axis].min(to[axis])
+            });
+
+            if overlaps {
+                assert!(near.contains(&face), "face {} is missing", face);
+            }
+        }
+
+        // only the faces around the line, not the whole sphere
+        assert!(!near.is_empty() && near.len() < welded.triangles.len() / 4);
+        assert!(near.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn silhouettes_follow_the_outline_in_front_of_the_surface() {
+        let lines = find_smooth_silhouettes(&sphere(5.0), &front_scene());
+        assert!(!lines.is_empty());
+
+        for line in &lines {
+            for point in &[line.line.from, line.line.to] {
+                let radius = (point.x * point.x + point.y * point.y).sqrt();
+                assert!((radius - 5.0).abs() < 0.1, "{} is off the outline", point);
+                // lifted towards the viewer, but no further than a triangle
+                assert!(point.z > 0.0 && point.z < 1.5, "{} isn't lifted", point);
+            }
+        }
+    }
+
+    #[test]
+    fn lift_scales_with_the_mesh() {
+        let scene = front_scene();
+        let small = find_smooth_silhouettes(&sphere(0.05), &scene);
+        let large = find_smooth_silhouettes(&sphere(5.0), &scene);
+
+        assert_eq!(small.len(), large.len());
+        for (small, large) in small.iter().zip(&large) {
+            assert_relative_eq!(small.line.from * 100.0, large.line.from, epsilon = 1e-3);
+            assert_relative_eq!(small.line.to * 100.0, large.line.to, epsilon = 1e-3);
+        }
+    }
+}
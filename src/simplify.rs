@@ -0,0 +1,372 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use na::{distance, Point2};
+
+use crate::lines::LineSegmentCategorized;
+use crate::polyline::{chain_line_segments, Polyline, WELD_EPSILON};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SimplifyMethod {
+    /// keeps the points furthest from the line between those already kept
+    DouglasPeucker,
+    /// repeatedly drops the point forming the smallest triangle with its neighbours
+    Visvalingam,
+}
+
+pub struct SimplifyConfig {
+    pub method: SimplifyMethod,
+    /// in screen space units. Douglas-Peucker keeps points further than this from the simplified
+    /// line, Visvalingam keeps points whose triangles are larger than its square
+    pub tolerance: f32,
+}
+
+impl SimplifyMethod {
+    pub fn from_name(name: &str) -> Option<SimplifyMethod> {
+        match name.to_lowercase().as_str() {
+            "douglas-peucker" => Some(SimplifyMethod::DouglasPeucker),
+            "visvalingam" => Some(SimplifyMethod::Visvalingam),
+            _ => None,
+        }
+    }
+}
+
+impl SimplifyConfig {
+    pub fn new_default() -> SimplifyConfig {
+        SimplifyConfig {
+            method: SimplifyMethod::DouglasPeucker,
+            tolerance: 0.5,
+        }
+    }
+}
+
+/// Chains the classified lines and drops intermediate points that lie within the tolerance of the
+/// simplified chain. Chains only run between points where visibility changes or lines meet, and
+/// any point that's the end of another chain is kept so lines still meet where they did
+pub fn simplify_line_segments(
+    segments: &[LineSegmentCategorized],
+    config: &SimplifyConfig,
+) -> Vec<LineSegmentCategorized> {
+    let polylines = chain_line_segments(segments);
+
+    let mut ends = EndPoints::default();
+    for polyline in &polylines {
+        ends.insert(polyline.start());
+        ends.insert(polyline.end());
+    }
+
+    polylines
+        .iter()
+        .map(|polyline| simplify_polyline(polyline, &ends, config))
+        .flat_map(|polyline| polyline.segments().collect::<Vec<_>>())
+        .collect()
+}
+
+fn simplify_polyline(polyline: &Polyline, ends: &EndPoints, config: &SimplifyConfig) -> Polyline {
+    let last = polyline.points.len() - 1;
+    let mut kept = vec![0];
+
+    // simplified separately between the ends of other chains, so that those points stay put
+    let mut section_start = 0;
+    for index in 1..=last {
+        if index < last && !ends.contains(&polyline.points[index]) {
+            continue;
+        }
+
+        let section = &polyline.points[section_start..=index];
+        let section_kept = match config.method {
+            SimplifyMethod::DouglasPeucker => douglas_peucker(section, config.tolerance),
+            SimplifyMethod::Visvalingam => visvalingam(section, config.tolerance),
+        };
+
+        kept.extend(section_kept[1..].iter().map(|&i| section_start + i));
+        section_start = index;
+    }
+
+    Polyline {
+        points: kept.iter().map(|&i| polyline.points[i]).collect(),
+        mesh_space_points: kept
+            .iter()
+            .map(|&i| polyline.mesh_space_points[i])
+            .collect(),
+        ..polyline.clone()
+    }
+}
+
+/// Indices of the points kept, always including both ends
+fn douglas_peucker(points: &[Point2<f32>], tolerance: f32) -> Vec<usize> {
+    let last = points.len() - 1;
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[last] = true;
+
+    let mut stack = vec![(0, last)];
+
+    while let Some((first, end)) = stack.pop() {
+        let furthest = (first + 1..end)
+            .map(|i| {
+                (
+                    i,
+                    distance_to_line(&points[i], &points[first], &points[end]),
+                )
+            })
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        if let Some((index, offset)) = furthest {
+            if offset > tolerance {
+                keep[index] = true;
+                stack.push((first, index));
+                stack.push((index, end));
+            }
+        }
+    }
+
+    (0..=last).filter(|&i| keep[i]).collect()
+}
+
+/// Indices of the points kept, always including both ends
+fn visvalingam(points: &[Point2<f32>], tolerance: f32) -> Vec<usize> {
+    let last = points.len() - 1;
+    let threshold = tolerance * tolerance;
+
+    // neighbours still in the line, as removing a point changes its neighbours' triangles
+    let mut previous: Vec<usize> = (0..=last).map(|i| i.saturating_sub(1)).collect();
+    let mut next: Vec<usize> = (0..=last).map(|i| (i + 1).min(last)).collect();
+    let mut removed = vec![false; points.len()];
+
+    let area = |previous: usize, index: usize, next: usize| {
+        ((points[index] - points[previous]).perp(&(points[next] - points[previous])) / 2.0).abs()
+    };
+
+    let mut heap: BinaryHeap<Triangle> = (1..last)
+        .map(|index| Triangle {
+            area: area(index - 1, index, index + 1),
+            index,
+            previous: index - 1,
+            next: index + 1,
+        })
+        .collect();
+
+    while let Some(triangle) = heap.pop() {
+        let index = triangle.index;
+
+        // stale entries from before a neighbour was removed
+        if removed[index] || previous[index] != triangle.previous || next[index] != triangle.next {
+            continue;
+        }
+
+        if triangle.area > threshold {
+            break;
+        }
+
+        removed[index] = true;
+        let (before, after) = (previous[index], next[index]);
+        next[before] = after;
+        previous[after] = before;
+
+        // areas never drop below that of a point already removed, so that points go in order
+        for &neighbour in &[before, after] {
+            if neighbour != 0 && neighbour != last {
+                heap.push(Triangle {
+                    area: area(previous[neighbour], neighbour, next[neighbour]).max(triangle.area),
+                    index: neighbour,
+                    previous: previous[neighbour],
+                    next: next[neighbour],
+                });
+            }
+        }
+    }
+
+    (0..=last).filter(|&i| !removed[i]).collect()
+}
+
+/// A point's triangle with its neighbours, ordered so that the heap pops the smallest first
+struct Triangle {
+    area: f32,
+    index: usize,
+    previous: usize,
+    next: usize,
+}
+
+impl PartialEq for Triangle {
+    fn eq(&self, other: &Triangle) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Triangle {}
+
+impl PartialOrd for Triangle {
+    fn partial_cmp(&self, other: &Triangle) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Triangle {
+    fn cmp(&self, other: &Triangle) -> Ordering {
+        other
+            .area
+            .partial_cmp(&self.area)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+/// Chain end points, looked up within the tolerance chains are welded with
+#[derive(Default)]
+struct EndPoints {
+    grid: HashMap<(i64, i64), Vec<Point2<f32>>>,
+}
+
+impl EndPoints {
+    fn cell(point: &Point2<f32>) -> (i64, i64) {
+        (
+            (point.x / WELD_EPSILON).floor() as i64,
+            (point.y / WELD_EPSILON).floor() as i64,
+        )
+    }
+
+    fn insert(&mut self, point: &Point2<f32>) {
+        self.grid
+            .entry(EndPoints::cell(point))
+            .or_default()
+            .push(*point);
+    }
+
+    fn contains(&self, point: &Point2<f32>) -> bool {
+        let (cx, cy) = EndPoints::cell(point);
+
+        (cx - 1..=cx + 1).any(|x| {
+            (cy - 1..=cy + 1).any(|y| match self.grid.get(&(x, y)) {
+                Some(candidates) => candidates
+                    .iter()
+                    .any(|candidate| distance(candidate, point) <= WELD_EPSILON),
+                None => false,
+            })
+        })
+    }
+}
+
+fn distance_to_line(point: &Point2<f32>, from: &Point2<f32>, to: &Point2<f32>) -> f32 {
+    let along = to - from;
+    let length = along.norm();
+
+    // closed chains start and end at the same point
+    if length <= f32::EPSILON {
+        return distance(point, from);
+    }
+
+    (point - from).perp(&along).abs() / length
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::*;
+    use crate::lines::{EdgeKind, LineSegment2, LineSegment3, LineVisibility};
+
+    fn points(coordinates: &[(f32, f32)]) -> Vec<Point2<f32>> {
+        coordinates
+            .iter()
+            .map(|&(x, y)| Point2::new(x, y))
+            .collect()
+    }
+
+    fn segment(from: (f32, f32), to: (f32, f32)) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility: LineVisibility::VISIBLE,
+            kind: EdgeKind::SILHOUETTE,
+            object_id: 0,
+        }
+    }
+
+    #[test]
+    fn douglas_peucker_drops_points_within_tolerance() {
+        let line = points(&[(0.0, 0.0), (1.0, 0.1), (2.0, -0.1), (3.0, 0.0)]);
+
+        assert_eq!(douglas_peucker(&line, 0.5), vec![0, 3]);
+        assert_eq!(douglas_peucker(&line, 0.05), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn douglas_peucker_keeps_corners() {
+        let line = points(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (2.0, 2.0)]);
+
+        assert_eq!(douglas_peucker(&line, 0.5), vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn visvalingam_drops_small_triangles() {
+        // the triangle at the bump has an area of 0.1, under the squared tolerance of 0.25
+        let line = points(&[(0.0, 0.0), (1.0, 0.1), (2.0, 0.0), (3.0, 0.0)]);
+
+        assert_eq!(visvalingam(&line, 0.5), vec![0, 3]);
+        assert_eq!(visvalingam(&line, 0.25), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn visvalingam_keeps_corners() {
+        let line = points(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (2.0, 2.0)]);
+
+        assert_eq!(visvalingam(&line, 0.5), vec![0, 2, 4]);
+    }
+
+    #[test]
+    fn keeps_points_where_other_lines_end() {
+        // a straight run with a branch leaving from its middle
+        let segments = vec![
+            segment((0.0, 0.0), (1.0, 0.0)),
+            segment((1.0, 0.0), (2.0, 0.0)),
+            segment((2.0, 0.0), (3.0, 0.0)),
+            segment((3.0, 0.0), (4.0, 0.0)),
+            segment((2.0, 0.0), (2.0, 2.0)),
+        ];
+
+        for method in &[SimplifyMethod::DouglasPeucker, SimplifyMethod::Visvalingam] {
+            let config = SimplifyConfig {
+                method: *method,
+                tolerance: 0.5,
+            };
+            let simplified = simplify_line_segments(&segments, &config);
+
+            assert_eq!(simplified.len(), 3);
+            assert!(simplified
+                .iter()
+                .all(|segment| segment.line_segment.from == Point2::new(2.0, 0.0)
+                    || segment.line_segment.to == Point2::new(2.0, 0.0)));
+        }
+    }
+
+    #[test]
+    fn keeps_the_mesh_space_ends_of_the_kept_points() {
+        let segments = vec![
+            segment((0.0, 0.0), (1.0, 0.1)),
+            segment((1.0, 0.1), (2.0, 0.0)),
+        ];
+
+        let simplified = simplify_line_segments(&segments, &SimplifyConfig::new_default());
+
+        assert_eq!(simplified.len(), 1);
+        let (screen, mesh) = (&simplified[0].line_segment, &simplified[0].mesh_space);
+        assert_eq!(Point2::new(mesh.from.x, mesh.from.y), screen.from);
+        assert_eq!(Point2::new(mesh.to.x, mesh.to.y), screen.to);
+    }
+
+    #[test]
+    fn reads_method_names() {
+        assert!(
+            SimplifyMethod::from_name("Douglas-Peucker") == Some(SimplifyMethod::DouglasPeucker)
+        );
+        assert!(SimplifyMethod::from_name("visvalingam") == Some(SimplifyMethod::Visvalingam));
+        assert!(SimplifyMethod::from_name("ramer") == None);
+    }
+}
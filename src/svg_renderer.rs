@@ -1,76 +1,250 @@
-use crate::lines::{LineSegment2, LineSegmentCategorized, LineVisibility};
+use crate::curve_fit::{fit_curves, CurveFitConfig, PathSegment};
+use crate::dimensions::{dimension_elements, dimension_room, Dimension, DimensionStyle};
+use crate::error::MeshToSvgError;
+use crate::fill::FilledFace;
+use crate::lines::{EdgeKind, LineSegment2, LineSegmentCategorized, LineVisibility};
+use crate::paper::{fit_scale, DrawingScale, LengthUnit, Page, PageLayout};
+use crate::path_order::optimise_path_order_by;
+use crate::polyline::ordered_line_classes;
+use crate::polyline::{chain_line_segments, Polyline};
+use crate::scene::Scene;
+use crate::sheet::{sheet_elements, SheetConfig};
+use crate::utils::{escape_css, escape_xml};
 use na::{Point2, Vector2};
+use std::cmp::Reverse;
 
+#[derive(Copy, Clone, PartialEq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Clone)]
 pub struct SvgLineConfig {
-    pub stroke_width: i32,
+    pub stroke_width: f32,
     pub stroke: String,
+    /// alternating dash and gap lengths, solid when empty. Dashes restart at each polyline
+    pub dash_array: Vec<f32>,
+    pub dash_offset: f32,
+    pub opacity: f32,
+    pub linecap: LineCap,
+    pub linejoin: LineJoin,
+}
+
+/// Lays the drawing out on a page, with the svg's user units in mm or inches so that it prints at
+/// the right size
+#[derive(Clone)]
+pub struct SvgPageConfig {
+    pub page: Page,
+    pub scale: DrawingScale,
+    /// real world length of one world space unit, used for exact scales
+    pub model_unit_mm: f32,
+    pub units: LengthUnit,
+}
+
+impl SvgPageConfig {
+    pub fn new_default() -> SvgPageConfig {
+        SvgPageConfig {
+            page: Page::new_default(),
+            scale: DrawingScale::Fit,
+            model_unit_mm: 1.0,
+            units: LengthUnit::Millimetres,
+        }
+    }
+}
+
+// css pixels are defined as 1/96th of an inch
+const PX_PER_MM: f32 = 96.0 / 25.4;
+
+// fills are stroked in their own colour to cover the anti-aliased seams between faces
+const FILL_SEAM_WIDTH_PX: f32 = 0.5;
+
+/// The extent of the svg's user space, optionally with the physical unit of one user unit
+struct Canvas {
+    width: f32,
+    height: f32,
+    units: Option<LengthUnit>,
+    /// distance from the edges to the sheet frame
+    frame_inset: f32,
+}
+
+impl Canvas {
+    fn from_config(config: &SvgConfig) -> Canvas {
+        Canvas {
+            width: config.width as f32,
+            height: config.height as f32,
+            units: None,
+            // lines are fitted within half the margin of the edges, so the frame goes halfway to that
+            frame_inset: config.margin as f32 / 4.0,
+        }
+    }
+
+    fn units_per_mm(&self) -> f32 {
+        match self.units {
+            Some(units) => 1.0 / units.mm(),
+            None => PX_PER_MM,
+        }
+    }
+
+    fn size_attributes(&self) -> String {
+        match self.units {
+            Some(units) => format!(
+                "width=\"{width}{suffix}\" height=\"{height}{suffix}\" viewBox=\"0 0 {width} {height}\"",
+                width = self.width,
+                height = self.height,
+                suffix = units.suffix(),
+            ),
+            None => format!(
+                "viewBox=\"0 0 {width} {height}\"",
+                width = self.width,
+                height = self.height,
+            ),
+        }
+    }
+}
+
+impl SvgLineConfig {
+    pub fn new(stroke_width: f32, stroke: String, dash_array: Vec<f32>) -> SvgLineConfig {
+        SvgLineConfig {
+            stroke_width,
+            stroke,
+            dash_array,
+            dash_offset: 0.0,
+            opacity: 1.0,
+            linecap: LineCap::Round,
+            linejoin: LineJoin::Round,
+        }
+    }
+
+    /// Thinner than visible lines and grey, but solid unless given dashes
+    pub fn new_default_obscured() -> SvgLineConfig {
+        SvgLineConfig::new(2.0, "grey".to_owned(), vec![])
+    }
+
+    /// ISO 128 dashed line for hidden outlines, dashes of 12 line widths with 3 line width gaps
+    pub fn iso_hidden_dash_array(stroke_width: f32) -> Vec<f32> {
+        vec![stroke_width * 12.0, stroke_width * 3.0]
+    }
+}
+
+impl LineCap {
+    pub fn from_name(name: &str) -> Option<LineCap> {
+        match name.to_lowercase().as_str() {
+            "butt" => Some(LineCap::Butt),
+            "round" => Some(LineCap::Round),
+            "square" => Some(LineCap::Square),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineCap::Butt => "butt",
+            LineCap::Round => "round",
+            LineCap::Square => "square",
+        }
+    }
+}
+
+impl LineJoin {
+    pub fn from_name(name: &str) -> Option<LineJoin> {
+        match name.to_lowercase().as_str() {
+            "miter" => Some(LineJoin::Miter),
+            "round" => Some(LineJoin::Round),
+            "bevel" => Some(LineJoin::Bevel),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineJoin::Miter => "miter",
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct SvgConfig {
     pub width: i32,
     pub height: i32,
     pub margin: i32,
     pub visible: SvgLineConfig,
     pub obscured: Option<SvgLineConfig>,
+    /// used for visible hatch lines instead of the visible config
+    pub hatch: SvgLineConfig,
     pub fit_lines: bool,
+    pub optimise_path_order: bool,
+    /// nest paths in a `<g>` layer per object and per visibility and edge kind
+    pub group_layers: bool,
+    /// embed a `<style>` block with a rule per class, only used for grouped layers
+    pub embed_style: bool,
+    /// decimal places for path coordinates, full precision when `None`
+    pub precision: Option<u32>,
+    /// write path coordinates relative to the previous point
+    pub relative_commands: bool,
+    /// leave out implicit commands and any separators that aren't needed
+    pub compact_paths: bool,
+    /// replace runs of lines with arcs and béziers, with the tolerance in css pixels
+    pub curve_fit: Option<CurveFitConfig>,
+    /// frame, title block and view label drawn around the lines
+    pub sheet: Option<SheetConfig>,
+    /// screen space dimensions, see `find_dimensions`
+    pub dimensions: Vec<Dimension>,
+    pub dimension_style: DimensionStyle,
+    /// screen space faces filled beneath the lines, see `find_filled_faces`
+    pub fills: Vec<FilledFace>,
     pub source_canvas_width: i32,
     pub source_canvas_height: i32,
 }
 
 impl SvgConfig {
+    /// Lines fitted to an svg the size of the canvas, with the visible lines black and the
+    /// obscured lines grey
     pub fn new_default(source_canvas_width: i32, source_canvas_height: i32) -> SvgConfig {
-        SvgConfig::new(
+        SvgConfig {
             source_canvas_width,
             source_canvas_height,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-        )
+            width: source_canvas_width,
+            height: source_canvas_height,
+            margin: 100,
+            visible: SvgLineConfig::new(4.0, "black".to_owned(), vec![]),
+            obscured: Some(SvgLineConfig::new_default_obscured()),
+            hatch: SvgLineConfig::new(1.0, "black".to_owned(), vec![]),
+            fit_lines: true,
+            optimise_path_order: false,
+            group_layers: false,
+            embed_style: false,
+            precision: None,
+            relative_commands: false,
+            compact_paths: false,
+            curve_fit: None,
+            sheet: None,
+            dimensions: vec![],
+            dimension_style: DimensionStyle::new_default(),
+            fills: vec![],
+        }
     }
 
-    pub fn new(
-        source_canvas_width: i32,
-        source_canvas_height: i32,
-        svg_config_width: Option<i32>,
-        svg_config_height: Option<i32>,
-        svg_config_margin: Option<i32>,
-        svg_config_visible_stroke_width: Option<i32>,
-        svg_config_visible_stroke: Option<String>,
-        svg_config_hide_obscured: Option<bool>,
-        svg_config_obscured_stroke_width: Option<i32>,
-        svg_config_obscured_stroke: Option<String>,
-        svg_config_fit_lines: Option<bool>,
-    ) -> SvgConfig {
-        let fit_lines = svg_config_fit_lines.unwrap_or(true);
-
-        SvgConfig {
-            source_canvas_width,
-            source_canvas_height,
-            width: svg_config_width.unwrap_or(source_canvas_width),
-            height: svg_config_height.unwrap_or(source_canvas_height),
-            margin: if fit_lines {
-                svg_config_margin.unwrap_or(100)
-            } else {
-                0
-            },
-            visible: SvgLineConfig {
-                stroke_width: svg_config_visible_stroke_width.unwrap_or(4),
-                stroke: svg_config_visible_stroke.unwrap_or("black".to_owned()),
-            },
-            obscured: match svg_config_hide_obscured {
-                Some(false) | None => Some(SvgLineConfig {
-                    stroke_width: svg_config_obscured_stroke_width.unwrap_or(2),
-                    stroke: svg_config_obscured_stroke.unwrap_or("grey".to_owned()),
-                }),
-                Some(true) => None,
-            },
-            fit_lines,
+    /// The stroke for a class of lines, or `None` when the class isn't drawn
+    pub fn line_config(
+        &self,
+        visibility: LineVisibility,
+        kind: EdgeKind,
+    ) -> Option<&SvgLineConfig> {
+        match (visibility, kind) {
+            (LineVisibility::VISIBLE, EdgeKind::HATCH) => Some(&self.hatch),
+            (LineVisibility::VISIBLE, _) => Some(&self.visible),
+            (LineVisibility::OBSCURED, _) => self.obscured.as_ref(),
         }
     }
 }
@@ -79,106 +253,1242 @@ pub fn scale_screen_space_lines(
     screen_space_lines: &[LineSegmentCategorized],
     svg_config: &SvgConfig,
 ) -> Vec<LineSegmentCategorized> {
-    let (min_bound, max_bound) = if svg_config.fit_lines {
-        let all_points: Vec<Point2<f32>> = screen_space_lines
+    FitTransform::new(screen_space_lines, svg_config).apply_to_lines(screen_space_lines)
+}
+
+/// Maps screen space onto the svg, fitting the lines within the margins or scaling the whole
+/// source canvas when lines aren't fitted
+struct FitTransform {
+    scale: f32,
+    half_viewport: Vector2<f32>,
+    half_canvas: Vector2<f32>,
+}
+
+impl FitTransform {
+    fn new(screen_space_lines: &[LineSegmentCategorized], svg_config: &SvgConfig) -> FitTransform {
+        // an empty drawing has nothing to fit, so it keeps the source canvas
+        let (min_bound, max_bound) = if svg_config.fit_lines && !screen_space_lines.is_empty() {
+            let all_points: Vec<Point2<f32>> = screen_space_lines
+                .iter()
+                .flat_map(|seg| vec![seg.line_segment.from, seg.line_segment.to])
+                .collect();
+
+            let all_x_values: Vec<f32> = all_points.iter().map(|p| p.x).collect();
+            let max_x = all_x_values.iter().cloned().fold(std::f32::NAN, f32::max);
+            let min_x = all_x_values.iter().cloned().fold(std::f32::NAN, f32::min);
+            let all_y_values: Vec<f32> = all_points.iter().map(|p| p.y).collect();
+            let max_y = all_y_values.iter().cloned().fold(std::f32::NAN, f32::max);
+            let min_y = all_y_values.iter().cloned().fold(std::f32::NAN, f32::min);
+
+            (Vector2::new(min_x, min_y), Vector2::new(max_x, max_y))
+        } else {
+            (
+                Vector2::new(0.0, 0.0),
+                Vector2::new(
+                    svg_config.source_canvas_width as f32,
+                    svg_config.source_canvas_height as f32,
+                ),
+            )
+        };
+
+        let margin = Vector2::new(svg_config.margin as f32, svg_config.margin as f32);
+
+        let canvas = Vector2::new(svg_config.width as f32, svg_config.height as f32) - margin;
+
+        let viewport = max_bound - min_bound;
+        let half_viewport = (viewport * 0.5) + min_bound;
+        let half_canvas = (canvas * 0.5) + (margin * 0.5);
+
+        let scale = fit_scale(&canvas, &viewport);
+
+        FitTransform {
+            scale,
+            half_viewport,
+            half_canvas,
+        }
+    }
+
+    fn apply(&self, point: &Point2<f32>) -> Point2<f32> {
+        ((point - self.half_viewport) * self.scale) + self.half_canvas
+    }
+
+    fn apply_to_lines(
+        &self,
+        screen_space_lines: &[LineSegmentCategorized],
+    ) -> Vec<LineSegmentCategorized> {
+        screen_space_lines
             .iter()
-            .flat_map(|seg| vec![seg.line_segment.from, seg.line_segment.to])
-            .collect();
+            .map(|line| LineSegmentCategorized {
+                line_segment: LineSegment2 {
+                    from: self.apply(&line.line_segment.from),
+                    to: self.apply(&line.line_segment.to),
+                },
+                ..*line
+            })
+            .collect()
+    }
+}
 
-        let all_x_values: Vec<f32> = all_points.iter().map(|p| p.x).collect();
-        let max_x = all_x_values.iter().cloned().fold(std::f32::NAN, f32::max);
-        let min_x = all_x_values.iter().cloned().fold(std::f32::NAN, f32::min);
-        let all_y_values: Vec<f32> = all_points.iter().map(|p| p.y).collect();
-        let max_y = all_y_values.iter().cloned().fold(std::f32::NAN, f32::max);
-        let min_y = all_y_values.iter().cloned().fold(std::f32::NAN, f32::min);
+pub fn screen_space_lines_to_fitted_svg(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+) -> String {
+    let fit = fitted_svg_transform(screen_space_lines, svg_config);
 
-        (Vector2::new(min_x, min_y), Vector2::new(max_x, max_y))
-    } else {
-        (
-            Vector2::new(0.0, 0.0),
-            Vector2::new(
-                svg_config.source_canvas_width as f32,
-                svg_config.source_canvas_height as f32,
-            ),
-        )
+    let scaled = fit.apply_to_lines(screen_space_lines);
+    let dimensions: Vec<Dimension> = svg_config
+        .dimensions
+        .iter()
+        .map(|dimension| dimension.transformed(|point| fit.apply(point)))
+        .collect();
+    let fills: Vec<FilledFace> = svg_config
+        .fills
+        .iter()
+        .map(|face| face.transformed(|point| fit.apply(point)))
+        .collect();
+
+    line_segments_to_svg(
+        &scaled,
+        svg_config,
+        &Canvas::from_config(svg_config),
+        &dimensions,
+        &fills,
+    )
+}
+
+/// The paths `screen_space_lines_to_fitted_svg` writes, in the order it writes them
+pub fn fitted_svg_paths(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+) -> Vec<Polyline> {
+    let fit = fitted_svg_transform(screen_space_lines, svg_config);
+
+    svg_paths(&fit.apply_to_lines(screen_space_lines), svg_config)
+}
+
+fn fitted_svg_transform(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+) -> FitTransform {
+    let mut fit_config = svg_config.clone();
+
+    // fit the lines above the title block and view label
+    if let Some(sheet) = &svg_config.sheet {
+        fit_config.height -= (sheet.reserved_height_mm() * PX_PER_MM).ceil() as i32;
+    }
+
+    // and leave room on whichever sides dimensions are placed
+    let room = dimension_room(&svg_config.dimensions, &svg_config.dimension_style);
+    let before = (room.before * PX_PER_MM).map(f32::ceil);
+    let after = (room.after * PX_PER_MM).map(f32::ceil);
+    fit_config.width -= (before.x + after.x) as i32;
+    fit_config.height -= (before.y + after.y) as i32;
+
+    let mut fit = FitTransform::new(screen_space_lines, &fit_config);
+    fit.half_canvas += before;
+
+    fit
+}
+
+/// Lays the lines out on the configured page instead of fitting them to the svg config's width and
+/// height. Stroke widths and dashes are still given in css pixels, and are converted so that they
+/// print at the same size
+pub fn screen_space_lines_to_page_svg(
+    screen_space_lines: &[LineSegmentCategorized],
+    scene: &Scene,
+    svg_config: &SvgConfig,
+    page_config: &SvgPageConfig,
+) -> Result<String, MeshToSvgError> {
+    let (page_width, page_height) = page_config.page.dimensions_mm();
+    let layout = page_svg_layout(screen_space_lines, scene, svg_config, page_config)?;
+    let unit_mm = page_config.units.mm();
+    let to_page = |point: &Point2<f32>| layout.to_page(point) / unit_mm;
+    let laid_out = lay_out_lines(screen_space_lines, to_page);
+
+    let px_per_unit = PX_PER_MM * unit_mm;
+    let mut page_svg_config = svg_config.clone();
+    page_svg_config.visible = line_config_in_units(&svg_config.visible, px_per_unit);
+    page_svg_config.hatch = line_config_in_units(&svg_config.hatch, px_per_unit);
+    page_svg_config.obscured = svg_config
+        .obscured
+        .as_ref()
+        .map(|obscured| line_config_in_units(obscured, px_per_unit));
+    page_svg_config.curve_fit = svg_config
+        .curve_fit
+        .as_ref()
+        .map(|curve_fit| CurveFitConfig {
+            tolerance: curve_fit.tolerance / px_per_unit,
+            ..curve_fit.clone()
+        });
+
+    // exact scales are filled in to the title block unless it's been given one
+    if let Some(sheet) = &mut page_svg_config.sheet {
+        if let Some(title_block) = &mut sheet.title_block {
+            if title_block.scale.is_none() {
+                title_block.scale = page_config.scale.ratio_label();
+            }
+        }
+    }
+
+    let canvas = Canvas {
+        width: page_width / unit_mm,
+        height: page_height / unit_mm,
+        units: Some(page_config.units),
+        frame_inset: page_config.page.margin_mm / unit_mm,
     };
 
-    let margin = Vector2::new(svg_config.margin as f32, svg_config.margin as f32);
+    let dimensions: Vec<Dimension> = svg_config
+        .dimensions
+        .iter()
+        .map(|dimension| dimension.transformed(to_page))
+        .collect();
+    let fills: Vec<FilledFace> = svg_config
+        .fills
+        .iter()
+        .map(|face| face.transformed(to_page))
+        .collect();
+
+    Ok(line_segments_to_svg(
+        &laid_out,
+        &page_svg_config,
+        &canvas,
+        &dimensions,
+        &fills,
+    ))
+}
+
+/// The paths `screen_space_lines_to_page_svg` writes, in the order it writes them
+pub fn page_svg_paths(
+    screen_space_lines: &[LineSegmentCategorized],
+    scene: &Scene,
+    svg_config: &SvgConfig,
+    page_config: &SvgPageConfig,
+) -> Result<Vec<Polyline>, MeshToSvgError> {
+    let layout = page_svg_layout(screen_space_lines, scene, svg_config, page_config)?;
+    let unit_mm = page_config.units.mm();
+    let laid_out = lay_out_lines(screen_space_lines, |point| layout.to_page(point) / unit_mm);
 
-    let canvas = Vector2::new(svg_config.width as f32, svg_config.height as f32) - margin;
+    Ok(svg_paths(&laid_out, svg_config))
+}
 
-    let viewport = max_bound - min_bound;
-    let half_viewport = (viewport * 0.5) + min_bound;
-    let half_canvas = (canvas * 0.5) + (margin * 0.5);
+/// Places the lines within the page's drawable area, inside any frame and clear of the title
+/// block and dimensions
+fn page_svg_layout(
+    screen_space_lines: &[LineSegmentCategorized],
+    scene: &Scene,
+    svg_config: &SvgConfig,
+    page_config: &SvgPageConfig,
+) -> Result<PageLayout, MeshToSvgError> {
+    let page = &page_config.page;
+    let (page_width, page_height) = page.dimensions_mm();
 
-    let x_scale = canvas.x / viewport.x;
-    let y_scale = canvas.y / viewport.y;
-    let scale = f32::min(x_scale, y_scale);
+    let (mut area_origin, mut area_size) = match &svg_config.sheet {
+        Some(sheet) => {
+            // inside the frame, which sits on the page margin, and above the title block
+            let inset = page.margin_mm + sheet.padding_mm;
 
-    let scaled_points = screen_space_lines
+            (
+                Point2::new(inset, inset),
+                Vector2::new(
+                    page_width - inset * 2.0,
+                    page_height - inset * 2.0 - sheet.reserved_height_mm(),
+                ),
+            )
+        }
+        None => (
+            Point2::new(page.margin_mm, page.margin_mm),
+            Vector2::new(
+                page_width - page.margin_mm * 2.0,
+                page_height - page.margin_mm * 2.0,
+            ),
+        ),
+    };
+
+    // with room on whichever sides dimensions are placed
+    let room = dimension_room(&svg_config.dimensions, &svg_config.dimension_style);
+    area_origin += room.before;
+    area_size -= room.before + room.after;
+
+    PageLayout::new_in_area(
+        screen_space_lines,
+        scene,
+        &area_origin,
+        &area_size,
+        page_config.scale,
+        page_config.model_unit_mm,
+    )
+}
+
+fn lay_out_lines<F>(
+    screen_space_lines: &[LineSegmentCategorized],
+    to_page: F,
+) -> Vec<LineSegmentCategorized>
+where
+    F: Fn(&Point2<f32>) -> Point2<f32>,
+{
+    screen_space_lines
         .iter()
         .map(|line| LineSegmentCategorized {
-            visibility: line.visibility,
             line_segment: LineSegment2 {
-                from: ((&line.line_segment.from - half_viewport) * scale) + half_canvas,
-                to: ((&line.line_segment.to - half_viewport) * scale) + half_canvas,
+                from: to_page(&line.line_segment.from),
+                to: to_page(&line.line_segment.to),
             },
+            ..*line
         })
-        .collect();
+        .collect()
+}
 
-    scaled_points
+fn line_config_in_units(line_config: &SvgLineConfig, px_per_unit: f32) -> SvgLineConfig {
+    SvgLineConfig {
+        stroke_width: line_config.stroke_width / px_per_unit,
+        dash_array: line_config
+            .dash_array
+            .iter()
+            .map(|dash| dash / px_per_unit)
+            .collect(),
+        dash_offset: line_config.dash_offset / px_per_unit,
+        ..line_config.clone()
+    }
 }
 
-pub fn screen_space_lines_to_fitted_svg(
-    screen_space_lines: &[LineSegmentCategorized],
-    svg_config: &SvgConfig,
+fn line_segments_to_svg(
+    segments: &[LineSegmentCategorized],
+    config: &SvgConfig,
+    canvas: &Canvas,
+    dimensions: &[Dimension],
+    fills: &[FilledFace],
 ) -> String {
-    let scaled = scale_screen_space_lines(screen_space_lines, svg_config);
-    line_segments_to_svg(&scaled, svg_config)
-}
+    let polylines = svg_paths(segments, config);
+
+    let mut annotations = match dimensions.is_empty() {
+        true => "".to_owned(),
+        false => format!(
+            "\n{}",
+            dimension_elements(dimensions, &config.dimension_style, canvas.units_per_mm())
+        ),
+    };
 
-fn line_segments_to_svg(segments: &[LineSegmentCategorized], config: &SvgConfig) -> String {
-    let (visible, obscured) = segments.iter().partition(|&seg| match seg.visibility {
-        LineVisibility::VISIBLE => true,
-        _ => false,
+    annotations.push_str(&match &config.sheet {
+        Some(sheet) => format!(
+            "\n{}",
+            sheet_elements(
+                sheet,
+                &Point2::new(canvas.frame_inset, canvas.frame_inset),
+                &Point2::new(
+                    canvas.width - canvas.frame_inset,
+                    canvas.height - canvas.frame_inset
+                ),
+                canvas.units_per_mm(),
+                polyline_bounds(&polylines),
+            )
+        ),
+        None => "".to_owned(),
     });
 
+    // fills sit beneath all of the line work
+    let fills = match fills.is_empty() {
+        true => "".to_owned(),
+        false => format!("{}\n", fill_elements(fills, config, canvas)),
+    };
+
+    if config.group_layers {
+        return format!(
+            "<svg {size} xmlns=\"http://www.w3.org/2000/svg\" xmlns:inkscape=\"http://www.inkscape.org/namespaces/inkscape\">
+{fills}{layers}{annotations}
+</svg>",
+            size = canvas.size_attributes(),
+            fills = fills,
+            layers = grouped_layers(&polylines, config),
+            annotations = annotations,
+        );
+    }
+
+    let (visible, obscured): (Vec<Polyline>, Vec<Polyline>) = polylines
+        .into_iter()
+        .partition(|polyline| polyline.visibility == LineVisibility::VISIBLE);
+    let (hatch, visible): (Vec<Polyline>, Vec<Polyline>) = visible
+        .into_iter()
+        .partition(|polyline| polyline.kind == EdgeKind::HATCH);
+
+    // hatching goes between the obscured and visible lines so that outlines stay on top
+    let hatch = match hatch.is_empty() {
+        true => "".to_owned(),
+        false => format!("{}\n", create_path_element(&hatch, &config.hatch, config)),
+    };
+
     format!(
-        "<svg viewBox=\"0 0 {width} {height}\" xmlns=\"http://www.w3.org/2000/svg\">
-{obscured}
-{visible}
+        "<svg {size} xmlns=\"http://www.w3.org/2000/svg\">
+{fills}{obscured}
+{hatch}{visible}{annotations}
 </svg>",
-        width = &config.width,
-        height = &config.height,
-        visible = create_path_element(visible, &config.visible),
+        size = canvas.size_attributes(),
+        fills = fills,
+        hatch = hatch,
+        visible = create_path_element(&visible, &config.visible, config),
         obscured = match &config.obscured {
-            Some(conf) => create_path_element(obscured, conf),
+            Some(conf) => create_path_element(&obscured, conf, config),
             None => "".to_owned(),
+        },
+        annotations = annotations,
+    )
+}
+
+/// The lines chained into the paths that are drawn, in the order they're written
+fn svg_paths(segments: &[LineSegmentCategorized], config: &SvgConfig) -> Vec<Polyline> {
+    let mut polylines = chain_line_segments(segments);
+
+    if config.obscured.is_none() {
+        polylines.retain(|polyline| polyline.visibility == LineVisibility::VISIBLE);
+    }
+
+    // the order paths are drawn in matters when the svg is sent to a pen plotter, so they're
+    // ordered the way they're written rather than by class like the plotter formats
+    if config.optimise_path_order {
+        polylines = optimise_path_order_by(polylines, |polyline| {
+            drawing_position(polyline, config.group_layers)
+        });
+    }
+
+    polylines
+}
+
+/// Where a path is written relative to others: per object when grouped, then obscured lines,
+/// hatching and visible edges. Kinds are only written apart when layers are grouped
+fn drawing_position(polyline: &Polyline, group_layers: bool) -> (usize, Reverse<u8>, bool, u8) {
+    match group_layers {
+        true => (
+            polyline.object_id,
+            Reverse(polyline.visibility as u8),
+            polyline.kind != EdgeKind::HATCH,
+            polyline.kind as u8,
+        ),
+        false => (
+            0,
+            Reverse(polyline.visibility as u8),
+            polyline.kind != EdgeKind::HATCH,
+            0,
+        ),
+    }
+}
+
+/// A path per run of faces sharing a colour, in the back to front order they're given in. Each
+/// face is closed back to its first point so that the seam stroke runs all the way round. The fill
+/// rule is set rather than inherited, as holes in silhouettes rely on it
+fn fill_elements(faces: &[FilledFace], config: &SvgConfig, canvas: &Canvas) -> String {
+    let seam_width = FILL_SEAM_WIDTH_PX / PX_PER_MM * canvas.units_per_mm();
+    let mut paths = Vec::new();
+    let mut run_start = 0;
+
+    for end in 1..=faces.len() {
+        if end < faces.len() && faces[end].colour == faces[run_start].colour {
+            continue;
+        }
+
+        let mut encoder = PathEncoder::new(config);
+        for face in &faces[run_start..end] {
+            let mut points = face.points.clone();
+            points.push(face.points[0]);
+            encoder.push_polyline(&points);
         }
+
+        paths.push(format!(
+            "<path d=\"{path_def}\" fill=\"{colour}\" stroke=\"{colour}\" stroke-width=\"{seam_width}\" />",
+            path_def = encoder.data,
+            colour = escape_xml(&faces[run_start].colour),
+            seam_width = seam_width,
+        ));
+
+        run_start = end;
+    }
+
+    let layer_attributes = match config.group_layers {
+        true => " inkscape:groupmode=\"layer\" inkscape:label=\"fills\"",
+        false => "",
+    };
+
+    format!(
+        "<g id=\"fills\" class=\"fills\"{layer_attributes} fill-rule=\"nonzero\" stroke-linejoin=\"round\">\n{paths}\n</g>",
+        layer_attributes = layer_attributes,
+        paths = paths.join("\n"),
     )
 }
 
-fn create_path_element(lines: Vec<LineSegmentCategorized>, line_config: &SvgLineConfig) -> String {
-    let mut path_def = "".to_string();
-    let mut current: Option<Point2<f32>> = None;
+fn polyline_bounds(polylines: &[Polyline]) -> Option<(Point2<f32>, Point2<f32>)> {
+    let mut points = polylines.iter().flat_map(|polyline| polyline.points.iter());
+    let first = *points.next()?;
+
+    Some(points.fold((first, first), |(min, max), point| {
+        (
+            Point2::new(min.x.min(point.x), min.y.min(point.y)),
+            Point2::new(max.x.max(point.x), max.y.max(point.y)),
+        )
+    }))
+}
 
-    for (_i, line) in lines.iter().enumerate() {
-        let start = &line.line_segment.from;
-        let end = &line.line_segment.to;
+/// Nests the paths in inkscape layers, a `<g>` per object containing a `<g>` per visibility and
+/// edge kind. Obscured layers come first in each object so they're drawn underneath, followed by
+/// hatching so that it sits beneath the visible edges
+fn grouped_layers(polylines: &[Polyline], config: &SvgConfig) -> String {
+    let mut object_ids = Vec::new();
+    for polyline in polylines {
+        if !object_ids.contains(&polyline.object_id) {
+            object_ids.push(polyline.object_id);
+        }
+    }
+    object_ids.sort_unstable();
+
+    let mut layers = Vec::with_capacity(object_ids.len());
+
+    for object_id in object_ids {
+        let object_polylines: Vec<&Polyline> = polylines
+            .iter()
+            .filter(|polyline| polyline.object_id == object_id)
+            .collect();
 
-        match current {
-            Some(current) if relative_eq!(current, start) => {
-                path_def.push_str("L ");
+        let mut classes =
+            ordered_line_classes(object_polylines.iter().map(|p| (p.visibility, p.kind)));
+        classes.sort_by_key(|&(visibility, kind)| {
+            (Reverse(visibility as u8), kind != EdgeKind::HATCH)
+        });
+
+        let mut class_layers = Vec::with_capacity(classes.len());
+
+        for (visibility, kind) in classes {
+            let line_config = match config.line_config(visibility, kind) {
+                Some(line_config) => line_config,
+                None => continue,
+            };
+
+            let class_polylines: Vec<Polyline> = object_polylines
+                .iter()
+                .filter(|polyline| polyline.visibility == visibility && polyline.kind == kind)
+                .map(|&polyline| polyline.clone())
+                .collect();
+
+            class_layers.push(format!(
+                "<g id=\"object-{object_id}-{visibility}-{kind}\" class=\"{visibility} {kind}\" data-visibility=\"{visibility}\" data-kind=\"{kind}\" inkscape:groupmode=\"layer\" inkscape:label=\"{visibility} {kind}\" {stroke_attributes}>\n<path d=\"{path_def}\" />\n</g>",
+                object_id = object_id,
+                visibility = visibility.name(),
+                kind = kind.name(),
+                stroke_attributes = stroke_attributes(line_config),
+                path_def = path_definition(&class_polylines, config),
+            ));
+        }
+
+        layers.push(format!(
+            "<g id=\"object-{object_id}\" class=\"object\" data-object-id=\"{object_id}\" inkscape:groupmode=\"layer\" inkscape:label=\"object {object_id}\">\n{class_layers}\n</g>",
+            object_id = object_id,
+            class_layers = class_layers.join("\n"),
+        ));
+    }
+
+    let style = match config.embed_style {
+        true => format!("<style>\n{}</style>\n", style_rules(config)),
+        false => "".to_owned(),
+    };
+
+    format!("{}{}", style, layers.join("\n"))
+}
+
+/// Class rules matching the stroke attributes, so that stylesheets can override them per class
+fn style_rules(config: &SvgConfig) -> String {
+    let mut rules = vec![("visible", &config.visible)];
+    if let Some(obscured_config) = &config.obscured {
+        rules.push(("obscured", obscured_config));
+    }
+    // after the visible rule, which also matches hatch lines
+    rules.push(("hatch", &config.hatch));
+
+    rules
+        .iter()
+        .map(|(class, line_config)| {
+            format!(
+                ".{class} {{ stroke: {stroke}; stroke-width: {stroke_width}px; stroke-dasharray: {dash_array}; stroke-dashoffset: {dash_offset}px; stroke-opacity: {opacity}; fill: none; stroke-linecap: {linecap}; stroke-linejoin: {linejoin}; }}\n",
+                class = class,
+                stroke = escape_css(&line_config.stroke),
+                stroke_width = line_config.stroke_width,
+                dash_array = match line_config.dash_array.is_empty() {
+                    true => "none".to_owned(),
+                    false => dash_array_value(&line_config.dash_array),
+                },
+                dash_offset = line_config.dash_offset,
+                opacity = line_config.opacity,
+                linecap = line_config.linecap.name(),
+                linejoin = line_config.linejoin.name(),
+            )
+        })
+        .collect()
+}
+
+fn stroke_attributes(line_config: &SvgLineConfig) -> String {
+    let mut attributes = format!("stroke=\"{stroke}\" fill=\"none\" stroke-width=\"{stroke_width}\" stroke-linecap=\"{linecap}\" stroke-linejoin=\"{linejoin}\"", stroke=escape_xml(&line_config.stroke), stroke_width=line_config.stroke_width, linecap=line_config.linecap.name(), linejoin=line_config.linejoin.name());
+
+    // each polyline is a separate subpath, and svg restarts the dash pattern at every subpath
+    if !line_config.dash_array.is_empty() {
+        attributes.push_str(
+            format!(
+                " stroke-dasharray=\"{dash_array}\"",
+                dash_array = dash_array_value(&line_config.dash_array)
+            )
+            .as_str(),
+        );
+
+        if line_config.dash_offset != 0.0 {
+            attributes
+                .push_str(format!(" stroke-dashoffset=\"{}\"", line_config.dash_offset).as_str());
+        }
+    }
+
+    if line_config.opacity < 1.0 {
+        attributes.push_str(format!(" stroke-opacity=\"{}\"", line_config.opacity).as_str());
+    }
+
+    attributes
+}
+
+fn dash_array_value(dash_array: &[f32]) -> String {
+    dash_array
+        .iter()
+        .map(|dash| dash.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn create_path_element(
+    polylines: &[Polyline],
+    line_config: &SvgLineConfig,
+    config: &SvgConfig,
+) -> String {
+    format!(
+        "<path d=\"{path_def}\" {stroke_attributes} />",
+        path_def = path_definition(polylines, config),
+        stroke_attributes = stroke_attributes(line_config),
+    )
+}
+
+fn path_definition(polylines: &[Polyline], config: &SvgConfig) -> String {
+    let mut encoder = PathEncoder::new(config);
+
+    for polyline in polylines {
+        encoder.push_polyline(&polyline.points);
+    }
+
+    encoder.data
+}
+
+struct PathEncoder<'a> {
+    config: &'a SvgConfig,
+    data: String,
+    position: Point2<f32>,
+    last_number_has_point: bool,
+}
+
+impl<'a> PathEncoder<'a> {
+    fn new(config: &'a SvgConfig) -> PathEncoder<'a> {
+        PathEncoder {
+            config,
+            data: "".to_owned(),
+            position: Point2::origin(),
+            last_number_has_point: false,
+        }
+    }
+
+    fn push_polyline(&mut self, points: &[Point2<f32>]) {
+        if points.len() < 2 {
+            return;
+        }
+
+        // curves are fitted to the exact points, and only their output is rounded
+        let segments = match &self.config.curve_fit {
+            Some(curve_fit) => fit_curves(points, curve_fit),
+            None => points[1..]
+                .iter()
+                .map(|&point| PathSegment::Line(point))
+                .collect(),
+        };
+
+        let start = self.round(&points[0]);
+        let mut end = start;
+        let mut rounded = Vec::with_capacity(segments.len());
+
+        for segment in segments {
+            let segment = self.round_segment(&segment);
+
+            // segments that round to nothing only add bytes
+            if *segment.end() == end {
+                continue;
+            }
+
+            end = *segment.end();
+            rounded.push(segment);
+        }
+
+        if rounded.is_empty() {
+            return;
+        }
+
+        self.push_command('M');
+        self.push_point(&start);
+
+        // points following a move are implicitly line-to commands, which compact paths rely on to
+        // leave out the command entirely
+        let mut implicit = match self.config.compact_paths {
+            true => Some('L'),
+            false => None,
+        };
+
+        for segment in &rounded {
+            let command = match segment {
+                PathSegment::Line(_) => 'L',
+                PathSegment::Arc { .. } => 'A',
+                PathSegment::Cubic { .. } => 'C',
+            };
+
+            if implicit != Some(command) {
+                self.push_command(command);
+                implicit = Some(command);
             }
-            _ => {
-                path_def.push_str(format!("M {x} {y} ", x = start.x, y = start.y).as_str());
+
+            match segment {
+                PathSegment::Line(to) => self.push_point(to),
+                PathSegment::Arc {
+                    to, radius, sweep, ..
+                } => {
+                    self.push_number(*radius);
+                    self.push_number(*radius);
+                    // x axis rotation, which doesn't matter for circles
+                    self.push_number(0.0);
+                    self.push_flag(sweep.abs() > std::f32::consts::PI);
+                    self.push_flag(*sweep > 0.0);
+                    self.push_point(to);
+                }
+                PathSegment::Cubic {
+                    control_1,
+                    control_2,
+                    to,
+                } => {
+                    self.push_control_point(control_1);
+                    self.push_control_point(control_2);
+                    self.push_point(to);
+                }
+            }
+        }
+    }
+
+    fn round_segment(&self, segment: &PathSegment) -> PathSegment {
+        match *segment {
+            PathSegment::Line(to) => PathSegment::Line(self.round(&to)),
+            PathSegment::Arc {
+                to,
+                centre,
+                radius,
+                sweep,
+            } => PathSegment::Arc {
+                to: self.round(&to),
+                centre,
+                radius,
+                sweep,
+            },
+            PathSegment::Cubic {
+                control_1,
+                control_2,
+                to,
+            } => PathSegment::Cubic {
+                control_1: self.round(&control_1),
+                control_2: self.round(&control_2),
+                to: self.round(&to),
+            },
+        }
+    }
+
+    fn round(&self, point: &Point2<f32>) -> Point2<f32> {
+        match self.config.precision {
+            Some(precision) => {
+                let factor = 10f32.powi(precision as i32);
+                Point2::new(
+                    (point.x * factor).round() / factor,
+                    (point.y * factor).round() / factor,
+                )
             }
+            None => *point,
+        }
+    }
+
+    fn push_point(&mut self, point: &Point2<f32>) {
+        let output = match self.config.relative_commands {
+            true => Point2::from(point - self.position),
+            false => *point,
+        };
+
+        self.push_number(output.x);
+        self.push_number(output.y);
+        self.position = *point;
+    }
+
+    /// Control points are relative to the start of their curve, like its end point
+    fn push_control_point(&mut self, point: &Point2<f32>) {
+        let output = match self.config.relative_commands {
+            true => Point2::from(point - self.position),
+            false => *point,
+        };
+
+        self.push_number(output.x);
+        self.push_number(output.y);
+    }
+
+    fn push_flag(&mut self, flag: bool) {
+        self.push_number(if flag { 1.0 } else { 0.0 });
+    }
+
+    /// Pushes the command, in lowercase for relative commands
+    fn push_command(&mut self, command: char) {
+        if !self.config.compact_paths && !self.data.is_empty() {
+            self.data.push(' ');
+        }
+
+        match self.config.relative_commands {
+            true => self.data.push(command.to_ascii_lowercase()),
+            false => self.data.push(command),
+        }
+    }
+
+    fn push_number(&mut self, value: f32) {
+        let mut number = match self.config.precision {
+            Some(precision) => {
+                let fixed = format!("{:.*}", precision as usize, value);
+                match fixed.contains('.') {
+                    true => fixed.trim_end_matches('0').trim_end_matches('.').to_owned(),
+                    false => fixed,
+                }
+            }
+            None => value.to_string(),
+        };
+
+        if number == "-0" {
+            number = "0".to_owned();
+        }
+
+        if self.config.compact_paths {
+            if number.starts_with("0.") {
+                number.remove(0);
+            } else if number.starts_with("-0.") {
+                number.remove(1);
+            }
+
+            // a sign or a second decimal point already separates numbers
+            let separated = match self.data.chars().last() {
+                Some(last) if last.is_ascii_alphabetic() => true,
+                _ => {
+                    number.starts_with('-')
+                        || (number.starts_with('.') && self.last_number_has_point)
+                }
+            };
+
+            if !separated {
+                self.data.push(' ');
+            }
+        } else {
+            self.data.push(' ');
+        }
+
+        self.last_number_has_point = number.contains('.');
+        self.data.push_str(&number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::*;
+    use crate::lines::LineSegment3;
+
+    fn segment(
+        from: (f32, f32),
+        to: (f32, f32),
+        visibility: LineVisibility,
+        kind: EdgeKind,
+    ) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility,
+            kind,
+            object_id: 0,
+        }
+    }
+
+    fn visible(from: (f32, f32), to: (f32, f32)) -> LineSegmentCategorized {
+        segment(from, to, LineVisibility::VISIBLE, EdgeKind::SILHOUETTE)
+    }
+
+    #[test]
+    fn fits_lines_within_the_margins() {
+        let lines = vec![visible((10.0, 10.0), (30.0, 20.0))];
+
+        let fitted = scale_screen_space_lines(&lines, &SvgConfig::new_default(300, 200));
+
+        // the 200 wide canvas inside the margins is the tighter fit
+        assert_relative_eq!(fitted[0].line_segment.from, Point2::new(50.0, 50.0));
+        assert_relative_eq!(fitted[0].line_segment.to, Point2::new(250.0, 150.0));
+    }
+
+    #[test]
+    fn fits_degenerate_drawings() {
+        let config = SvgConfig::new_default(300, 200);
+        let fit = |lines: &[LineSegmentCategorized]| FitTransform::new(lines, &config);
+
+        // an empty drawing keeps the source canvas instead of dividing by nothing
+        assert!(fit(&[]).scale.is_finite());
+
+        // a horizontal line is only fitted along x
+        let horizontal = fit(&[visible((10.0, 5.0), (30.0, 5.0))]);
+        assert_relative_eq!(horizontal.scale, 10.0);
+        assert_relative_eq!(
+            horizontal.apply(&Point2::new(10.0, 5.0)),
+            Point2::new(50.0, 100.0)
+        );
+
+        // and a single point is centred unscaled
+        let point = fit(&[visible((5.0, 5.0), (5.0, 5.0))]);
+        assert_relative_eq!(point.scale, 1.0);
+        assert_relative_eq!(
+            point.apply(&Point2::new(5.0, 5.0)),
+            Point2::new(150.0, 100.0)
+        );
+    }
+
+    fn grouped_config() -> SvgConfig {
+        SvgConfig {
+            group_layers: true,
+            embed_style: true,
+            fit_lines: false,
+            margin: 0,
+            ..SvgConfig::new_default(20, 20)
+        }
+    }
+
+    // a visible silhouette on object 1, and hatching and an obscured crease on object 0
+    fn objects() -> Vec<LineSegmentCategorized> {
+        let mut lines = vec![
+            segment(
+                (0.0, 0.0),
+                (10.0, 0.0),
+                LineVisibility::VISIBLE,
+                EdgeKind::SILHOUETTE,
+            ),
+            segment(
+                (0.0, 5.0),
+                (10.0, 5.0),
+                LineVisibility::VISIBLE,
+                EdgeKind::HATCH,
+            ),
+            segment(
+                (0.0, 10.0),
+                (10.0, 10.0),
+                LineVisibility::OBSCURED,
+                EdgeKind::CREASE,
+            ),
+        ];
+        lines[0].object_id = 1;
+
+        lines
+    }
+
+    #[test]
+    fn groups_layers_by_object_and_class() {
+        let svg = screen_space_lines_to_fitted_svg(&objects(), &grouped_config());
+        let position = |text: &str| svg.find(text).unwrap();
+
+        // objects in order, each with obscured lines beneath hatching beneath visible lines
+        assert!(
+            position("<g id=\"object-0\" class=\"object\" data-object-id=\"0\"")
+                < position("<g id=\"object-1\"")
+        );
+        assert!(
+            position("id=\"object-0-obscured-crease\"") < position("id=\"object-0-visible-hatch\"")
+        );
+        assert!(position("id=\"object-0-visible-hatch\"") < position("id=\"object-1\""));
+
+        assert!(svg.contains(
+            "<g id=\"object-1-visible-silhouette\" class=\"visible silhouette\" data-visibility=\"visible\" data-kind=\"silhouette\" inkscape:groupmode=\"layer\" inkscape:label=\"visible silhouette\" stroke=\"black\" fill=\"none\" stroke-width=\"4\" stroke-linecap=\"round\" stroke-linejoin=\"round\">\n<path d=\"M 0 0 L 10 0\" />\n</g>"
+        ));
+        assert!(svg.contains("class=\"obscured crease\""));
+        assert!(svg.contains("class=\"visible hatch\""));
+        assert_eq!(svg.matches("inkscape:groupmode=\"layer\"").count(), 5);
+    }
+
+    #[test]
+    fn embeds_a_rule_per_class() {
+        let svg = screen_space_lines_to_fitted_svg(&objects(), &grouped_config());
+        let style = &svg[svg.find("<style>").unwrap() + 7..svg.find("</style>").unwrap()];
+
+        let selectors: Vec<&str> = style
+            .lines()
+            .skip(1)
+            .map(|rule| rule.split(" {").next().unwrap())
+            .collect();
+        // hatch lines are visible too, so their rule comes last to win
+        assert_eq!(selectors, [".visible", ".obscured", ".hatch"]);
+        assert!(
+            style.contains(".visible { stroke: black; stroke-width: 4px; stroke-dasharray: none;")
+        );
+    }
+
+    #[test]
+    fn escapes_strokes_in_style_rules() {
+        let mut config = grouped_config();
+        config.visible.stroke = "red; } svg { display: none".to_owned();
+        config.hatch.stroke = "</style><script>".to_owned();
+
+        let svg = screen_space_lines_to_fitted_svg(&objects(), &config);
+        let style = &svg[svg.find("<style>").unwrap() + 7..svg.find("</style>").unwrap()];
+
+        assert_eq!(style.matches('{').count(), 3);
+        assert_eq!(style.matches('}').count(), 3);
+        assert!(!style.contains('<'));
+        assert!(style.contains(".visible { stroke: red\\3b  \\7d  svg \\7b  display\\3a  none;"));
+        // css colours are left alone
+        assert_eq!(escape_css("rgb(0 0 0 / 50%)"), "rgb(0 0 0 / 50%)");
+        assert_eq!(escape_css("#fff"), "#fff");
+    }
+
+    #[test]
+    fn keeps_current_colour_strokes() {
+        let mut config = grouped_config();
+        config.visible.stroke = "currentColor".to_owned();
+
+        let svg = screen_space_lines_to_fitted_svg(&objects(), &config);
+        assert!(svg.contains(".visible { stroke: currentColor;"));
+
+        config.embed_style = false;
+        let svg = screen_space_lines_to_fitted_svg(&objects(), &config);
+        assert!(svg.contains("stroke=\"currentColor\""));
+    }
+
+    fn dashed_obscured() -> SvgLineConfig {
+        SvgLineConfig {
+            dash_offset: 2.0,
+            opacity: 0.5,
+            linecap: LineCap::Butt,
+            ..SvgLineConfig::new(
+                1.5,
+                "grey".to_owned(),
+                SvgLineConfig::iso_hidden_dash_array(1.5),
+            )
         }
+    }
+
+    #[test]
+    fn formats_dash_arrays() {
+        assert_eq!(dash_array_value(&[3.0, 0.75, 2.5]), "3 0.75 2.5");
+        assert_eq!(
+            dash_array_value(&SvgLineConfig::iso_hidden_dash_array(0.5)),
+            "6 1.5"
+        );
+    }
+
+    #[test]
+    fn strokes_each_visibility_with_its_config() {
+        let config = SvgConfig {
+            fit_lines: false,
+            margin: 0,
+            obscured: Some(dashed_obscured()),
+            ..SvgConfig::new_default(20, 20)
+        };
+
+        let svg = screen_space_lines_to_fitted_svg(&objects(), &config);
+
+        // obscured lines are drawn first, and solid lines leave out the dash attributes
+        assert_eq!(
+            svg.lines().skip(1).collect::<Vec<&str>>(),
+            [
+                "<path d=\"M 0 10 L 10 10\" stroke=\"grey\" fill=\"none\" stroke-width=\"1.5\" stroke-linecap=\"butt\" stroke-linejoin=\"round\" stroke-dasharray=\"18 4.5\" stroke-dashoffset=\"2\" stroke-opacity=\"0.5\" />",
+                "<path d=\"M 0 5 L 10 5\" stroke=\"black\" fill=\"none\" stroke-width=\"1\" stroke-linecap=\"round\" stroke-linejoin=\"round\" />",
+                "<path d=\"M 0 0 L 10 0\" stroke=\"black\" fill=\"none\" stroke-width=\"4\" stroke-linecap=\"round\" stroke-linejoin=\"round\" />",
+                "</svg>",
+            ]
+        );
+    }
+
+    #[test]
+    fn only_offsets_dashed_lines() {
+        let solid = SvgLineConfig {
+            dash_offset: 2.0,
+            linejoin: LineJoin::Bevel,
+            ..SvgLineConfig::new(1.0, "red".to_owned(), vec![])
+        };
+
+        assert_eq!(
+            stroke_attributes(&solid),
+            "stroke=\"red\" fill=\"none\" stroke-width=\"1\" stroke-linecap=\"round\" stroke-linejoin=\"bevel\""
+        );
+    }
 
-        path_def.push_str(format!("{x} {y}", x = end.x, y = end.y).as_str());
+    #[test]
+    fn leaves_out_obscured_lines_without_a_config() {
+        let config = SvgConfig {
+            fit_lines: false,
+            margin: 0,
+            obscured: None,
+            ..SvgConfig::new_default(20, 20)
+        };
 
-        current = Some(end.to_owned());
+        let svg = screen_space_lines_to_fitted_svg(&objects(), &config);
+
+        assert!(!svg.contains("M 0 10"));
+        assert_eq!(svg.matches("<path").count(), 2);
+    }
+
+    #[test]
+    fn embeds_dashes_in_style_rules() {
+        let config = SvgConfig {
+            obscured: Some(dashed_obscured()),
+            ..grouped_config()
+        };
+
+        let svg = screen_space_lines_to_fitted_svg(&objects(), &config);
+
+        assert!(svg.contains(".obscured { stroke: grey; stroke-width: 1.5px; stroke-dasharray: 18 4.5; stroke-dashoffset: 2px; stroke-opacity: 0.5; fill: none; stroke-linecap: butt; stroke-linejoin: round; }"));
+    }
+
+    fn path_config(
+        precision: Option<u32>,
+        relative_commands: bool,
+        compact_paths: bool,
+    ) -> SvgConfig {
+        SvgConfig {
+            precision,
+            relative_commands,
+            compact_paths,
+            ..SvgConfig::new_default(20, 20)
+        }
     }
 
-    format!("<path d=\"{path_def}\" stroke=\"{stroke}\" fill=\"none\" stroke-width=\"{stroke_width}\" stroke-linecap=\"round\" stroke-linejoin=\"round\" />", path_def=path_def, stroke=line_config.stroke, stroke_width=line_config.stroke_width)
+    fn encode(config: &SvgConfig, polylines: &[&[(f32, f32)]]) -> String {
+        let mut encoder = PathEncoder::new(config);
+
+        for points in polylines {
+            let points: Vec<Point2<f32>> = points.iter().map(|&(x, y)| Point2::new(x, y)).collect();
+            encoder.push_polyline(&points);
+        }
+
+        encoder.data
+    }
+
+    #[test]
+    fn encodes_fitted_arcs() {
+        let config = SvgConfig {
+            curve_fit: Some(CurveFitConfig::new_default()),
+            ..path_config(Some(2), false, false)
+        };
+        let quarter: Vec<(f32, f32)> = (0..=16)
+            .map(|i| {
+                let angle = i as f32 / 16.0 * std::f32::consts::FRAC_PI_2;
+                (10.0 * angle.cos(), 10.0 * angle.sin())
+            })
+            .collect();
+
+        assert_eq!(encode(&config, &[&quarter]), "M 10 0 A 10 10 0 0 1 0 10");
+    }
+
+    #[test]
+    fn encodes_rounded_paths() {
+        let config = path_config(Some(1), false, false);
+
+        // negative values that round to zero lose their sign
+        assert_eq!(
+            encode(&config, &[&[(-0.04, 5.0), (2.25, -0.01)]]),
+            "M 0 5 L 2.3 0"
+        );
+        // segments that round to nothing are dropped, along with whole polylines
+        assert_eq!(
+            encode(
+                &config,
+                &[
+                    &[(0.0, 0.0), (0.01, 0.0), (1.0, 0.0)],
+                    &[(5.0, 5.0), (5.02, 5.0)]
+                ]
+            ),
+            "M 0 0 L 1 0"
+        );
+    }
+
+    #[test]
+    fn drops_separators_in_compact_paths() {
+        let config = path_config(Some(2), false, true);
+
+        // a sign or a second decimal point starts the next number, and lines after the move are
+        // implicit
+        assert_eq!(
+            encode(&config, &[&[(0.5, -0.5), (1.25, 0.75), (-2.0, 3.0)]]),
+            "M.5-.5 1.25.75-2 3"
+        );
+        assert_eq!(encode(&config, &[&[(0.0, 0.5), (1.5, 2.0)]]), "M0 .5 1.5 2");
+    }
+
+    #[test]
+    fn moves_relative_to_the_end_of_the_last_polyline() {
+        let polylines: &[&[(f32, f32)]] =
+            &[&[(10.0, 10.0), (20.0, 10.0)], &[(30.0, 30.0), (30.0, 40.0)]];
+
+        assert_eq!(
+            encode(&path_config(None, true, false), polylines),
+            "m 10 10 l 10 0 m 10 20 l 0 10"
+        );
+        assert_eq!(
+            encode(&path_config(None, true, true), polylines),
+            "m10 10 10 0m10 20 0 10"
+        );
+    }
+
+    #[test]
+    fn closes_polylines_back_to_their_start() {
+        let square: &[&[(f32, f32)]] = &[&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+        ]];
+
+        assert_eq!(
+            encode(&path_config(None, false, false), square),
+            "M 0 0 L 10 0 10 10 0 10 0 0"
+        );
+        assert_eq!(
+            encode(&path_config(None, true, true), square),
+            "m0 0 10 0 0 10-10 0 0-10"
+        );
+    }
+
+    #[test]
+    fn fills_faces_with_an_explicit_fill_rule() {
+        let square = FilledFace {
+            points: vec![
+                Point2::new(0.0, 0.0),
+                Point2::new(10.0, 0.0),
+                Point2::new(10.0, 10.0),
+                Point2::new(0.0, 10.0),
+            ],
+            colour: "#f0f0f0".to_owned(),
+        };
+        let config = SvgConfig {
+            fit_lines: false,
+            margin: 0,
+            fills: vec![square.clone(), square],
+            ..SvgConfig::new_default(20, 20)
+        };
+
+        let svg = screen_space_lines_to_fitted_svg(&[], &config);
+
+        // faces sharing a colour share a path, each closed back to its start
+        assert!(svg.contains("<g id=\"fills\" class=\"fills\" fill-rule=\"nonzero\" stroke-linejoin=\"round\">\n<path d=\"M 0 0 L 10 0 10 10 0 10 0 0 M 0 0 L 10 0 10 10 0 10 0 0\" fill=\"#f0f0f0\" stroke=\"#f0f0f0\""));
+    }
 }
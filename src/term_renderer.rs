@@ -0,0 +1,401 @@
+use na::Point2;
+
+use crate::lines::{EdgeKind, LineSegmentCategorized, LineVisibility};
+use crate::svg_renderer::{scale_screen_space_lines, SvgConfig};
+
+const BRAILLE_BASE: u32 = 0x2800;
+
+// braille dot bits, indexed by [y][x] within the 2x4 cell
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+const HORIZONTAL: u8 = 1;
+const VERTICAL: u8 = 2;
+const FALLING: u8 = 4;
+const RISING: u8 = 8;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TermCharset {
+    /// 2x4 dots per character
+    Braille,
+    /// one box drawing character per cell, picked by line direction
+    BoxDrawing,
+}
+
+/// ANSI SGR colour codes, e.g. `"1;37"` for bold white or `"90"` for bright black
+pub struct TermColours {
+    pub silhouette: String,
+    pub crease: String,
+    pub wireframe: String,
+    pub feature: String,
+    pub hatch: String,
+    pub obscured: String,
+}
+
+pub struct TermConfig {
+    pub charset: TermCharset,
+    pub colours: Option<TermColours>,
+}
+
+impl TermConfig {
+    pub fn new_default() -> TermConfig {
+        TermConfig {
+            charset: TermCharset::Braille,
+            colours: None,
+        }
+    }
+}
+
+impl TermColours {
+    pub fn new_default() -> TermColours {
+        TermColours {
+            silhouette: "1;37".to_owned(),
+            crease: "36".to_owned(),
+            wireframe: "33".to_owned(),
+            feature: "34".to_owned(),
+            hatch: "37".to_owned(),
+            obscured: "90".to_owned(),
+        }
+    }
+
+    fn code_for(&self, visibility: LineVisibility, kind: EdgeKind) -> &str {
+        match (visibility, kind) {
+            (LineVisibility::OBSCURED, _) => &self.obscured,
+            (LineVisibility::VISIBLE, EdgeKind::SILHOUETTE) => &self.silhouette,
+            (LineVisibility::VISIBLE, EdgeKind::CREASE) => &self.crease,
+            (LineVisibility::VISIBLE, EdgeKind::WIREFRAME) => &self.wireframe,
+            (LineVisibility::VISIBLE, EdgeKind::FEATURE) => &self.feature,
+            (LineVisibility::VISIBLE, EdgeKind::HATCH) => &self.hatch,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Cell {
+    pattern: u32,
+    // the most important line class in the cell decides its colour
+    class: Option<(LineVisibility, EdgeKind)>,
+}
+
+/// Renders the lines to a string of terminal characters. The svg config width and height are
+/// treated as dots, so for braille a terminal of `c` columns and `r` rows fits `2c` x `4r`.
+/// Obscured lines are only drawn when the svg config has an obscured line config
+pub fn screen_space_lines_to_terminal(
+    screen_space_lines: &[LineSegmentCategorized],
+    svg_config: &SvgConfig,
+    term_config: &TermConfig,
+) -> String {
+    let (cell_width, cell_height) = match term_config.charset {
+        TermCharset::Braille => (2, 4),
+        TermCharset::BoxDrawing => (1, 1),
+    };
+
+    let columns = (svg_config.width.max(0) as usize).div_ceil(cell_width);
+    let rows = (svg_config.height.max(0) as usize).div_ceil(cell_height);
+
+    let mut cells = vec![
+        Cell {
+            pattern: 0,
+            class: None,
+        };
+        columns * rows
+    ];
+
+    let mut lines = scale_screen_space_lines(screen_space_lines, svg_config);
+    if svg_config.obscured.is_none() {
+        lines.retain(|line| line.visibility == LineVisibility::VISIBLE);
+    }
+
+    // lowest priority first so visible lines end up on top
+    lines.sort_by_key(|line| priority(line.visibility, line.kind));
+
+    for line in &lines {
+        let pattern = match term_config.charset {
+            TermCharset::Braille => None,
+            TermCharset::BoxDrawing => Some(direction_pattern(
+                &line.line_segment.from,
+                &line.line_segment.to,
+            )),
+        };
+
+        for (x, y) in dots_along(&line.line_segment.from, &line.line_segment.to) {
+            if x < 0 || y < 0 {
+                continue;
+            }
+
+            let (x, y) = (x as usize, y as usize);
+            let (column, row) = (x / cell_width, y / cell_height);
+
+            if column >= columns || row >= rows {
+                continue;
+            }
+
+            let cell = &mut cells[row * columns + column];
+            cell.pattern |= match pattern {
+                Some(direction) => direction as u32,
+                None => BRAILLE_DOTS[y % cell_height][x % cell_width],
+            };
+            cell.class = Some((line.visibility, line.kind));
+        }
+    }
+
+    let mut output = Vec::with_capacity(rows);
+
+    for row in cells.chunks(columns.max(1)) {
+        output.push(render_row(row, term_config));
+    }
+
+    output.join("\n")
+}
+
+fn render_row(row: &[Cell], config: &TermConfig) -> String {
+    let mut line = String::new();
+    let mut current_code: Option<&str> = None;
+
+    for cell in row {
+        let character = match (cell.pattern, config.charset) {
+            (0, _) => ' ',
+            (pattern, TermCharset::Braille) => {
+                std::char::from_u32(BRAILLE_BASE + pattern).unwrap_or(' ')
+            }
+            (pattern, TermCharset::BoxDrawing) => box_character(pattern as u8),
+        };
+
+        if let (Some(colours), Some((visibility, kind))) = (&config.colours, cell.class) {
+            let code = colours.code_for(visibility, kind);
+
+            if current_code != Some(code) {
+                line.push_str(format!("\x1b[{}m", code).as_str());
+                current_code = Some(code);
+            }
+        }
+
+        line.push(character);
+    }
+
+    let mut line = line.trim_end().to_owned();
+
+    if current_code.is_some() {
+        line.push_str("\x1b[0m");
+    }
+
+    line
+}
+
+fn priority(visibility: LineVisibility, kind: EdgeKind) -> u8 {
+    let kind_priority = match kind {
+        EdgeKind::HATCH => 0,
+        EdgeKind::WIREFRAME => 1,
+        EdgeKind::FEATURE => 2,
+        EdgeKind::CREASE => 3,
+        EdgeKind::SILHOUETTE => 4,
+    };
+
+    match visibility {
+        LineVisibility::OBSCURED => kind_priority,
+        LineVisibility::VISIBLE => 5 + kind_priority,
+    }
+}
+
+fn direction_pattern(from: &Point2<f32>, to: &Point2<f32>) -> u8 {
+    let dx = (to.x - from.x).abs();
+    let dy = (to.y - from.y).abs();
+
+    if dx > dy * 2.0 {
+        HORIZONTAL
+    } else if dy > dx * 2.0 {
+        VERTICAL
+    } else if (to.x - from.x) * (to.y - from.y) > 0.0 {
+        // screen space is y down, so increasing x and y falls to the right
+        FALLING
+    } else {
+        RISING
+    }
+}
+
+fn box_character(pattern: u8) -> char {
+    if pattern & (HORIZONTAL | VERTICAL) == HORIZONTAL | VERTICAL {
+        '┼'
+    } else if pattern & (FALLING | RISING) == FALLING | RISING {
+        '╳'
+    } else if pattern & HORIZONTAL != 0 {
+        '─'
+    } else if pattern & VERTICAL != 0 {
+        '│'
+    } else if pattern & FALLING != 0 {
+        '╲'
+    } else {
+        '╱'
+    }
+}
+
+/// Bresenham's line between the dots containing each end point
+fn dots_along(from: &Point2<f32>, to: &Point2<f32>) -> Vec<(i64, i64)> {
+    let (mut x, mut y) = (from.x.floor() as i64, from.y.floor() as i64);
+    let (end_x, end_y) = (to.x.floor() as i64, to.y.floor() as i64);
+
+    let dx = (end_x - x).abs();
+    let dy = -(end_y - y).abs();
+    let step_x = if x < end_x { 1 } else { -1 };
+    let step_y = if y < end_y { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    let mut dots = Vec::with_capacity((dx - dy) as usize + 1);
+
+    loop {
+        dots.push((x, y));
+
+        if x == end_x && y == end_y {
+            break;
+        }
+
+        let doubled = error * 2;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+
+    dots
+}
+
+#[cfg(test)]
+mod tests {
+    use na::Point3;
+
+    use super::*;
+    use crate::lines::{LineSegment2, LineSegment3};
+
+    fn segment(
+        from: (f32, f32),
+        to: (f32, f32),
+        visibility: LineVisibility,
+        kind: EdgeKind,
+    ) -> LineSegmentCategorized {
+        LineSegmentCategorized {
+            line_segment: LineSegment2 {
+                from: Point2::new(from.0, from.1),
+                to: Point2::new(to.0, to.1),
+            },
+            mesh_space: LineSegment3 {
+                from: Point3::new(from.0, from.1, 0.0),
+                to: Point3::new(to.0, to.1, 0.0),
+            },
+            visibility,
+            kind,
+            object_id: 0,
+        }
+    }
+
+    // lines drawn where they are, one canvas pixel to a dot
+    fn unfitted_config(width: i32, height: i32) -> SvgConfig {
+        SvgConfig {
+            margin: 0,
+            fit_lines: false,
+            ..SvgConfig::new_default(width, height)
+        }
+    }
+
+    fn rectangle(
+        left: f32,
+        top: f32,
+        right: f32,
+        bottom: f32,
+        visibility: LineVisibility,
+    ) -> Vec<LineSegmentCategorized> {
+        vec![
+            segment((left, top), (right, top), visibility, EdgeKind::SILHOUETTE),
+            segment(
+                (right, top),
+                (right, bottom),
+                visibility,
+                EdgeKind::SILHOUETTE,
+            ),
+            segment(
+                (right, bottom),
+                (left, bottom),
+                visibility,
+                EdgeKind::SILHOUETTE,
+            ),
+            segment(
+                (left, bottom),
+                (left, top),
+                visibility,
+                EdgeKind::SILHOUETTE,
+            ),
+        ]
+    }
+
+    #[test]
+    fn draws_braille() {
+        let lines = rectangle(0.0, 0.0, 7.0, 7.0, LineVisibility::VISIBLE);
+
+        let output = screen_space_lines_to_terminal(
+            &lines,
+            &unfitted_config(8, 8),
+            &TermConfig::new_default(),
+        );
+
+        assert_eq!(output, "⡏⠉⠉⢹\n⣇⣀⣀⣸");
+    }
+
+    #[test]
+    fn draws_box_drawing() {
+        let mut lines = rectangle(0.0, 0.0, 5.0, 2.0, LineVisibility::VISIBLE);
+        lines.push(segment(
+            (0.0, 0.0),
+            (2.0, 2.0),
+            LineVisibility::VISIBLE,
+            EdgeKind::CREASE,
+        ));
+        let term_config = TermConfig {
+            charset: TermCharset::BoxDrawing,
+            ..TermConfig::new_default()
+        };
+
+        let output = screen_space_lines_to_terminal(&lines, &unfitted_config(6, 3), &term_config);
+
+        assert_eq!(output, "┼────┼\n│╲   │\n┼────┼");
+    }
+
+    #[test]
+    fn leaves_out_obscured_lines_unless_configured() {
+        let mut lines = rectangle(0.0, 0.0, 3.0, 3.0, LineVisibility::VISIBLE);
+        lines.append(&mut rectangle(4.0, 0.0, 7.0, 3.0, LineVisibility::OBSCURED));
+        let mut svg_config = unfitted_config(8, 4);
+
+        let with_obscured =
+            screen_space_lines_to_terminal(&lines, &svg_config, &TermConfig::new_default());
+        svg_config.obscured = None;
+        let without_obscured =
+            screen_space_lines_to_terminal(&lines, &svg_config, &TermConfig::new_default());
+
+        assert_eq!(with_obscured, "⣏⣹⣏⣹");
+        assert_eq!(without_obscured, "⣏⣹");
+    }
+
+    #[test]
+    fn colours_by_line_class() {
+        let mut lines = rectangle(0.0, 0.0, 3.0, 3.0, LineVisibility::VISIBLE);
+        lines.append(&mut rectangle(4.0, 0.0, 7.0, 3.0, LineVisibility::OBSCURED));
+        let term_config = TermConfig {
+            colours: Some(TermColours::new_default()),
+            ..TermConfig::new_default()
+        };
+
+        let output = screen_space_lines_to_terminal(&lines, &unfitted_config(8, 4), &term_config);
+
+        assert_eq!(output, "\x1b[1;37m⣏⣹\x1b[90m⣏⣹\x1b[0m");
+    }
+
+    #[test]
+    fn draws_nothing_without_lines() {
+        let output =
+            screen_space_lines_to_terminal(&[], &unfitted_config(4, 8), &TermConfig::new_default());
+
+        assert_eq!(output, "\n");
+    }
+}
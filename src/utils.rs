@@ -9,6 +9,28 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+/// Escapes text for use in xml content and attribute values
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes text for use as a css declaration value, so that it can't end the declaration or the
+/// rule it's in. Anything that can't be part of a colour or length is written as a hex escape,
+/// which also leaves nothing to escape for xml
+pub fn escape_css(text: &str) -> String {
+    text.chars()
+        .map(|character| {
+            match character.is_ascii_alphanumeric() || " #%.,()-+/_".contains(character) {
+                true => character.to_string(),
+                false => format!("\\{:x} ", character as u32),
+            }
+        })
+        .collect()
+}
+
 #[allow(unused_macros)]
 #[macro_use]
 #[cfg(feature = "console_log")]
@@ -0,0 +1,719 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use na::{Point3, Vector3};
+use serde::Serialize;
+
+use crate::mesh::{bounds, weld_points, WELD_TOLERANCE};
+
+/// What's wrong with the buffers for a mesh or wireframe, found before they're made into one so
+/// that problems can be pointed out instead of drawn. Triangles and lines are numbered by their
+/// place in the indices, and vertices by theirs in the vertices
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct MeshReport {
+    /// `triangle` for a mesh, `line` for a wireframe
+    pub primitive: &'static str,
+    pub vertex_count: usize,
+    pub primitive_count: usize,
+    /// values after the last whole vertex
+    pub trailing_values: usize,
+    /// vertices with a NaN or infinite coordinate
+    pub non_finite_vertices: Vec<usize>,
+    /// length of the normals when it isn't three values for every vertex
+    pub normal_values: Option<usize>,
+    /// normals with a NaN or infinite value
+    pub non_finite_normals: Vec<usize>,
+    /// indices after the last whole triangle or line
+    pub trailing_indices: usize,
+    /// places in the indices of vertices that don't exist
+    pub out_of_range_indices: Vec<usize>,
+    /// triangles thinner than the distance vertices are welded at, or lines shorter than it
+    pub degenerate: Vec<usize>,
+    /// triangles or lines between the same vertices as an earlier one, whichever way around
+    pub duplicates: Vec<usize>,
+    /// pairs of vertices with more than two triangles along the edge between them
+    pub non_manifold_edges: Vec<[usize; 2]>,
+    /// pairs of vertices where the triangles either side run the same way along the edge, so
+    /// that one of them faces the other way to the other
+    pub inconsistent_winding: Vec<[usize; 2]>,
+}
+
+/// A triangle or line that can be drawn, by its vertices and by the welded points they became
+struct Primitive {
+    vertices: Vec<usize>,
+    welded: Vec<usize>,
+}
+
+struct CheckedPrimitives {
+    report: MeshReport,
+    points: Vec<Point3<f32>>,
+    primitives: Vec<Primitive>,
+    /// the first vertex merged into each welded point, to name edges by
+    representatives: Vec<usize>,
+}
+
+impl MeshReport {
+    pub fn is_valid(&self) -> bool {
+        self.messages().is_empty()
+    }
+
+    /// A sentence for each kind of problem, saying how many there are and where the first is
+    pub fn messages(&self) -> Vec<String> {
+        let primitive = self.primitive;
+        let mut messages = vec![];
+
+        if self.trailing_values > 0 {
+            messages.push(format!(
+                "{} after the last whole vertex",
+                count(self.trailing_values, "value", "values")
+            ));
+        }
+        if let Some(&vertex) = self.non_finite_vertices.first() {
+            messages.push(format!(
+                "{} with NaN or infinite coordinates, the first being vertex {}",
+                count(self.non_finite_vertices.len(), "vertex", "vertices"),
+                vertex
+            ));
+        }
+        if let Some(normal_values) = self.normal_values {
+            messages.push(format!(
+                "{} normal values for {}, instead of {}",
+                normal_values,
+                count(self.vertex_count, "vertex", "vertices"),
+                self.vertex_count * 3
+            ));
+        }
+        if let Some(&normal) = self.non_finite_normals.first() {
+            messages.push(format!(
+                "{} with NaN or infinite values, the first being normal {}",
+                count(self.non_finite_normals.len(), "normal", "normals"),
+                normal
+            ));
+        }
+        if self.trailing_indices > 0 {
+            messages.push(format!(
+                "{} after the last whole {}",
+                count(self.trailing_indices, "index", "indices"),
+                primitive
+            ));
+        }
+        if let Some(&index) = self.out_of_range_indices.first() {
+            messages.push(format!(
+                "{} past the last of the {}, the first at index {}",
+                count(self.out_of_range_indices.len(), "index", "indices"),
+                count(self.vertex_count, "vertex", "vertices"),
+                index
+            ));
+        }
+        if let Some(&first) = self.degenerate.first() {
+            messages.push(format!(
+                "{} with no {}, the first being {} {}",
+                count(self.degenerate.len(), primitive, &format!("{}s", primitive)),
+                match primitive {
+                    "line" => "length",
+                    _ => "area",
+                },
+                primitive,
+                first
+            ));
+        }
+        if let Some(&first) = self.duplicates.first() {
+            messages.push(format!(
+                "{} repeating an earlier one, the first being {} {}",
+                count(self.duplicates.len(), primitive, &format!("{}s", primitive)),
+                primitive,
+                first
+            ));
+        }
+        if let Some(&[from, to]) = self.non_manifold_edges.first() {
+            messages.push(format!(
+                "{} shared by more than two triangles, the first between vertices {} and {}",
+                count(self.non_manifold_edges.len(), "edge", "edges"),
+                from,
+                to
+            ));
+        }
+        if let Some(&[from, to]) = self.inconsistent_winding.first() {
+            messages.push(format!(
+                "{} between triangles facing opposite ways, the first between vertices {} and {}",
+                count(self.inconsistent_winding.len(), "edge", "edges"),
+                from,
+                to
+            ));
+        }
+
+        messages
+    }
+}
+
+impl Display for MeshReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.messages()[..] {
+            [] => write!(f, "no problems found"),
+            ref messages => write!(f, "{}", messages.join("\n")),
+        }
+    }
+}
+
+fn count(count: usize, singular: &str, plural: &str) -> String {
+    match count {
+        1 => format!("1 {}", singular),
+        _ => format!("{} {}", count, plural),
+    }
+}
+
+pub fn validate_mesh(indices: Option<&[usize]>, vertices: &[f32], normals: &[f32]) -> MeshReport {
+    let checked = check_primitives("triangle", 3, indices, vertices);
+    let mut report = checked.report;
+
+    if normals.len() != report.vertex_count * 3 {
+        report.normal_values = Some(normals.len());
+    }
+    report.non_finite_normals = normals
+        .chunks_exact(3)
+        .take(report.vertex_count)
+        .enumerate()
+        .filter(|(_, normal)| !normal.iter().all(|value| value.is_finite()))
+        .map(|(normal_index, _)| normal_index)
+        .collect();
+
+    for (&(a, b), sides) in &edge_sides(&checked.primitives) {
+        let edge = [checked.representatives[a], checked.representatives[b]];
+
+        match sides[..] {
+            [(_, a_forward), (_, b_forward)] if a_forward == b_forward => {
+                report.inconsistent_winding.push(edge)
+            }
+            [_, _, _, ..] => report.non_manifold_edges.push(edge),
+            _ => {}
+        }
+    }
+
+    // edges come out of the hash map in no particular order
+    report.non_manifold_edges.sort_unstable();
+    report.inconsistent_winding.sort_unstable();
+
+    report
+}
+
+pub fn validate_wireframe(indices: Option<&[usize]>, vertices: &[f32]) -> MeshReport {
+    check_primitives("line", 2, indices, vertices).report
+}
+
+/// Indices, vertices and normals of the triangles that can be drawn, see `Mesh::repair`
+pub fn repair_mesh(
+    indices: Option<&[usize]>,
+    vertices: &[f32],
+    normals: &[f32],
+) -> (Vec<usize>, Vec<f32>, Vec<f32>) {
+    let checked = check_primitives("triangle", 3, indices, vertices);
+    let mut triangles = checked.primitives;
+    let flipped = orient(&mut triangles);
+
+    let (indices, used) = compact(&triangles);
+    let points = &checked.points;
+
+    // the normals given for a flipped triangle face the way it used to wind
+    let mut reoriented = vec![false; points.len()];
+    for (triangle, _) in triangles.iter().zip(&flipped).filter(|(_, &flip)| flip) {
+        for &vertex in &triangle.vertices {
+            reoriented[vertex] = true;
+        }
+    }
+
+    // area weighted, for vertices without a usable normal of their own or of a flipped triangle
+    let mut face_normal_sums = vec![Vector3::zeros(); used.len()];
+    for triangle in indices.chunks_exact(3) {
+        let (a, b, c) = (
+            points[used[triangle[0]]],
+            points[used[triangle[1]]],
+            points[used[triangle[2]]],
+        );
+        let normal = (b - a).cross(&(c - a));
+
+        for &vertex in triangle {
+            face_normal_sums[vertex] += normal;
+        }
+    }
+
+    let mut repaired_vertices = Vec::with_capacity(used.len() * 3);
+    let mut repaired_normals = Vec::with_capacity(used.len() * 3);
+
+    for (&vertex, face_normal_sum) in used.iter().zip(&face_normal_sums) {
+        repaired_vertices.extend_from_slice(&vertices[vertex * 3..vertex * 3 + 3]);
+
+        match normals.get(vertex * 3..vertex * 3 + 3) {
+            Some(normal) if !reoriented[vertex] && normal.iter().all(|value| value.is_finite()) => {
+                repaired_normals.extend_from_slice(normal)
+            }
+            _ => {
+                let normal = face_normal_sum
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(Vector3::z);
+                repaired_normals.extend_from_slice(&[normal.x, normal.y, normal.z]);
+            }
+        }
+    }
+
+    (indices, repaired_vertices, repaired_normals)
+}
+
+/// Indices and vertices of the lines that can be drawn, see `Wireframe::repair`
+pub fn repair_wireframe(indices: Option<&[usize]>, vertices: &[f32]) -> (Vec<usize>, Vec<f32>) {
+    let checked = check_primitives("line", 2, indices, vertices);
+    let (indices, used) = compact(&checked.primitives);
+
+    let repaired_vertices = used
+        .iter()
+        .flat_map(|&vertex| vertices[vertex * 3..vertex * 3 + 3].iter().cloned())
+        .collect();
+
+    (indices, repaired_vertices)
+}
+
+/// Problems with the vertices and indices common to meshes and wireframes, along with the
+/// triangles or lines without any
+fn check_primitives(
+    primitive: &'static str,
+    corner_count: usize,
+    indices: Option<&[usize]>,
+    vertices: &[f32],
+) -> CheckedPrimitives {
+    let points: Vec<Point3<f32>> = vertices
+        .chunks_exact(3)
+        .map(|vertex| Point3::new(vertex[0], vertex[1], vertex[2]))
+        .collect();
+    let finite: Vec<bool> = points
+        .iter()
+        .map(|point| point.coords.iter().all(|value| value.is_finite()))
+        .collect();
+    let vertex_count = points.len();
+
+    let all_vertices: Vec<usize>;
+    let indices = match indices {
+        Some(indices) => indices,
+        None => {
+            all_vertices = (0..vertex_count).collect();
+            &all_vertices
+        }
+    };
+
+    let mut report = MeshReport {
+        primitive,
+        vertex_count,
+        primitive_count: indices.len() / corner_count,
+        trailing_values: vertices.len() % 3,
+        non_finite_vertices: (0..vertex_count)
+            .filter(|&vertex| !finite[vertex])
+            .collect(),
+        trailing_indices: indices.len() % corner_count,
+        ..MeshReport::default()
+    };
+
+    let mut drawable = vec![];
+
+    for (primitive_index, corners) in indices.chunks_exact(corner_count).enumerate() {
+        let out_of_range = (0..corner_count)
+            .filter(|&corner| corners[corner] >= vertex_count)
+            .map(|corner| primitive_index * corner_count + corner);
+        let out_of_range_count = report.out_of_range_indices.len();
+        report.out_of_range_indices.extend(out_of_range);
+
+        // non finite vertices are already reported once each
+        if report.out_of_range_indices.len() == out_of_range_count
+            && corners.iter().all(|&vertex| finite[vertex])
+        {
+            drawable.push((primitive_index, corners));
+        }
+    }
+
+    let finite_points: Vec<Point3<f32>> = points
+        .iter()
+        .zip(&finite)
+        .filter(|(_, &finite)| finite)
+        .map(|(&point, _)| point)
+        .collect();
+    let size = match bounds(&finite_points) {
+        Some((min, max)) => (max - min).norm(),
+        None => 0.0,
+    };
+
+    let corner_points: Vec<Point3<f32>> = drawable
+        .iter()
+        .flat_map(|(_, corners)| corners.iter().map(|&vertex| points[vertex]))
+        .collect();
+    let (welded_points, welded) = weld_points(&corner_points, size);
+
+    // welded points are numbered in the order they're first seen
+    let mut representatives = Vec::with_capacity(welded_points.len());
+    for (&point, &vertex) in welded
+        .iter()
+        .zip(drawable.iter().flat_map(|(_, corners)| corners.iter()))
+    {
+        if point == representatives.len() {
+            representatives.push(vertex);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut primitives = vec![];
+
+    for ((primitive_index, corners), welded) in drawable.iter().zip(welded.chunks(corner_count)) {
+        let mut key = welded.to_vec();
+        key.sort_unstable();
+
+        let merged = key.windows(2).any(|pair| pair[0] == pair[1]);
+        let thin = corner_count == 3 && {
+            let (a, b, c) = (points[corners[0]], points[corners[1]], points[corners[2]]);
+            let longest = na::distance(&a, &b)
+                .max(na::distance(&b, &c))
+                .max(na::distance(&c, &a));
+
+            // twice the area over the longest side is the height of the triangle
+            (b - a).cross(&(c - a)).norm() <= longest * size * WELD_TOLERANCE
+        };
+
+        if merged || thin {
+            report.degenerate.push(*primitive_index);
+        } else if !seen.insert(key) {
+            report.duplicates.push(*primitive_index);
+        } else {
+            primitives.push(Primitive {
+                vertices: corners.to_vec(),
+                welded: welded.to_vec(),
+            });
+        }
+    }
+
+    CheckedPrimitives {
+        report,
+        points,
+        primitives,
+        representatives,
+    }
+}
+
+/// The triangles along each edge between welded points, and whether each runs from the lower to
+/// the higher numbered point
+fn edge_sides(triangles: &[Primitive]) -> HashMap<(usize, usize), Vec<(usize, bool)>> {
+    let mut sides: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        for corner in 0..3 {
+            let (a, b) = (triangle.welded[corner], triangle.welded[(corner + 1) % 3]);
+
+            sides
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push((triangle_index, a < b));
+        }
+    }
+
+    sides
+}
+
+/// Flips triangles to wind the same way as their neighbours across edges shared by only two,
+/// keeping whichever way most of each connected piece already winds. A piece that can't be wound
+/// consistently, like a mobius strip, is left with one mismatch. Returns which were flipped
+fn orient(triangles: &mut [Primitive]) -> Vec<bool> {
+    let mut neighbours: Vec<Vec<(usize, bool)>> = vec![vec![]; triangles.len()];
+
+    for sides in edge_sides(triangles).values() {
+        if let [(a, a_forward), (b, b_forward)] = sides[..] {
+            // running the same way along the edge means exactly one of them is facing backwards
+            neighbours[a].push((b, a_forward == b_forward));
+            neighbours[b].push((a, a_forward == b_forward));
+        }
+    }
+
+    let mut visited = vec![false; triangles.len()];
+    let mut flipped = vec![false; triangles.len()];
+
+    for start in 0..triangles.len() {
+        if visited[start] {
+            continue;
+        }
+
+        visited[start] = true;
+        let mut piece = vec![start];
+        let mut queue = VecDeque::from(vec![start]);
+
+        while let Some(triangle) = queue.pop_front() {
+            for &(neighbour, mismatched) in &neighbours[triangle] {
+                if !visited[neighbour] {
+                    visited[neighbour] = true;
+                    flipped[neighbour] = flipped[triangle] != mismatched;
+                    piece.push(neighbour);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        if piece.iter().filter(|&&triangle| flipped[triangle]).count() * 2 > piece.len() {
+            for &triangle in &piece {
+                flipped[triangle] = !flipped[triangle];
+            }
+        }
+    }
+
+    for (triangle, &flip) in triangles.iter_mut().zip(&flipped) {
+        if flip {
+            triangle.vertices.swap(1, 2);
+            triangle.welded.swap(1, 2);
+        }
+    }
+
+    flipped
+}
+
+/// Indices into only the vertices used, in the order they're first used, along with which of the
+/// given vertices each of them was
+fn compact(primitives: &[Primitive]) -> (Vec<usize>, Vec<usize>) {
+    let mut renumbered: HashMap<usize, usize> = HashMap::new();
+    let mut used = vec![];
+
+    let indices = primitives
+        .iter()
+        .flat_map(|primitive| primitive.vertices.iter())
+        .map(|&vertex| {
+            *renumbered.entry(vertex).or_insert_with(|| {
+                used.push(vertex);
+                used.len() - 1
+            })
+        })
+        .collect();
+
+    (indices, used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TETRAHEDRON_VERTICES: [f32; 12] = [
+        0.0, 0.0, 0.0, //
+        1.0, 0.0, 0.0, //
+        0.0, 1.0, 0.0, //
+        0.0, 0.0, 1.0,
+    ];
+
+    // wound anticlockwise seen from outside
+    const TETRAHEDRON_INDICES: [usize; 12] = [0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3];
+
+    fn tetrahedron_normals() -> Vec<f32> {
+        TETRAHEDRON_VERTICES
+            .chunks_exact(3)
+            .flat_map(|vertex| vertex.iter().map(|value| value - 0.25))
+            .collect()
+    }
+
+    /// Every triangle with its own vertices, as read from an stl, each with its face normal
+    fn unindexed(indices: &[usize]) -> (Vec<f32>, Vec<f32>) {
+        let mut vertices = vec![];
+        let mut normals = vec![];
+
+        for triangle in indices.chunks_exact(3) {
+            let corners: Vec<Vector3<f32>> = triangle
+                .iter()
+                .map(|&vertex| {
+                    Vector3::from_column_slice(&TETRAHEDRON_VERTICES[vertex * 3..vertex * 3 + 3])
+                })
+                .collect();
+            let normal = (corners[1] - corners[0])
+                .cross(&(corners[2] - corners[0]))
+                .normalize();
+
+            for corner in &corners {
+                vertices.extend_from_slice(corner.as_slice());
+                normals.extend_from_slice(normal.as_slice());
+            }
+        }
+
+        (vertices, normals)
+    }
+
+    #[test]
+    fn finds_nothing_wrong_with_a_closed_mesh() {
+        let report = validate_mesh(
+            Some(&TETRAHEDRON_INDICES),
+            &TETRAHEDRON_VERTICES,
+            &tetrahedron_normals(),
+        );
+
+        assert!(report.is_valid());
+        assert_eq!(report.primitive_count, 4);
+        assert_eq!(report.to_string(), "no problems found");
+    }
+
+    #[test]
+    fn welds_unindexed_vertices() {
+        let (vertices, normals) = unindexed(&TETRAHEDRON_INDICES);
+
+        assert!(validate_mesh(None, &vertices, &normals).is_valid());
+    }
+
+    #[test]
+    fn reports_bad_buffers() {
+        let mut vertices = TETRAHEDRON_VERTICES.to_vec();
+        vertices.extend_from_slice(&[f32::NAN, 0.0, 0.0, 1.0]);
+        let mut indices = TETRAHEDRON_INDICES.to_vec();
+        indices.extend_from_slice(&[0, 1, 9, 4, 0, 1, 2]);
+
+        let report = validate_mesh(Some(&indices), &vertices, &[0.0; 6]);
+
+        assert_eq!(report.vertex_count, 5);
+        assert_eq!(report.trailing_values, 1);
+        assert_eq!(report.non_finite_vertices, vec![4]);
+        assert_eq!(report.normal_values, Some(6));
+        assert_eq!(report.out_of_range_indices, vec![14]);
+        assert_eq!(report.trailing_indices, 1);
+        assert_eq!(report.messages().len(), 5);
+    }
+
+    #[test]
+    fn reports_non_finite_normals() {
+        let mut normals = tetrahedron_normals();
+        normals[4] = f32::NAN;
+        normals[11] = f32::NEG_INFINITY;
+
+        let report = validate_mesh(Some(&TETRAHEDRON_INDICES), &TETRAHEDRON_VERTICES, &normals);
+
+        assert_eq!(report.non_finite_normals, vec![1, 3]);
+        assert_eq!(
+            report.messages(),
+            vec!["2 normals with NaN or infinite values, the first being normal 1"]
+        );
+    }
+
+    #[test]
+    fn reports_degenerate_and_repeated_triangles() {
+        let mut indices = TETRAHEDRON_INDICES.to_vec();
+        // a repeated corner and the first triangle the other way around
+        indices.extend_from_slice(&[0, 1, 1, 1, 2, 0]);
+
+        let report = validate_mesh(
+            Some(&indices),
+            &TETRAHEDRON_VERTICES,
+            &tetrahedron_normals(),
+        );
+
+        assert_eq!(report.degenerate, vec![4]);
+        assert_eq!(report.duplicates, vec![5]);
+        assert_eq!(
+            report.messages(),
+            vec![
+                "1 triangle with no area, the first being triangle 4",
+                "1 triangle repeating an earlier one, the first being triangle 5",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_edge_problems() {
+        let mut indices = TETRAHEDRON_INDICES.to_vec();
+        // the last face flipped
+        indices[10] = 3;
+        indices[11] = 2;
+
+        let report = validate_mesh(
+            Some(&indices),
+            &TETRAHEDRON_VERTICES,
+            &tetrahedron_normals(),
+        );
+
+        // edges are named by the vertices in the order they're first used
+        assert_eq!(report.inconsistent_winding, vec![[1, 3], [2, 1], [2, 3]]);
+
+        let mut vertices = TETRAHEDRON_VERTICES.to_vec();
+        vertices.extend_from_slice(&[0.5, -1.0, 0.5]);
+        let mut indices = TETRAHEDRON_INDICES.to_vec();
+        // a fin on the edge between the first two vertices
+        indices.extend_from_slice(&[1, 0, 4]);
+
+        let report = validate_mesh(Some(&indices), &vertices, &[0.0; 15]);
+
+        assert_eq!(report.non_manifold_edges, vec![[0, 1]]);
+    }
+
+    #[test]
+    fn repairs_what_can_be_dropped() {
+        let mut vertices = TETRAHEDRON_VERTICES.to_vec();
+        // an unused vertex
+        vertices.extend_from_slice(&[5.0, 5.0, 5.0]);
+        let mut indices = TETRAHEDRON_INDICES.to_vec();
+        indices.extend_from_slice(&[0, 1, 1, 1, 2, 0, 0, 1, 9]);
+        let mut normals = tetrahedron_normals();
+        normals.extend_from_slice(&[0.0, 0.0, 1.0]);
+
+        let (indices, vertices, normals) = repair_mesh(Some(&indices), &vertices, &normals);
+
+        assert_eq!(indices.len(), 12);
+        assert_eq!(vertices.len(), 12);
+        assert_eq!(normals.len(), 12);
+        assert!(!vertices.contains(&5.0));
+        assert!(validate_mesh(Some(&indices), &vertices, &normals).is_valid());
+    }
+
+    #[test]
+    fn repairs_winding_and_the_normals_of_flipped_triangles() {
+        let mut flipped = TETRAHEDRON_INDICES.to_vec();
+        flipped[10] = 3;
+        flipped[11] = 2;
+        // normals worked out from the winding, so the flipped face's point inwards
+        let (vertices, normals) = unindexed(&flipped);
+
+        let (indices, vertices, normals) = repair_mesh(None, &vertices, &normals);
+
+        assert!(validate_mesh(Some(&indices), &vertices, &normals).is_valid());
+
+        let outwards = Vector3::new(1.0, 1.0, 1.0).normalize();
+        for &vertex in &indices[9..12] {
+            let normal = Vector3::from_column_slice(&normals[vertex * 3..vertex * 3 + 3]);
+            assert_relative_eq!(normal, outwards, epsilon = 1e-6);
+        }
+
+        // the faces that didn't need flipping keep their own normals
+        assert_relative_eq!(
+            Vector3::from_column_slice(&normals[0..3]),
+            Vector3::new(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn repairs_normals_that_cant_be_used() {
+        let mut normals = tetrahedron_normals();
+        normals[0] = f32::INFINITY;
+
+        let (_, _, normals) = repair_mesh(
+            Some(&TETRAHEDRON_INDICES),
+            &TETRAHEDRON_VERTICES,
+            &normals[..9],
+        );
+
+        assert!(normals.iter().all(|value| value.is_finite()));
+        assert_relative_eq!(
+            Vector3::from_column_slice(&normals[9..12]),
+            // the sum of the area weighted normals of the three faces around it
+            Vector3::z(),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn validates_and_repairs_wireframes() {
+        let vertices = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0];
+        let indices = [0, 1, 1, 2, 2, 2, 1, 0];
+
+        let report = validate_wireframe(Some(&indices), &vertices);
+
+        assert_eq!(report.primitive, "line");
+        assert_eq!(report.degenerate, vec![2]);
+        assert_eq!(report.duplicates, vec![3]);
+
+        let (indices, vertices) = repair_wireframe(Some(&indices), &vertices);
+
+        assert_eq!(indices, vec![0, 1, 1, 2]);
+        assert!(validate_wireframe(Some(&indices), &vertices).is_valid());
+    }
+}